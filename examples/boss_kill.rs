@@ -0,0 +1,34 @@
+//! Forecasts the chance of killing a chapter boss on player phase, doubling
+//! with a 75-hit sword against a boss sitting at 32 HP. Two hits aren't
+//! enough, so the kill has to come from a crit.
+
+use parthia::fegame::FEGame;
+use parthia::format::ProbFormat;
+use parthia::simple_calc::{possible_outcomes, CombatStats, OutcomeDist, SpeedDiff};
+
+/// The expected chance of killing the boss.
+const EXPECTED_KILL: f64 = 0.19629;
+
+/// The expected chance of the boss landing a killing crit on the counter.
+const EXPECTED_DEATH: f64 = 0.03060;
+
+fn main() {
+    let hero = CombatStats{
+        dmg: 14, hit: 75, crit: 12, ..Default::default()
+    };
+    let boss = CombatStats{
+        dmg: 9, hit: 60, crit: 5, ..Default::default()
+    };
+
+    let outcomes = possible_outcomes(FEGame::FE7, hero, 20, boss, 32,
+                                     SpeedDiff::AtkDoubles);
+    let prob_kill = outcomes.prob_def_dies();
+    let prob_death = outcomes.prob_atk_dies();
+
+    let format = ProbFormat::default();
+    println!("Chance to kill the boss: {}", format.format(prob_kill));
+    println!("Chance to die: {}", format.format(prob_death));
+
+    assert!((prob_kill - EXPECTED_KILL).abs() < 1e-4);
+    assert!((prob_death - EXPECTED_DEATH).abs() < 1e-4);
+}
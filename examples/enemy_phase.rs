@@ -0,0 +1,50 @@
+//! Computes the survival odds of a tank left in range of three enemies on
+//! enemy phase, each attacking in turn.
+
+use parthia::fegame::FEGame;
+use parthia::format::ProbFormat;
+use parthia::simple_calc::{enemy_phase, CombatStats, EnemyMatchup, OutcomeDist,
+                           SpeedDiff};
+
+/// The expected chance of the tank surviving all three attacks.
+const EXPECTED_SURVIVAL: f64 = 0.97876;
+
+/// The expected HP of the tank after enemy phase, counting death as 0 HP.
+const EXPECTED_HP: f64 = 17.777;
+
+fn main() {
+    let tank = CombatStats{
+        dmg: 8, hit: 85, crit: 0, ..Default::default()
+    };
+    let fighter = EnemyMatchup{
+        enemy: CombatStats{
+            dmg: 9, hit: 55, crit: 3, ..Default::default()
+        },
+        enemy_hp: 30,
+        unit: tank,
+        speed: SpeedDiff::Even,
+    };
+    let archer = EnemyMatchup{
+        enemy: CombatStats{
+            dmg: 6, hit: 70, crit: 0, ..Default::default()
+        },
+        enemy_hp: 24,
+        // archers attack from range, so the counter can't reach
+        unit: CombatStats{
+            cannot_counter: true, ..tank
+        },
+        speed: SpeedDiff::Even,
+    };
+
+    let outcomes = enemy_phase(FEGame::FE8, 34, &[fighter, fighter, archer]);
+    let prob_survive = 1.0 - outcomes.prob_def_dies();
+    let expected_hp: f64 = outcomes.iter()
+        .map(|x| x.prob * x.def_hp as f64)
+        .sum();
+
+    println!("Chance to survive: {}", ProbFormat::default().format(prob_survive));
+    println!("Expected HP remaining: {:.2}", expected_hp);
+
+    assert!((prob_survive - EXPECTED_SURVIVAL).abs() < 1e-4);
+    assert!((expected_hp - EXPECTED_HP).abs() < 1e-2);
+}
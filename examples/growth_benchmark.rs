@@ -0,0 +1,73 @@
+//! Plans a trainee's growth through a branching promotion tree, like Ross's
+//! in *The Sacred Stones*, comparing the Str and Spd they end up with as a
+//! Berserker or a Hero, and the odds of their Str growth coming through as a
+//! Journeyman. The class numbers are trimmed down to the two stats.
+
+use parthia::growth::{Class, GrowthUnit, Stat};
+use parthia::rng::growth_distribution;
+use parthia::stats::StatBlock;
+
+use std::collections::BTreeMap;
+
+/// The expected Str and Spd at level 20 as a Berserker.
+const EXPECTED_BERSERKER: (f64, f64) = (26.5, 20.8);
+
+/// The expected Str and Spd at level 20 as a Hero, whose Str hits its cap.
+const EXPECTED_HERO: (f64, f64) = (25.0, 22.8);
+
+/// The expected chance of gaining at least 5 Str over the 9 Journeyman levels.
+const EXPECTED_STR_ODDS: f64 = 0.5;
+
+/// Makes a class with the given promotion gains and caps.
+fn class(name: &str, gains: &[(Stat, u32)], caps: &[(Stat, u32)],
+         promotions: Vec<Class>) -> Class {
+    Class{
+        name: name.to_string(),
+        bases: StatBlock::default(),
+        mov: 5,
+        unit_types: vec!(),
+        weapon_ranks: BTreeMap::new(),
+        max_level: if promotions.is_empty() { 20 } else { 10 },
+        promote_level: 10,
+        promotion_gains: StatBlock::new(gains),
+        caps: StatBlock::new(caps),
+        promotions,
+    }
+}
+
+fn main() {
+    let berserker = class("Berserker", &[(Stat::Str, 1), (Stat::Spd, 1)],
+                          &[(Stat::Str, 30), (Stat::Spd, 28)], vec!());
+    let hero = class("Hero", &[(Stat::Str, 2), (Stat::Spd, 3)],
+                     &[(Stat::Str, 25), (Stat::Spd, 30)], vec!());
+    let fighter = class("Fighter", &[(Stat::Str, 2), (Stat::Spd, 2)],
+                        &[(Stat::Str, 20), (Stat::Spd, 20)],
+                        vec!(berserker, hero));
+    let journeyman = class("Journeyman", &[],
+                           &[(Stat::Str, 10), (Stat::Spd, 10)],
+                           vec!(fighter));
+
+    let ross = GrowthUnit{
+        base_level: 1,
+        bases: vec![(Stat::Str, 5.0), (Stat::Spd, 3.0)].into_iter().collect(),
+        growths: vec![(Stat::Str, 50), (Stat::Spd, 40)].into_iter().collect(),
+    };
+
+    let branches = ross.compare_branches(&journeyman);
+    for (path, stats) in &branches {
+        println!("{}: {:.1} Str, {:.1} Spd", path.join(" -> "),
+                 stats[&Stat::Str], stats[&Stat::Spd]);
+    }
+    let str_gains = growth_distribution(50, 9);
+    let str_odds: f64 = str_gains.iter().skip(5).sum();
+    println!("Chance of at least 5 Str as a Journeyman: {:.1}%",
+             100.0 * str_odds);
+
+    let check = |stats: &BTreeMap<Stat, f64>, (str, spd): (f64, f64)| {
+        assert!((stats[&Stat::Str] - str).abs() < 1e-9);
+        assert!((stats[&Stat::Spd] - spd).abs() < 1e-9);
+    };
+    check(&branches[0].1, EXPECTED_BERSERKER);
+    check(&branches[1].1, EXPECTED_HERO);
+    assert!((str_odds - EXPECTED_STR_ODDS).abs() < 1e-9);
+}
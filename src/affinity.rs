@@ -0,0 +1,157 @@
+//! Support bonuses: units with a support conversation get bonuses to their
+//! combat stats when fighting near each other. In the GBA games, the bonuses
+//! depend on each unit's affinity and the rank of the support, and every
+//! support in range adds up. Only *The Blazing Blade* and *The Sacred Stones*
+//! are modeled: FE6, the Tellius affinities, the 3DS support ranks, and Three
+//! Houses adjacency bonuses are listed as unsupported by `FEGame::support`.
+
+use crate::calc::CombatSide;
+use crate::fegame::FEGame;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A unit's support affinity, which decides the bonuses they give.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Affinity {
+    Fire,
+    Thunder,
+    Wind,
+    Ice,
+    Dark,
+    Light,
+    Anima,
+}
+
+/// The rank of a support between two units, from lowest to highest.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display,
+         EnumString, EnumIter, Deserialize, Serialize)]
+pub enum SupportRank {
+    C,
+    B,
+    A,
+}
+
+impl SupportRank {
+    /// The number the affinity bonuses are multiplied by at this rank.
+    fn level(&self) -> f64 {
+        match self {
+            SupportRank::C => 1.0,
+            SupportRank::B => 2.0,
+            SupportRank::A => 3.0,
+        }
+    }
+}
+
+/// The combat bonuses a unit gets from their supports.
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize,
+         Serialize)]
+pub struct SupportBonus {
+    /// The bonus to Atk.
+    pub atk: u32,
+
+    /// The bonus to Def and Res.
+    pub prt: u32,
+
+    /// The bonus to hit.
+    pub hit: u32,
+
+    /// The bonus to avoid.
+    pub avoid: u32,
+
+    /// The bonus to crit.
+    pub crit: u32,
+
+    /// The bonus to crit avoid.
+    pub dodge: u32,
+}
+
+impl SupportBonus {
+    /// Returns the side with these bonuses added.
+    pub fn apply(&self, side: CombatSide) -> CombatSide {
+        CombatSide{
+            atk: side.atk + self.atk,
+            prt: side.prt + self.prt,
+            hit: side.hit + self.hit,
+            avoid: side.avoid + self.avoid,
+            crit: side.crit + self.crit,
+            dodge: side.dodge + self.dodge,
+            ..side
+        }
+    }
+}
+
+impl Affinity {
+    /// The bonuses this affinity gives for each level of support rank in
+    /// *The Blazing Blade* and *The Sacred Stones*, in the order Atk, Def, hit,
+    /// avoid, crit, and crit avoid.
+    fn gba_bonus(&self) -> [f64; 6] {
+        match self {
+            Affinity::Fire => [0.5, 0.0, 2.5, 2.5, 2.5, 0.0],
+            Affinity::Thunder => [0.0, 0.5, 0.0, 2.5, 2.5, 2.5],
+            Affinity::Wind => [0.5, 0.0, 2.5, 0.0, 2.5, 2.5],
+            Affinity::Ice => [0.0, 0.5, 2.5, 2.5, 0.0, 2.5],
+            Affinity::Dark => [0.0, 0.0, 2.5, 2.5, 2.5, 2.5],
+            Affinity::Light => [0.5, 0.5, 2.5, 0.0, 2.5, 0.0],
+            Affinity::Anima => [0.5, 0.5, 0.0, 2.5, 0.0, 2.5],
+        }
+    }
+}
+
+impl FEGame {
+    /// Returns the total bonuses a unit with the given affinity gets from
+    /// supports with each of the given partners in range, or `None` if the
+    /// crate doesn't know this game's support rules. Each support adds the
+    /// bonuses of both units' affinities times the rank, and the total is
+    /// rounded down. Only *The Blazing Blade* and *The Sacred Stones* are
+    /// covered so far.
+    pub fn support_bonus(&self, affinity: Affinity,
+                         partners: &[(Affinity, SupportRank)])
+                         -> Option<SupportBonus> {
+        if !matches!(self, FEGame::FE7 | FEGame::FE8) {
+            return None;
+        }
+        let mut total = [0.0; 6];
+        let own = affinity.gba_bonus();
+        for (partner, rank) in partners {
+            let theirs = partner.gba_bonus();
+            for (i, x) in total.iter_mut().enumerate() {
+                *x += (own[i] + theirs[i]) * rank.level();
+            }
+        }
+        let t: Vec<u32> = total.iter().map(|x| x.floor() as u32).collect();
+        Some(SupportBonus{
+            atk: t[0], prt: t[1], hit: t[2], avoid: t[3], crit: t[4],
+            dodge: t[5],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_support_bonus() {
+        let fire = FEGame::FE7.support_bonus(
+            Affinity::Fire, &[(Affinity::Fire, SupportRank::A)]
+        ).unwrap();
+        assert_eq!(fire, SupportBonus{
+            atk: 3, prt: 0, hit: 15, avoid: 15, crit: 15, dodge: 0,
+        });
+        // half points add up before rounding down
+        let partners = [
+            (Affinity::Ice, SupportRank::C),
+            (Affinity::Thunder, SupportRank::C),
+        ];
+        let mixed = FEGame::FE8.support_bonus(Affinity::Fire, &partners)
+            .unwrap();
+        assert_eq!(mixed.atk, 1);
+        assert_eq!(mixed.prt, 1);
+        assert_eq!(mixed.hit, 7);
+        let side = mixed.apply(CombatSide{hit: 80, ..Default::default()});
+        assert_eq!(side.hit, 87);
+        assert_eq!(FEGame::FE14.support_bonus(Affinity::Fire, &[]), None);
+    }
+}
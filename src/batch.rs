@@ -0,0 +1,227 @@
+//! Evaluating many scenarios at once on a pool of threads, for workloads like
+//! servers or optimizers that need more than one forecast at a time. A batch
+//! runs in the background and hands back a `BatchJob`, which reports progress,
+//! can be cancelled, and can either be waited on or awaited as a future. This
+//! doesn't depend on any particular async runtime.
+
+use crate::scenario::Scenario;
+use crate::simple_calc::Outcome;
+
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// The error returned when a batch is cancelled before it finishes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "batch was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// The results of a job and who's waiting on them.
+struct JobState {
+    /// The outcomes of each scenario, filled in as they finish.
+    results: Vec<Option<Vec<Outcome>>>,
+
+    /// The number of workers still running.
+    running: usize,
+
+    /// The waker of the task awaiting the job, if any.
+    waker: Option<Waker>,
+}
+
+/// The state shared between a job and its worker threads.
+struct Shared {
+    /// The results, behind a lock.
+    state: Mutex<JobState>,
+
+    /// Signalled when the last worker stops.
+    finished: Condvar,
+
+    /// The index of the next scenario to start on.
+    next: AtomicUsize,
+
+    /// The number of scenarios finished.
+    done: AtomicUsize,
+
+    /// Whether the job has been cancelled.
+    cancelled: AtomicBool,
+}
+
+/// A batch of scenarios being evaluated in the background.
+pub struct BatchJob {
+    shared: Arc<Shared>,
+    total: usize,
+}
+
+/// Starts evaluating the given scenarios on the given number of threads,
+/// returning immediately. At least one thread is always used.
+pub fn evaluate(scenarios: Vec<Scenario>, threads: usize) -> BatchJob {
+    let total = scenarios.len();
+    let threads = threads.max(1).min(total.max(1));
+    let shared = Arc::new(Shared{
+        state: Mutex::new(JobState{
+            results: vec![None; total],
+            running: threads,
+            waker: None,
+        }),
+        finished: Condvar::new(),
+        next: AtomicUsize::new(0),
+        done: AtomicUsize::new(0),
+        cancelled: AtomicBool::new(false),
+    });
+    let scenarios = Arc::new(scenarios);
+
+    for _ in 0..threads {
+        let shared = Arc::clone(&shared);
+        let scenarios = Arc::clone(&scenarios);
+        thread::spawn(move || {
+            // each worker takes the next scenario nobody has started on
+            while !shared.cancelled.load(Ordering::SeqCst) {
+                let i = shared.next.fetch_add(1, Ordering::SeqCst);
+                if i >= scenarios.len() {
+                    break;
+                }
+                let outcomes = scenarios[i].outcomes();
+                shared.state.lock().unwrap().results[i] = Some(outcomes);
+                shared.done.fetch_add(1, Ordering::SeqCst);
+            }
+
+            let mut state = shared.state.lock().unwrap();
+            state.running -= 1;
+            if state.running == 0 {
+                shared.finished.notify_all();
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+    }
+
+    BatchJob{
+        shared,
+        total,
+    }
+}
+
+impl BatchJob {
+    /// Returns the number of scenarios finished so far and the total number.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.shared.done.load(Ordering::SeqCst), self.total)
+    }
+
+    /// Stops the batch. Scenarios that are already being evaluated still
+    /// finish, but the batch's result will be `Cancelled`.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the outcomes of each scenario, in the order they were given,
+    /// from the finished state.
+    fn collect(&self,
+               state: &mut JobState) -> Result<Vec<Vec<Outcome>>, Cancelled> {
+        if self.shared.cancelled.load(Ordering::SeqCst) {
+            return Err(Cancelled);
+        }
+        Ok(state.results.iter_mut()
+           .map(|x| x.take().unwrap_or_default())
+           .collect())
+    }
+
+    /// Blocks until the batch finishes, returning the outcomes of each
+    /// scenario in the order they were given.
+    pub fn wait(self) -> Result<Vec<Vec<Outcome>>, Cancelled> {
+        let mut state = self.shared.state.lock().unwrap();
+        while state.running > 0 {
+            state = self.shared.finished.wait(state).unwrap();
+        }
+        self.collect(&mut state)
+    }
+}
+
+impl Future for BatchJob {
+    type Output = Result<Vec<Vec<Outcome>>, Cancelled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.running > 0 {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        } else {
+            Poll::Ready(self.collect(&mut state))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fegame::FEGame;
+    use crate::simple_calc::{CombatStats, SpeedDiff};
+    use std::task::Wake;
+
+    fn scenario(def_hp: u32) -> Scenario {
+        Scenario{
+            name: format!("{} HP", def_hp),
+            tags: vec!(),
+            game: FEGame::FE7,
+            atk: CombatStats{dmg: 10, hit: 80, crit: 5, ..Default::default()},
+            atk_hp: 20,
+            def: CombatStats{dmg: 5, hit: 70, crit: 0, ..Default::default()},
+            def_hp,
+            speed: SpeedDiff::AtkDoubles,
+            atk_uses: None,
+            def_uses: None,
+        }
+    }
+
+    /// Wakes the blocked thread when the future can make progress.
+    struct ThreadWaker(thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Runs a future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = Box::pin(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_batch() {
+        let scenarios: Vec<Scenario> = (10..30).map(scenario).collect();
+        let expected: Vec<Vec<Outcome>> = scenarios.iter()
+            .map(|x| x.outcomes())
+            .collect();
+
+        let job = evaluate(scenarios.clone(), 4);
+        assert_eq!(job.progress().1, 20);
+        assert_eq!(job.wait(), Ok(expected.clone()));
+        assert_eq!(block_on(evaluate(scenarios.clone(), 3)), Ok(expected));
+        assert_eq!(evaluate(vec!(), 2).wait(), Ok(vec!()));
+
+        let job = evaluate(scenarios, 2);
+        job.cancel();
+        assert_eq!(block_on(job), Err(Cancelled));
+    }
+}
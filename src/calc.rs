@@ -0,0 +1,239 @@
+//! A single entry point for the common case: two units' battle stats go in and
+//! a full `CombatReport` comes out. This takes care of subtracting the
+//! defender's stats from the attacker's, clamping to what the preview would
+//! show, and working out who doubles, so simple consumers don't have to wire
+//! the other modules together themselves.
+
+use crate::fegame::{CritAvoid, Era, FEGame};
+use crate::preview::RawPreview;
+use crate::report::CombatReport;
+use crate::simple_calc::{CombatStats, SpeedDiff};
+
+use serde::{Deserialize, Serialize};
+
+/// One side of combat as the game's stat screen shows it, with the weapon
+/// already equipped.
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct CombatSide {
+    /// The unit's current HP.
+    pub hp: u32,
+
+    /// The unit's Atk: Str or Mag plus the weapon's might.
+    pub atk: u32,
+
+    /// The Def or Res that applies against the other side's weapon.
+    pub prt: u32,
+
+    /// The unit's hit rate before the other side's avoid.
+    pub hit: u32,
+
+    /// The unit's avoid.
+    pub avoid: u32,
+
+    /// The unit's crit rate before the other side's crit avoid.
+    pub crit: u32,
+
+    /// The unit's crit avoid, usually its Luck.
+    pub dodge: u32,
+
+    /// The unit's attack speed, after weapon weight.
+    pub attack_speed: u32,
+
+    /// Whether the unit's weapon strikes twice.
+    pub is_brave: bool,
+
+    /// Whether the unit has Pursuit, which Jugdral requires to attack twice.
+    /// Other games ignore this.
+    #[serde(default)]
+    pub has_pursuit: bool,
+}
+
+impl FEGame {
+    /// Returns a unit's attack speed from their Spd and their weapon's weight.
+    /// The stat that offsets weight depends on the game: Con on the GBA, Bld in
+    /// Jugdral and *Engage*, and Str elsewhere, with *Three Houses* using a
+    /// fifth of Str. *Gaiden*, *Awakening*, and *Shadows of Valentia* ignore
+    /// weight, so the other two arguments don't matter there.
+    pub fn attack_speed(&self, spd: u32, weapon_weight: u32,
+                        str_or_con_or_bld: u32) -> u32 {
+        let offset = match self {
+            FEGame::FE2 | FEGame::FE13 | FEGame::FE15 => return spd,
+            FEGame::FE16 => str_or_con_or_bld / 5,
+            _ => str_or_con_or_bld,
+        };
+        spd.saturating_sub(weapon_weight.saturating_sub(offset))
+    }
+
+    /// Returns a unit's hit rate before the enemy's avoid, from their Skl (Dex
+    /// in *Three Houses*), Luck, and their weapon's hit, or `None` if the crate
+    /// doesn't know this game's formula. Fractions are rounded down.
+    pub fn hit_rate(&self, skill: u32, luck: u32,
+                    weapon_hit: u32) -> Option<u32> {
+        let from_stats = match self.era() {
+            Era::Gba | Era::Tellius => 2 * skill + luck / 2,
+            _ => match self {
+                FEGame::FE13 | FEGame::FE14 => (3 * skill + luck) / 2,
+                FEGame::FE16 => skill,
+                _ => return None,
+            },
+        };
+        Some(weapon_hit + from_stats)
+    }
+
+    /// Returns a unit's avoid before terrain and other bonuses, from their
+    /// attack speed and Luck, or `None` if the crate doesn't know this game's
+    /// formula. Fractions are rounded down.
+    pub fn avoid(&self, attack_speed: u32, luck: u32) -> Option<u32> {
+        match self.era() {
+            Era::Gba | Era::Tellius => Some(2 * attack_speed + luck),
+            _ => match self {
+                FEGame::FE13 | FEGame::FE14 => {
+                    Some((3 * attack_speed + luck) / 2)
+                }
+                FEGame::FE16 => Some(attack_speed),
+                _ => None,
+            },
+        }
+    }
+
+    /// The lead in attack speed a unit needs to attack twice. In Jugdral, any
+    /// lead will do, but only for units with Pursuit.
+    pub fn doubling_threshold(&self) -> u32 {
+        match self {
+            FEGame::FE4 | FEGame::FE5 => 1,
+            FEGame::FE14 | FEGame::FE15 | FEGame::FE17 => 5,
+            FEGame::Custom(config) => config.doubling_threshold,
+            _ => 4,
+        }
+    }
+
+    /// Returns whether a unit with the given lead in attack speed over its
+    /// opponent attacks twice. The lead is negative if the unit is slower.
+    /// Whether the unit has Pursuit only matters in Jugdral, where units
+    /// without it never attack twice.
+    pub fn doubles(&self, attack_speed_diff: i32, has_pursuit: bool) -> bool {
+        if matches!(self, FEGame::FE4 | FEGame::FE5) && !has_pursuit {
+            return false;
+        }
+        attack_speed_diff >= self.doubling_threshold() as i32
+    }
+
+    /// Returns who doubles given each side's attack speed and whether they
+    /// have Pursuit.
+    pub fn speed_diff(&self, atk: &CombatSide, def: &CombatSide) -> SpeedDiff {
+        let diff = atk.attack_speed as i32 - def.attack_speed as i32;
+        if self.doubles(diff, atk.has_pursuit) {
+            SpeedDiff::AtkDoubles
+        } else if self.doubles(-diff, def.has_pursuit) {
+            SpeedDiff::DefDoubles
+        } else {
+            SpeedDiff::Even
+        }
+    }
+}
+
+/// Returns the stats for one side striking the other in the given game.
+pub(crate) fn strike_stats(game: FEGame, striker: &CombatSide,
+                target: &CombatSide) -> CombatStats {
+    let crit = game.displayed_crit(striker.crit,
+                                   &[CritAvoid::Bonus(target.dodge)]);
+    let preview = game.preview(RawPreview{
+        hit: striker.hit as f64 - target.avoid as f64,
+        crit: crit as f64,
+        dmg: striker.atk as f64 - target.prt as f64,
+        attack_speed: striker.attack_speed as f64,
+    });
+    CombatStats{
+        is_brave: striker.is_brave,
+        crit_dmg: Some(game.crit_damage(striker.atk, target.prt)),
+        ..preview.combat_stats()
+    }
+}
+
+/// Computes the full report for the attacker initiating combat against the
+/// defender in the given game, with no skills.
+pub fn calc(game: FEGame, atk_side: &CombatSide,
+            def_side: &CombatSide) -> CombatReport {
+    let atk = strike_stats(game, atk_side, def_side);
+    let def = strike_stats(game, def_side, atk_side);
+    let speed = game.speed_diff(atk_side, def_side);
+    CombatReport::new(game, atk, atk_side.hp, def, def_side.hp, speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_calc::{possible_outcomes, OutcomeDist};
+
+    #[test]
+    fn test_calc() {
+        let atk = CombatSide{
+            hp: 30, atk: 18, prt: 8, hit: 110, avoid: 30, crit: 10, dodge: 5,
+            attack_speed: 14, is_brave: false, has_pursuit: false,
+        };
+        let def = CombatSide{
+            hp: 25, atk: 14, prt: 6, hit: 80, avoid: 20, crit: 0, dodge: 2,
+            attack_speed: 9, is_brave: false, has_pursuit: false,
+        };
+        let report = calc(FEGame::FE7, &atk, &def);
+        let atk_stats = CombatStats{
+            dmg: 12, hit: 90, crit: 8, crit_dmg: Some(36),
+            ..Default::default()
+        };
+        let def_stats = CombatStats{
+            dmg: 6, hit: 50, crit: 0, crit_dmg: Some(18),
+            ..Default::default()
+        };
+        assert_eq!(report.outcomes, possible_outcomes(
+            FEGame::FE7, atk_stats, 30, def_stats, 25, SpeedDiff::AtkDoubles
+        ));
+        // Fates needs a bigger lead to double
+        let speed = |x| CombatSide{attack_speed: x, ..def};
+        assert_eq!(FEGame::FE14.speed_diff(&atk, &def), SpeedDiff::AtkDoubles);
+        assert_eq!(FEGame::FE14.speed_diff(&speed(13), &def), SpeedDiff::Even);
+        assert_eq!(FEGame::FE14.speed_diff(&speed(4), &def),
+                   SpeedDiff::DefDoubles);
+        assert!(FEGame::FE8.doubles(4, false));
+        assert!(!FEGame::FE8.doubles(3, false));
+        assert!(!FEGame::FE8.doubles(-6, false));
+        // Jugdral units only double with Pursuit, but then any lead will do
+        assert!(!FEGame::FE4.doubles(10, false));
+        assert!(FEGame::FE4.doubles(1, true) && !FEGame::FE4.doubles(0, true));
+        let pursuer = CombatSide{attack_speed: 10, has_pursuit: true, ..def};
+        assert_eq!(FEGame::FE5.speed_diff(&speed(10), &pursuer),
+                   SpeedDiff::Even);
+        assert_eq!(FEGame::FE5.speed_diff(&atk, &pursuer),
+                   SpeedDiff::Even);
+        assert_eq!(FEGame::FE5.speed_diff(&speed(9), &pursuer),
+                   SpeedDiff::DefDoubles);
+        // crits double Atk in Jugdral, even when it can't get through Def,
+        // so two of them kill
+        let wall = CombatSide{prt: 20, ..def};
+        let report = calc(FEGame::FE5, &CombatSide{has_pursuit: true, ..atk},
+                          &wall);
+        assert!(report.outcomes.prob_def_dies() > 0.0);
+        assert_eq!(strike_stats(FEGame::FE5, &atk, &wall).crit_dmg, Some(16));
+    }
+
+    #[test]
+    fn test_attack_speed() {
+        // 13 Wt against 9 Con costs 4 Spd
+        assert_eq!(FEGame::FE7.attack_speed(15, 13, 9), 11);
+        assert_eq!(FEGame::FE7.attack_speed(15, 5, 9), 15);
+        assert_eq!(FEGame::FE7.attack_speed(2, 20, 5), 0);
+        assert_eq!(FEGame::FE13.attack_speed(15, 13, 0), 15);
+        // 20 Str only offsets 4 Wt in Three Houses
+        assert_eq!(FEGame::FE16.attack_speed(15, 10, 20), 9);
+    }
+
+    #[test]
+    fn test_hit_and_avoid() {
+        // an Iron Sword (90 hit) with 12 Skl and 7 Luck
+        assert_eq!(FEGame::FE7.hit_rate(12, 7, 90), Some(117));
+        assert_eq!(FEGame::FE14.hit_rate(12, 7, 90), Some(111));
+        assert_eq!(FEGame::FE7.avoid(11, 7), Some(29));
+        assert_eq!(FEGame::FE13.avoid(11, 7), Some(20));
+        assert_eq!(FEGame::FE16.avoid(11, 7), Some(11));
+        assert_eq!(FEGame::FE5.hit_rate(12, 7, 90), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! Stat caps, the highest each stat can go. Older games cap every unit at the
+//! same values, most games after that take caps from the unit's class, and the
+//! 3DS games and later adjust or replace class caps per character. Level-up
+//! calculators need the caps to pile the chance of going over onto the cap
+//! itself.
+
+use crate::fegame::FEGame;
+use crate::growth::Stat;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Where a game's stat caps come from.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum CapSource {
+    /// Every unit shares the same caps.
+    Universal,
+
+    /// Caps come from the unit's class, as in `growth::Class`.
+    Class,
+
+    /// Caps come from the unit's class, with each character raising or lowering
+    /// them by a fixed amount.
+    ClassAndCharacter,
+
+    /// Each character has their own caps, no matter their class.
+    Character,
+}
+
+impl FEGame {
+    /// Returns where this game's stat caps come from.
+    pub fn cap_source(&self) -> CapSource {
+        match self {
+            FEGame::FE1 | FEGame::FE3 | FEGame::FE11 => CapSource::Universal,
+            FEGame::FE13 | FEGame::FE14 | FEGame::FE17 => {
+                CapSource::ClassAndCharacter
+            }
+            FEGame::FE16 => CapSource::Character,
+            _ => CapSource::Class,
+        }
+    }
+
+    /// Returns the caps shared by every unit of the given tier, if the game has
+    /// them. Games with universal caps share them between every unit, and the
+    /// GBA games give every unpromoted class the same caps.
+    pub fn shared_caps(&self, promoted: bool) -> Option<BTreeMap<Stat, u32>> {
+        let (hp, lck) = match (self, promoted) {
+            (FEGame::FE1, _) | (FEGame::FE3, _) | (FEGame::FE11, _) => (52, 20),
+            (FEGame::FE6, false) | (FEGame::FE7, false)
+                | (FEGame::FE8, false) => (60, 30),
+            _ => return None,
+        };
+        let mut caps: BTreeMap<Stat, u32> = [
+            Stat::Str, Stat::Mag, Stat::Skl, Stat::Spd, Stat::Def, Stat::Res,
+        ].iter().map(|x| (*x, 20)).collect();
+        caps.insert(Stat::HP, hp);
+        caps.insert(Stat::Lck, lck);
+        Some(caps)
+    }
+}
+
+/// Caps a distribution over a stat's value, indexed by the value, by moving the
+/// chance of every value above the cap onto the cap.
+pub fn apply_cap(dist: &[f64], cap: u32) -> Vec<f64> {
+    let cap = cap as usize;
+    if dist.len() <= cap + 1 {
+        return dist.to_vec();
+    }
+    let mut capped = dist[..=cap].to_vec();
+    capped[cap] += dist[cap + 1..].iter().sum::<f64>();
+    capped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_caps() {
+        let caps = FEGame::FE7.shared_caps(false).unwrap();
+        assert_eq!(caps[&Stat::HP], 60);
+        assert_eq!(caps[&Stat::Lck], 30);
+        assert_eq!(caps[&Stat::Spd], 20);
+        // promoted GBA classes each have their own caps
+        assert_eq!(FEGame::FE7.shared_caps(true), None);
+        assert_eq!(FEGame::FE7.cap_source(), CapSource::Class);
+        assert_eq!(FEGame::FE14.cap_source(), CapSource::ClassAndCharacter);
+
+        assert_eq!(apply_cap(&[0.25, 0.5, 0.25], 1), vec![0.25, 0.75]);
+        assert_eq!(apply_cap(&[0.25, 0.5, 0.25], 5), vec![0.25, 0.5, 0.25]);
+    }
+}
@@ -0,0 +1,36 @@
+//! A compact binary encoding for computed results, enabled with the `bincode`
+//! feature. Outcome distributions can get large, and caching them as JSON is
+//! much slower and bulkier than it needs to be. Anything in the crate that
+//! implements `Serialize` and `Deserialize` can be encoded this way.
+
+use serde::{Deserialize, Serialize};
+
+/// Encodes the value in the compact binary format.
+pub fn to_bytes<T: Serialize>(value: &T) -> bincode::Result<Vec<u8>> {
+    bincode::serialize(value)
+}
+
+/// Decodes a value previously encoded with `to_bytes`.
+pub fn from_bytes<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> bincode::Result<T> {
+    bincode::deserialize(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fegame::FEGame;
+    use crate::simple_calc::{possible_outcomes, CombatStats, Outcome, SpeedDiff};
+
+    #[test]
+    fn test_round_trip() {
+        let stats = CombatStats{
+            dmg: 7, hit: 80, crit: 10, ..Default::default()
+        };
+        let outcomes = possible_outcomes(FEGame::FE8, stats, 25, stats, 25,
+                                         SpeedDiff::AtkDoubles);
+        let bytes = to_bytes(&outcomes).unwrap();
+        let decoded: Vec<Outcome> = from_bytes(&bytes).unwrap();
+        assert_eq!(outcomes, decoded);
+        assert!(bytes.len() < serde_json::to_vec(&outcomes).unwrap().len());
+    }
+}
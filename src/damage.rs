@@ -0,0 +1,98 @@
+//! Damage from raw stats. Physical weapons add the wielder's Str to the
+//! weapon's might and are blocked by Def, while magic adds Mag and is blocked
+//! by Res. A few games bend this: *Fates* has weapons that average Str and Mag,
+//! and *Gaiden* has no Mag stat, so spells hit for their might alone.
+
+use crate::fegame::FEGame;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// Which stats a weapon's damage uses.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum DamageType {
+    /// Adds Str and targets Def.
+    Physical,
+
+    /// Adds Mag and targets Res.
+    Magical,
+
+    /// Adds the average of Str and Mag and targets Res, like *Fates*' Levin
+    /// Sword and other magic weapons.
+    Hybrid,
+}
+
+/// A weapon's might and the kind of damage it deals.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub struct WeaponDamage {
+    /// The weapon's might, after effectiveness.
+    pub might: u32,
+
+    /// Which stats the damage uses.
+    pub damage_type: DamageType,
+}
+
+/// The stats of a unit that matter for damage.
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize,
+         Serialize)]
+pub struct DamageStats {
+    /// Strength.
+    pub str: u32,
+
+    /// Magic. *Gaiden* doesn't have this stat.
+    pub mag: u32,
+
+    /// Defense.
+    pub def: u32,
+
+    /// Resistance.
+    pub res: u32,
+}
+
+impl FEGame {
+    /// Returns the attacker's Atk with the given weapon.
+    pub fn atk(&self, atk_stats: &DamageStats, weapon: &WeaponDamage) -> u32 {
+        let from_stats = match weapon.damage_type {
+            DamageType::Physical => atk_stats.str,
+            DamageType::Magical => match self {
+                FEGame::FE2 => 0,
+                _ => atk_stats.mag,
+            },
+            DamageType::Hybrid => (atk_stats.str + atk_stats.mag) / 2,
+        };
+        from_stats + weapon.might
+    }
+
+    /// Returns the damage the attacker's weapon does to the defender, which
+    /// can't go below 0.
+    pub fn damage(&self, atk_stats: &DamageStats, weapon: &WeaponDamage,
+                  def_stats: &DamageStats) -> u32 {
+        let prt = match weapon.damage_type {
+            DamageType::Physical => def_stats.def,
+            DamageType::Magical | DamageType::Hybrid => def_stats.res,
+        };
+        self.atk(atk_stats, weapon).saturating_sub(prt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage() {
+        let mage = DamageStats{str: 4, mag: 12, def: 3, res: 8};
+        let knight = DamageStats{str: 10, mag: 0, def: 14, res: 2};
+        let fire = WeaponDamage{might: 5, damage_type: DamageType::Magical};
+        let iron = WeaponDamage{might: 8, damage_type: DamageType::Physical};
+        assert_eq!(FEGame::FE7.damage(&mage, &fire, &knight), 15);
+        assert_eq!(FEGame::FE7.damage(&knight, &iron, &mage), 15);
+        assert_eq!(FEGame::FE7.damage(&mage, &iron, &knight), 0);
+        // spells ignore the caster in Gaiden
+        assert_eq!(FEGame::FE2.damage(&mage, &fire, &knight), 3);
+        // (4 + 12) / 2 + 9 against 2 Res
+        let levin = WeaponDamage{might: 9, damage_type: DamageType::Hybrid};
+        assert_eq!(FEGame::FE14.damage(&mage, &levin, &knight), 15);
+    }
+}
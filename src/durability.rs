@@ -0,0 +1,272 @@
+//! Weapon durability: most games give weapons a limited number of uses, and
+//! each strike that lands uses one up. Misses are free. When a weapon breaks
+//! partway through combat, the unit's later strikes use whatever they have
+//! next, or nothing at all. Planners need this to know whether a unit's Killer
+//! Bow lasts the chapter, and it changes the odds of combat itself when a
+//! weapon is nearly gone.
+
+use crate::fegame::FEGame;
+use crate::mechanics::Capability;
+use crate::simple_calc::{attack_sequence, side_strike, Branch, CombatEvent,
+                         CombatStats, DefenseProc, Outcome, Side, SpeedDiff};
+use crate::unit::{combat_stats, CombatContext, Combatant};
+
+use serde::{Deserialize, Serialize};
+
+/// A side of combat with a weapon that can break.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct ArmedSide {
+    /// The side's stats with its weapon.
+    pub stats: CombatStats,
+
+    /// The uses the weapon has left, or `None` if it doesn't break.
+    pub uses: Option<u32>,
+
+    /// The side's stats after its weapon breaks, or `None` if it has nothing
+    /// left to attack with.
+    pub fallback: Option<CombatStats>,
+}
+
+/// An outcome of combat that also keeps track of each side's weapon uses.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DurableOutcome {
+    /// The final state and its probability.
+    pub outcome: Outcome,
+
+    /// The attacker's weapon uses left, or `None` if it doesn't break.
+    pub atk_uses: Option<u32>,
+
+    /// The defender's weapon uses left, or `None` if it doesn't break.
+    pub def_uses: Option<u32>,
+}
+
+impl DurableOutcome {
+    /// Returns the given side's weapon uses left.
+    pub fn uses(&self, side: Side) -> Option<u32> {
+        match side {
+            Side::Atk => self.atk_uses,
+            Side::Def => self.def_uses,
+        }
+    }
+
+    /// Returns whether the given side's weapon has broken.
+    pub fn is_broken(&self, side: Side) -> bool {
+        self.uses(side) == Some(0)
+    }
+}
+
+impl Branch for DurableOutcome {
+    fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    fn branch(&self, prob: f64, atk_hp: u32, def_hp: u32,
+              event: CombatEvent) -> DurableOutcome {
+        let used = |side: Side, uses: Option<u32>| match event.landed_by() {
+            Some(striker) if striker == side => {
+                uses.map(|x| x.saturating_sub(1))
+            }
+            _ => uses,
+        };
+        DurableOutcome{
+            outcome: self.outcome.branch(prob, atk_hp, def_hp, event),
+            atk_uses: used(Side::Atk, self.atk_uses),
+            def_uses: used(Side::Def, self.def_uses),
+        }
+    }
+
+    fn scale(&self, factor: f64) -> DurableOutcome {
+        DurableOutcome{
+            outcome: self.outcome.scale(factor),
+            ..*self
+        }
+    }
+
+    fn switch(&self) -> DurableOutcome {
+        DurableOutcome{
+            outcome: self.outcome.switch(),
+            atk_uses: self.def_uses,
+            def_uses: self.atk_uses,
+        }
+    }
+
+    fn merge(branches: Vec<DurableOutcome>) -> Vec<DurableOutcome> {
+        let mut merged: Vec<DurableOutcome> = vec!();
+        for branch in branches.into_iter().filter(|x| x.outcome.prob != 0.0) {
+            let same = merged.iter_mut().find(|x| {
+                x.outcome.atk_hp == branch.outcome.atk_hp
+                    && x.outcome.def_hp == branch.outcome.def_hp
+                    && x.atk_uses == branch.atk_uses
+                    && x.def_uses == branch.def_uses
+            });
+            match same {
+                Some(x) => x.outcome.prob += branch.outcome.prob,
+                None => merged.push(branch),
+            }
+        }
+        merged
+    }
+}
+
+/// Returns the possible outcomes of combat, with each side's weapon uses, using
+/// the given game's rules. In games without durability, weapons never break.
+pub fn durable_outcomes(game: FEGame, atk: &ArmedSide, atk_hp: u32,
+                        def: &ArmedSide, def_hp: u32,
+                        speed: SpeedDiff) -> Vec<DurableOutcome> {
+    let has_durability = game.has_capability(Capability::WeaponDurability);
+    let initial = vec!(DurableOutcome{
+        outcome: Outcome{prob: 1.0, atk_hp, def_hp},
+        atk_uses: atk.uses.filter(|_| has_durability),
+        def_uses: def.uses.filter(|_| has_durability),
+    });
+    durable_outcomes_after_combat(game, atk, def, speed, initial)
+}
+
+/// Like `durable_outcomes`, but starts from an existing list of possible
+/// states, so the uses left after one combat carry into the next. The uses in
+/// `atk` and `def` are ignored in favor of the ones in each state.
+///
+/// Each attack uses the weapon the side has when it starts, and a brave weapon
+/// that breaks after its first strike doesn't get its second. Strikes from a
+/// skill like Astra all happen before the weapon's uses are checked again. The
+/// attacks follow `simple_calc`'s order, which doesn't change when a weapon
+/// breaks, so a defender who can't counter with their weapon doesn't start
+/// countering with their fallback.
+pub fn durable_outcomes_after_combat(game: FEGame, atk: &ArmedSide,
+                                     def: &ArmedSide, speed: SpeedDiff,
+                                     initial: Vec<DurableOutcome>)
+                                     -> Vec<DurableOutcome> {
+    // the target's crit immunity doesn't depend on their weapon
+    let atk_fallback = atk.fallback.map(|x| x.against(&def.stats));
+    let def_fallback = def.fallback.map(|x| x.against(&atk.stats));
+    attack_sequence(atk.stats, def.stats, speed).into_iter()
+        .fold(initial, |states, (side, with_weapon, guard)| {
+            let without = match side {
+                Side::Atk => atk_fallback,
+                Side::Def => def_fallback,
+            };
+            let strikes = without.map_or(1, |x| x.strikes_per_attack())
+                .max(with_weapon.strikes_per_attack());
+            (0..strikes).fold(states, |states, n| {
+                let (broken, whole): (Vec<_>, Vec<_>) = states.into_iter()
+                    .partition(|x| x.is_broken(side));
+                let mut new_states = strike_with(game, Some(with_weapon), guard,
+                                                 side, whole, n);
+                new_states.extend(strike_with(game, without, guard, side,
+                                              broken, n));
+                DurableOutcome::merge(new_states)
+            })
+        })
+}
+
+/// Derives both sides of combat from the given units as in
+/// `unit::combat_stats`, with each side's weapon uses taken from the weapon.
+/// Neither side has a weapon to fall back on.
+pub fn armed_sides(game: FEGame, atk: &Combatant, def: &Combatant,
+                   context: &CombatContext) -> (ArmedSide, ArmedSide,
+                                                SpeedDiff) {
+    let (atk_stats, def_stats, speed) = combat_stats(game, atk, def, context);
+    let armed = |stats, uses| ArmedSide{stats, uses, fallback: None};
+    (armed(atk_stats, atk.weapon.uses()), armed(def_stats, def.weapon.uses()),
+     speed)
+}
+
+/// Returns the states after the given side makes the given strike (counting
+/// from 0) of an attack with the given stats against a target with the given
+/// defensive skill, or the same states if they have nothing to strike with or
+/// the attack has no such strike.
+fn strike_with(game: FEGame, stats: Option<CombatStats>,
+               target_guard: Option<DefenseProc>, side: Side,
+               states: Vec<DurableOutcome>, n: usize) -> Vec<DurableOutcome> {
+    match stats {
+        Some(stats) if n < stats.strikes_per_attack()
+            && !(side == Side::Def && stats.cannot_counter) => {
+            side_strike(game, side, &stats, target_guard, states, n)
+        }
+        _ => states,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::damage::DamageType;
+    use crate::rng::RNSystem;
+    use crate::round::RoundBuilder;
+    use crate::simple_calc::OutcomeDist;
+    use crate::unit::GbaUnit;
+    use crate::weapon::{Range, WeaponData, WeaponRank, WeaponType};
+
+    #[test]
+    fn test_durability() {
+        let bow = CombatStats{dmg: 5, hit: 100, crit: 0, ..Default::default()};
+        let armed = ArmedSide{stats: bow, uses: Some(1), fallback: None};
+        let unarmed = ArmedSide{
+            stats: CombatStats{hit: 0, ..Default::default()},
+            uses: None,
+            fallback: None,
+        };
+        // the bow breaks on the first strike, so the follow-up never happens
+        let outcomes = durable_outcomes(FEGame::FE7, &armed, 20, &unarmed, 20,
+                                        SpeedDiff::AtkDoubles);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].outcome.def_hp, 15);
+        assert_eq!(outcomes[0].atk_uses, Some(0));
+        // a backup weapon takes over
+        let backup = ArmedSide{
+            fallback: Some(CombatStats{dmg: 2, ..bow}), ..armed
+        };
+        let outcomes = durable_outcomes(FEGame::FE7, &backup, 20, &unarmed, 20,
+                                        SpeedDiff::AtkDoubles);
+        assert_eq!(outcomes[0].outcome.def_hp, 13);
+        // misses don't use anything up
+        let coin_flip = ArmedSide{
+            stats: CombatStats{hit: 50, ..bow}, uses: Some(5), fallback: None
+        };
+        let outcomes = durable_outcomes(FEGame::FE1, &coin_flip, 20, &unarmed,
+                                        20, SpeedDiff::Even);
+        let missed: f64 = outcomes.iter()
+            .filter(|x| x.atk_uses == Some(5))
+            .map(|x| x.outcome.prob)
+            .sum();
+        assert!((missed - 0.5).abs() < 1e-9);
+        // Fates weapons don't break
+        let outcomes = durable_outcomes(FEGame::FE14, &armed, 20, &unarmed, 20,
+                                        SpeedDiff::AtkDoubles);
+        let plain: Vec<Outcome> = outcomes.iter().map(|x| x.outcome).collect();
+        assert_eq!(plain.prob_def_hp_at_most(10), 1.0);
+        assert_eq!(outcomes[0].atk_uses, None);
+    }
+
+    #[test]
+    fn test_durable_paths() {
+        // rounds stop a side's strikes once its weapon breaks
+        let round = RoundBuilder::new()
+            .atk_hp(20).def_hp(20)
+            .attacker(100, 0, 5).defender(0, 0, 5)
+            .atk_repeat(true, false)
+            .build().unwrap();
+        let outcomes = round.durable_outcomes(RNSystem::TwoRN, Some(1), None);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].outcome.def_hp, 15);
+        let again = round.durable_outcomes_after(RNSystem::TwoRN, outcomes);
+        assert_eq!(again[0].outcome.def_hp, 15);
+        assert_eq!(round.durable_outcomes(RNSystem::TwoRN, None, None)[0]
+                   .outcome.def_hp, 10);
+
+        // units bring their weapons' uses along
+        let javelin = WeaponData{
+            might: 6, hit: 65, crit: 0, weight: 11, range: Range::new(1, 2),
+            uses: Some(20), rank: Some(WeaponRank::E),
+            weapon_type: WeaponType::Lance, damage_type: DamageType::Physical,
+            is_brave: false, reverses_triangle: false,
+            effective_against: vec!(),
+        };
+        let soldier = GbaUnit{hp: 20, ..Default::default()};
+        let atk = Combatant{unit: &soldier, weapon: &javelin, item: None};
+        let (atk_side, def_side, _) = armed_sides(FEGame::FE7, &atk, &atk,
+                                                  &CombatContext::melee());
+        assert_eq!(atk_side.uses, Some(20));
+        assert_eq!(def_side.fallback, None);
+    }
+}
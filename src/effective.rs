@@ -0,0 +1,51 @@
+//! Effective damage: weapons like bows against fliers or armorslayers against
+//! knights multiply their might against the units they're made to beat. How
+//! much they multiply it by depends on the game, and getting it wrong throws
+//! off the damage by a lot.
+
+use crate::fegame::FEGame;
+use crate::unit::UnitType;
+
+impl FEGame {
+    /// The number effective weapons multiply their might by in this game.
+    /// Some weapons have their own multiplier, which callers need to apply
+    /// themselves.
+    pub fn effectiveness_multiplier(&self) -> u32 {
+        match self {
+            // the Tellius numbers haven't been checked against the games
+            FEGame::FE9 | FEGame::FE10 => 2,
+            FEGame::Custom(config) => config.effectiveness_multiplier,
+            _ => 3,
+        }
+    }
+
+    /// Returns a weapon's might against a target, given the unit types the
+    /// weapon is effective against and the target's types. Might is multiplied
+    /// once if any type matches, no matter how many do.
+    pub fn effective_might(&self, might: u32, effective_against: &[UnitType],
+                           target: &[UnitType]) -> u32 {
+        if effective_against.iter().any(|x| target.contains(x)) {
+            might * self.effectiveness_multiplier()
+        } else {
+            might
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_might() {
+        let bow = [UnitType::Flier];
+        let pegasus = [UnitType::Flier];
+        let falcoknight = [UnitType::Flier, UnitType::Cavalry];
+        assert_eq!(FEGame::FE8.effective_might(6, &bow, &pegasus), 18);
+        assert_eq!(FEGame::FE10.effective_might(6, &bow, &pegasus), 12);
+        let both = [UnitType::Flier, UnitType::Cavalry];
+        assert_eq!(FEGame::FE8.effective_might(6, &both, &falcoknight), 18);
+        assert_eq!(FEGame::FE8.effective_might(6, &bow, &[UnitType::Armored]),
+                   6);
+    }
+}
@@ -0,0 +1,220 @@
+//! Calculations for boss abuse: attacking a boss that sits on a healing throne
+//! for turn after turn to farm EXP, without killing it before you're ready.
+//! The risks are the boss dying early, which ends the farming, and the farming
+//! unit dying, which ends much more than that.
+//!
+//! Each turn is the farming unit attacking the boss on player phase, the boss
+//! regenerating at the start of enemy phase, and optionally the boss attacking
+//! back on enemy phase. The unit and the boss carry their HP from one turn to
+//! the next, so this is a Markov chain over both of their HPs.
+
+use crate::fegame::FEGame;
+use crate::simple_calc::{possible_outcomes, CombatStats, Outcome, OutcomeDist,
+                         SpeedDiff};
+
+use serde::{Deserialize, Serialize};
+
+/// Returns the EXP a GBA unit gets from a combat that doesn't kill, given the
+/// unit's and the enemy's levels (counting promoted levels as 20 higher) and
+/// whether the unit did any damage. Units that do no damage only get 1 EXP.
+pub fn gba_combat_exp(unit_level: u32, enemy_level: u32,
+                      did_damage: bool) -> u32 {
+    if did_damage {
+        ((31 + enemy_level).saturating_sub(unit_level) / 3).max(1)
+    } else {
+        1
+    }
+}
+
+/// A plan to farm EXP from a boss.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BossAbuse {
+    /// The game being played.
+    pub game: FEGame,
+
+    /// The farming unit's stats when attacking the boss.
+    pub unit: CombatStats,
+
+    /// The farming unit's HP at the start.
+    pub unit_hp: u32,
+
+    /// The farming unit's max HP, which caps healing.
+    pub unit_max_hp: u32,
+
+    /// The boss's stats when fighting the unit.
+    pub boss: CombatStats,
+
+    /// The boss's HP at the start.
+    pub boss_hp: u32,
+
+    /// The boss's max HP, which caps regeneration.
+    pub boss_max_hp: u32,
+
+    /// The speed differential, with the unit as the attacker.
+    pub speed: SpeedDiff,
+
+    /// The percentage of max HP the boss regenerates every turn. Thrones and
+    /// gates in the GBA games heal 20%.
+    pub regen_percent: u32,
+
+    /// The HP the unit heals every turn, from a healer or terrain.
+    pub unit_heal: u32,
+
+    /// The speed differential when the boss attacks the unit on enemy phase,
+    /// with the boss as the attacker, or `None` if the boss can't reach.
+    pub enemy_phase: Option<SpeedDiff>,
+
+    /// The EXP the unit gets from a combat where they damage the boss.
+    pub exp_damage: u32,
+
+    /// The EXP the unit gets from a combat where they don't.
+    pub exp_no_damage: u32,
+}
+
+/// What to expect from farming a boss for some number of turns.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FarmingResult {
+    /// The expected EXP gained, counting nothing from the combat that kills
+    /// the boss.
+    pub expected_exp: f64,
+
+    /// The probability the boss dies before farming is done.
+    pub prob_boss_dies: f64,
+
+    /// The probability the farming unit dies.
+    pub prob_unit_dies: f64,
+}
+
+impl BossAbuse {
+    /// Returns the possible states after a single combat, along with the
+    /// expected EXP the unit earned in it. The unit is the attacker in the
+    /// states, so `boss_attacks` means the sides are switched for this combat.
+    fn combat(&self, states: Vec<Outcome>, boss_attacks: Option<SpeedDiff>)
+              -> (Vec<Outcome>, f64) {
+        let mut exp = 0.0;
+        let mut new_states = vec!();
+        for state in states {
+            if state.atk_hp == 0 || state.def_hp == 0 {
+                new_states.push(state);
+                continue;
+            }
+            let outcomes: Vec<Outcome> = match boss_attacks {
+                None => possible_outcomes(self.game, self.unit, state.atk_hp,
+                                          self.boss, state.def_hp, self.speed),
+                Some(speed) => possible_outcomes(
+                    self.game, self.boss, state.def_hp,
+                    self.unit, state.atk_hp, speed
+                ).iter().map(|x| x.switch()).collect(),
+            };
+            for outcome in outcomes {
+                let prob = state.prob * outcome.prob;
+                if outcome.atk_hp > 0 && outcome.def_hp > 0 {
+                    exp += prob * if outcome.def_hp < state.def_hp {
+                        self.exp_damage as f64
+                    } else {
+                        self.exp_no_damage as f64
+                    };
+                }
+                new_states.push(Outcome{prob, ..outcome});
+            }
+        }
+        (Outcome::collect(new_states), exp)
+    }
+
+    /// Returns the states after the given healing for the unit and the boss,
+    /// which only applies to the living.
+    fn heal(&self, states: Vec<Outcome>, unit_heal: u32,
+            boss_heal: u32) -> Vec<Outcome> {
+        let heal = |hp: u32, amount: u32, max_hp: u32| if hp == 0 {
+            0
+        } else {
+            hp.saturating_add(amount).min(max_hp.max(hp))
+        };
+        Outcome::collect(states.into_iter().map(|x| Outcome{
+            prob: x.prob,
+            atk_hp: heal(x.atk_hp, unit_heal, self.unit_max_hp),
+            def_hp: heal(x.def_hp, boss_heal, self.boss_max_hp),
+        }).collect())
+    }
+
+    /// Simulates farming the boss for the given number of turns.
+    pub fn farm(&self, turns: u32) -> FarmingResult {
+        let boss_regen = self.boss_max_hp * self.regen_percent / 100;
+        let mut states = vec!(Outcome{
+            prob: 1.0,
+            atk_hp: self.unit_hp,
+            def_hp: self.boss_hp,
+        });
+        let mut expected_exp = 0.0;
+        for turn in 0..turns {
+            if turn > 0 {
+                states = self.heal(states, self.unit_heal, 0);
+            }
+            let (after, exp) = self.combat(states, None);
+            expected_exp += exp;
+            states = self.heal(after, 0, boss_regen);
+            if let Some(speed) = self.enemy_phase {
+                let (after, exp) = self.combat(states, Some(speed));
+                expected_exp += exp;
+                states = after;
+            }
+        }
+
+        FarmingResult{
+            expected_exp,
+            prob_boss_dies: states.prob_def_dies(),
+            prob_unit_dies: states.prob_atk_dies(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gba_exp() {
+        assert_eq!(gba_combat_exp(5, 10, true), 12);
+        assert_eq!(gba_combat_exp(5, 10, false), 1);
+        // high-level units still get at least 1 EXP
+        assert_eq!(gba_combat_exp(40, 5, true), 1);
+    }
+
+    #[test]
+    fn test_boss_abuse() {
+        let abuse = BossAbuse{
+            game: FEGame::FE7,
+            unit: CombatStats{dmg: 5, hit: 100, crit: 0, ..Default::default()},
+            unit_hp: 20,
+            unit_max_hp: 20,
+            boss: CombatStats{dmg: 0, hit: 100, crit: 0, ..Default::default()},
+            boss_hp: 40,
+            boss_max_hp: 40,
+            speed: SpeedDiff::Even,
+            regen_percent: 10,
+            unit_heal: 0,
+            enemy_phase: None,
+            exp_damage: 10,
+            exp_no_damage: 1,
+        };
+        // the boss loses 5 and regains 4 each turn, so it lasts a while
+        let result = abuse.farm(10);
+        assert_eq!(result.expected_exp, 100.0);
+        assert_eq!(result.prob_boss_dies, 0.0);
+        assert_eq!(result.prob_unit_dies, 0.0);
+
+        // without regen, the boss dies on the eighth turn and the last combat
+        // gives no EXP
+        let result = BossAbuse{regen_percent: 0, ..abuse}.farm(10);
+        assert_eq!(result.expected_exp, 70.0);
+        assert_eq!(result.prob_boss_dies, 1.0);
+
+        // a boss that hits back on enemy phase kills the unit eventually
+        let result = BossAbuse{
+            boss: CombatStats{dmg: 4, hit: 100, crit: 0, ..Default::default()},
+            enemy_phase: Some(SpeedDiff::Even),
+            ..abuse
+        }.farm(3);
+        assert_eq!(result.prob_unit_dies, 1.0);
+    }
+}
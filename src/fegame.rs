@@ -5,10 +5,14 @@
 //! hit rate systems are dealt with by the `rng` module but encapsulated here as
 //! well.
 
-use crate::rng::RNSystem;
+use crate::preview::Rounding;
+use crate::rng::{CritModel, RNSystem};
 use strum_macros::{Display, EnumString, EnumIter};
 use serde::{Deserialize, Serialize};
 
+// custom rulesets are stored inline, like custom RN systems, so games stay
+// Copy
+#[allow(clippy::large_enum_variant)]
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
          Deserialize, Serialize)]
 pub enum FEGame {
@@ -26,31 +30,487 @@ pub enum FEGame {
     FE12,
     FE13,
     FE14,
+    /// *Shadows of Valentia*, which also parses from "SoV".
+    #[strum(to_string = "FE15", serialize = "SoV")]
+    #[serde(alias = "SoV")]
     FE15,
-    SoV,
+    /// *Three Houses*.
+    FE16,
+    /// *Engage*.
+    FE17,
+    /// A ruleset that isn't one of the games, like a ROM hack that mixes rules
+    /// from different games. Rules that aren't in the config follow its era
+    /// where the crate groups them by era, and otherwise use the most common
+    /// rule or are treated as unknown. This can't be parsed from a string, and
+    /// iterating over the games skips it.
+    #[strum(disabled)]
+    Custom(MechanicsConfig),
 }
 
 
 impl FEGame {
+    /// The game's English title, without the "Fire Emblem" in front. Games
+    /// that weren't released in English use their usual fan translations.
+    pub fn title(&self) -> &'static str {
+        match self {
+            FEGame::FE1 => "Shadow Dragon and the Blade of Light",
+            FEGame::FE2 => "Gaiden",
+            FEGame::FE3 => "Mystery of the Emblem",
+            FEGame::FE4 => "Genealogy of the Holy War",
+            FEGame::FE5 => "Thracia 776",
+            FEGame::FE6 => "The Binding Blade",
+            FEGame::FE7 => "The Blazing Blade",
+            FEGame::FE8 => "The Sacred Stones",
+            FEGame::FE9 => "Path of Radiance",
+            FEGame::FE10 => "Radiant Dawn",
+            FEGame::FE11 => "Shadow Dragon",
+            FEGame::FE12 => "New Mystery of the Emblem",
+            FEGame::FE13 => "Awakening",
+            FEGame::FE14 => "Fates",
+            FEGame::FE15 => "Shadows of Valentia",
+            FEGame::FE16 => "Three Houses",
+            FEGame::FE17 => "Engage",
+            FEGame::Custom(_) => "Custom",
+        }
+    }
+
+    /// The year the game first came out, in Japan for most of the series, or
+    /// `None` for custom rulesets.
+    pub fn release_year(&self) -> Option<u32> {
+        Some(match self {
+            FEGame::FE1 => 1990,
+            FEGame::FE2 => 1992,
+            FEGame::FE3 => 1994,
+            FEGame::FE4 => 1996,
+            FEGame::FE5 => 1999,
+            FEGame::FE6 => 2002,
+            FEGame::FE7 => 2003,
+            FEGame::FE8 => 2004,
+            FEGame::FE9 => 2005,
+            FEGame::FE10 => 2007,
+            FEGame::FE11 => 2008,
+            FEGame::FE12 => 2010,
+            FEGame::FE13 => 2012,
+            FEGame::FE14 => 2015,
+            FEGame::FE15 => 2017,
+            FEGame::FE16 => 2019,
+            FEGame::FE17 => 2023,
+            FEGame::Custom(_) => return None,
+        })
+    }
+
+    /// The generation of engines the game belongs to. Games from the same era
+    /// share most of their rules, so mechanics can fall back on the era
+    /// instead of listing every game.
+    pub fn era(&self) -> Era {
+        match self {
+            FEGame::FE1 | FEGame::FE2 | FEGame::FE3 | FEGame::FE4
+                | FEGame::FE5 => Era::Kaga,
+            FEGame::FE6 | FEGame::FE7 | FEGame::FE8 => Era::Gba,
+            FEGame::FE9 | FEGame::FE10 => Era::Tellius,
+            FEGame::FE11 | FEGame::FE12 => Era::Ds,
+            FEGame::FE13 | FEGame::FE14 | FEGame::FE15 => Era::ThreeDs,
+            FEGame::FE16 | FEGame::FE17 => Era::Switch,
+            FEGame::Custom(config) => config.era,
+        }
+    }
+
+    /// The console the game first came out on, or `None` for custom rulesets.
+    pub fn platform(&self) -> Option<Platform> {
+        Some(match self {
+            FEGame::FE1 | FEGame::FE2 => Platform::Famicom,
+            FEGame::FE3 | FEGame::FE4 | FEGame::FE5 => Platform::SuperFamicom,
+            FEGame::FE6 | FEGame::FE7 | FEGame::FE8 => Platform::GameBoyAdvance,
+            FEGame::FE9 => Platform::GameCube,
+            FEGame::FE10 => Platform::Wii,
+            FEGame::FE11 | FEGame::FE12 => Platform::DS,
+            FEGame::FE13 | FEGame::FE14 | FEGame::FE15 => Platform::ThreeDS,
+            FEGame::FE16 | FEGame::FE17 => Platform::Switch,
+            FEGame::Custom(_) => return None,
+        })
+    }
+
     /// Converts listed hit, what the game tells you the hit rate is, to true
     /// hit, the actual hit probability. Most of the games lie to you about
-    /// this: the full details are in the `rng` module.
+    /// this: the full details are in the `rng` module. The listed hit is
+    /// clamped to `hit_bounds` first.
     pub fn true_hit(&self, listed_hit: u32) -> f64 {
+        self.rn_system().true_hit(self.clamp_hit(listed_hit))
+    }
+
+    /// The lowest and highest hit rates this game allows. FE5 never lets hit go
+    /// below 1 or above 99, so nothing listed is ever certain; the other games
+    /// allow anything from 0 to 100. Strikes that are guaranteed to land, like
+    /// `CombatStats::always_hits` or the `AlwaysHit` RN system, don't go
+    /// through a listed hit and aren't clamped.
+    pub fn hit_bounds(&self) -> (u32, u32) {
+        match self {
+            FEGame::FE5 => (1, 99),
+            FEGame::Custom(config) => config.hit_bounds,
+            _ => (0, 100),
+        }
+    }
+
+    /// Clamps a hit rate to the bounds this game allows.
+    pub fn clamp_hit(&self, listed_hit: u32) -> u32 {
+        let (min, max) = self.hit_bounds();
+        listed_hit.clamp(min, max)
+    }
+
+    /// Converts listed crit to the true chance that a strike that hits also
+    /// crits.
+    pub fn true_crit(&self, listed_crit: u32) -> f64 {
+        self.rn_system().true_crit(listed_crit)
+    }
+
+    /// Returns the probability, as a number between 0 and 1, that a strike
+    /// with the given listed hit and crit lands as a crit, using the given
+    /// model for how the crit roll relates to the hit roll.
+    pub fn prob_crit(&self, listed_hit: u32, listed_crit: u32,
+                     model: CritModel) -> f64 {
+        self.rn_system().prob_crit(self.clamp_hit(listed_hit), listed_crit,
+                                   model)
+    }
+
+    /// The RN system this game uses for hit rates. The system doesn't know
+    /// about the game's `hit_bounds`, so clamp listed hits with `clamp_hit`
+    /// before using it directly.
+    pub fn rn_system(&self) -> RNSystem {
         match self {
             FEGame::FE1 | FEGame::FE2 | FEGame::FE3 | FEGame::FE4 |
-             FEGame::FE5 => RNSystem::OneRN.true_hit(listed_hit),
-            FEGame::FE14 | FEGame::SoV =>
-                RNSystem::FatesRN.true_hit(listed_hit),
-            _ => RNSystem::TwoRN.true_hit(listed_hit)
+             FEGame::FE5 => RNSystem::OneRN,
+            FEGame::FE16 => RNSystem::OneRN,
+            FEGame::FE14 | FEGame::FE15 | FEGame::FE17 => RNSystem::FatesRN,
+            FEGame::Custom(config) => config.rn_system,
+            _ => RNSystem::TwoRN
+        }
+    }
+
+    /// Returns the rules this game uses for the basic combat mechanics.
+    pub fn rules(&self) -> GameRules {
+        match self {
+            // Luck doesn't cut into crits in FE1. Its crits also only come
+            // from weapons, which the caller has to account for in the crit
+            // rate it gives.
+            FEGame::FE1 => GameRules{
+                crit_damage: CritDamage::Triple,
+                crit_avoid: false,
+            },
+            FEGame::FE2 | FEGame::FE3 => GameRules{
+                crit_damage: CritDamage::Triple,
+                crit_avoid: true,
+            },
+            // Jugdral: crits double Atk before the defender's Def is subtracted,
+            // and the defender's Luck doesn't lower the crit rate
+            FEGame::FE4 | FEGame::FE5 => GameRules{
+                crit_damage: CritDamage::DoubleAtk,
+                crit_avoid: false,
+            },
+            FEGame::Custom(config) => config.rules,
+            _ => GameRules::default()
+        }
+    }
+
+    /// Returns the rules this game uses that a custom ruleset can change.
+    pub fn mechanics(&self) -> MechanicsConfig {
+        match self {
+            FEGame::Custom(config) => *config,
+            _ => MechanicsConfig{
+                era: self.era(),
+                rn_system: self.rn_system(),
+                hit_bounds: self.hit_bounds(),
+                rules: self.rules(),
+                doubling_threshold: self.doubling_threshold(),
+                effectiveness_multiplier: self.effectiveness_multiplier(),
+                preview_rounding: self.preview_rounding(),
+            },
         }
     }
 
     /// Computes critical damage: this is done by doubling Atk in FE4 and FE5,
     /// but done by tripling damage (Atk - Def) in the other games.
     pub fn crit_damage(&self, atk: u32, def: u32) -> u32 {
+        self.rules().crit_damage.damage(atk, def)
+    }
+
+    /// Totals up the crit avoid (dodge) from the given sources using this
+    /// game's stacking rules, returning `None` if the unit can't be crit at
+    /// all. Flat bonuses add together, except in games like FE4 and FE5 where
+    /// crit rate isn't reduced by the defender's stats and only negation
+    /// matters.
+    pub fn total_crit_avoid(&self, sources: &[CritAvoid]) -> Option<u32> {
+        let mut total = 0;
+        for source in sources {
+            match source {
+                CritAvoid::Negate => return None,
+                CritAvoid::Bonus(bonus) => total += bonus,
+            }
+        }
+        if self.rules().crit_avoid {
+            Some(total)
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Returns the crit shown in the combat preview for an attacker with the
+    /// given crit rate against a defender with the given crit avoid sources.
+    pub fn displayed_crit(&self, crit_rate: u32, sources: &[CritAvoid]) -> u32 {
+        match self.total_crit_avoid(sources) {
+            Some(avoid) => crit_rate.saturating_sub(avoid),
+            None => 0
+        }
+    }
+
+    /// Returns how much more crit avoid the defender needs, on top of the given
+    /// sources, to bring the displayed crit down to 0. Returns `None` if no
+    /// amount of crit avoid will do it, which happens in games where only
+    /// negation works.
+    pub fn crit_avoid_needed(&self, crit_rate: u32,
+                             sources: &[CritAvoid]) -> Option<u32> {
+        let displayed = self.displayed_crit(crit_rate, sources);
+        if self.rules().crit_avoid || displayed == 0 {
+            Some(displayed)
+        } else {
+            None
+        }
+    }
+}
+
+/// The rules a game uses for the basic combat mechanics, apart from hit rates,
+/// which are handled by the game's `RNSystem`. Most games share the defaults,
+/// which are the rules used from the GBA games onwards.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub struct GameRules {
+    /// How critical damage is computed.
+    pub crit_damage: CritDamage,
+
+    /// Whether the defender's crit avoid (usually Luck) reduces crit rates.
+    pub crit_avoid: bool,
+}
+
+impl Default for GameRules {
+    fn default() -> GameRules {
+        GameRules{
+            crit_damage: CritDamage::Triple,
+            crit_avoid: true,
+        }
+    }
+}
+
+/// The rules a custom ruleset can set, for `FEGame::Custom`. Start from
+/// `FEGame::mechanics` of the closest game and change what's different.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub struct MechanicsConfig {
+    /// The era whose defaults are used for anything not set here.
+    pub era: Era,
+
+    /// How listed hit becomes true hit.
+    pub rn_system: RNSystem,
+
+    /// The lowest and highest hit rates allowed.
+    pub hit_bounds: (u32, u32),
+
+    /// The basic combat rules.
+    pub rules: GameRules,
+
+    /// The lead in attack speed needed to attack twice.
+    pub doubling_threshold: u32,
+
+    /// The number effective weapons multiply their might by.
+    pub effectiveness_multiplier: u32,
+
+    /// How the combat preview rounds fractions.
+    pub preview_rounding: Rounding,
+}
+
+/// The ways games compute critical damage.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub enum CritDamage {
+    /// Crits triple the damage dealt, (Atk - Def) * 3.
+    Triple,
+
+    /// Crits double Atk before Def is subtracted, 2 * Atk - Def.
+    DoubleAtk,
+}
+
+impl CritDamage {
+    /// Computes critical damage for the given Atk and Def. Damage can't go
+    /// below 0, so Def higher than Atk gives 0 instead of overflowing.
+    pub fn damage(&self, atk: u32, def: u32) -> u32 {
         match self {
-            FEGame::FE4 | FEGame::FE5 => atk * 2 - def,
-            _ => (atk - def) * 3
+            CritDamage::DoubleAtk => (atk * 2).saturating_sub(def),
+            CritDamage::Triple => atk.saturating_sub(def) * 3,
         }
     }
 }
+
+/// The generations of Fire Emblem engines.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Era {
+    /// The NES and SNES games directed by Shouzou Kaga, FE1 through FE5.
+    Kaga,
+
+    /// The Game Boy Advance games, FE6 through FE8.
+    Gba,
+
+    /// The Tellius games on the GameCube and Wii, FE9 and FE10.
+    Tellius,
+
+    /// The DS remakes, FE11 and FE12.
+    Ds,
+
+    /// The 3DS games, FE13 through FE15.
+    ThreeDs,
+
+    /// The Switch games, FE16 and FE17.
+    Switch,
+}
+
+/// The consoles the games came out on.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Platform {
+    Famicom,
+    SuperFamicom,
+    GameBoyAdvance,
+    GameCube,
+    Wii,
+    DS,
+    ThreeDS,
+    Switch,
+}
+
+/// A source of crit avoid, also called dodge: something that reduces the crit
+/// rate of enemies attacking the unit.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub enum CritAvoid {
+    /// A flat reduction in enemy crit, like the defender's Luck or a support
+    /// bonus.
+    Bonus(u32),
+
+    /// Complete immunity to critical hits, like Hoplon Guard, Iote's Shield, or
+    /// Fortune.
+    Negate,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_calc::CombatStats;
+
+    #[test]
+    fn test_archaic_rules() {
+        let fe1 = FEGame::FE1.rules();
+        assert!(!fe1.crit_avoid);
+        assert_eq!(FEGame::FE1.displayed_crit(20, &[CritAvoid::Bonus(10)]), 20);
+
+        let fe2 = FEGame::FE2.rules();
+        assert!(fe2.crit_avoid);
+
+        let fe3 = FEGame::FE3.rules();
+        assert_eq!(fe3.crit_damage, CritDamage::Triple);
+        assert_eq!(FEGame::FE3.displayed_crit(20, &[CritAvoid::Bonus(10)]), 10);
+        assert_eq!(FEGame::FE3.crit_damage(15, 10), 15);
+
+        assert_eq!(FEGame::FE4.rules().crit_damage, CritDamage::DoubleAtk);
+        assert_eq!(FEGame::FE4.crit_damage(15, 10), 20);
+        // Def past Atk can't make damage negative
+        assert_eq!(FEGame::FE7.crit_damage(10, 15), 0);
+        assert_eq!(FEGame::FE4.crit_damage(10, 25), 0);
+        assert_eq!(FEGame::FE4.crit_damage(10, 15), 5);
+        assert_eq!(FEGame::FE8.rules(), GameRules::default());
+    }
+
+    #[test]
+    fn test_crit_avoid() {
+        let sources = [CritAvoid::Bonus(8), CritAvoid::Bonus(5)];
+        assert_eq!(FEGame::FE7.total_crit_avoid(&sources), Some(13));
+        assert_eq!(FEGame::FE7.displayed_crit(20, &sources), 7);
+        assert_eq!(FEGame::FE7.crit_avoid_needed(20, &sources), Some(7));
+        assert_eq!(FEGame::FE7.crit_avoid_needed(10, &sources), Some(0));
+
+        let hoplon = [CritAvoid::Bonus(8), CritAvoid::Negate];
+        assert_eq!(FEGame::FE7.total_crit_avoid(&hoplon), None);
+        assert_eq!(FEGame::FE7.displayed_crit(50, &hoplon), 0);
+
+        // Jugdral crit ignores dodge entirely
+        assert_eq!(FEGame::FE5.displayed_crit(20, &sources), 20);
+        assert_eq!(FEGame::FE5.crit_avoid_needed(20, &sources), None);
+        assert_eq!(FEGame::FE5.crit_avoid_needed(20, &hoplon), Some(0));
+    }
+
+    #[test]
+    fn test_rn_system() {
+        assert_eq!(FEGame::FE7.rn_system(), RNSystem::TwoRN);
+        assert_eq!(FEGame::FE15.rn_system(), RNSystem::FatesRN);
+        let fe5 = FEGame::FE5;
+        assert_eq!(fe5.rn_system().true_hit(fe5.clamp_hit(100)),
+                   fe5.true_hit(100));
+    }
+
+    #[test]
+    fn test_metadata() {
+        assert_eq!(FEGame::FE5.title(), "Thracia 776");
+        assert_eq!(FEGame::FE7.release_year(), Some(2003));
+        assert_eq!(FEGame::FE10.platform(), Some(Platform::Wii));
+        assert_eq!(FEGame::FE15.platform(), Some(Platform::ThreeDS));
+        assert_eq!(FEGame::FE5.era(), Era::Kaga);
+        assert_eq!(FEGame::FE10.era(), Era::Tellius);
+    }
+
+    #[test]
+    fn test_hit_bounds() {
+        assert_eq!(FEGame::FE5.true_hit(100), 0.99);
+        assert_eq!(FEGame::FE5.true_hit(0), 0.01);
+        assert_eq!(FEGame::FE4.true_hit(0), 0.0);
+        assert_eq!(FEGame::FE7.true_hit(120), 1.0);
+        assert_eq!(FEGame::FE14.true_hit(150), 1.0);
+
+        // guaranteed hits skip the bounds, whatever the listed hit
+        for hit in [0, 100].iter() {
+            let stats = CombatStats{hit: *hit, ..Default::default()};
+            let certain = CombatStats{always_hits: true, ..stats};
+            assert_eq!(certain.true_hit(FEGame::FE5), 1.0);
+            assert!(stats.true_hit(FEGame::FE5) < 1.0);
+            assert!(stats.true_hit(FEGame::FE5) > 0.0);
+        }
+        let scripted = FEGame::Custom(MechanicsConfig{
+            rn_system: RNSystem::AlwaysHit,
+            ..FEGame::FE5.mechanics()
+        });
+        assert_eq!(scripted.true_hit(0), 1.0);
+    }
+
+    #[test]
+    fn test_custom() {
+        use strum::IntoEnumIterator;
+        // a GBA hack with Fates-style hit and a 5 speed threshold
+        let hack = FEGame::Custom(MechanicsConfig{
+            rn_system: RNSystem::FatesRN,
+            doubling_threshold: 5,
+            ..FEGame::FE8.mechanics()
+        });
+        assert_eq!(hack.true_hit(70), FEGame::FE14.true_hit(70));
+        assert!(!hack.doubles(4, false));
+        assert_eq!(hack.era(), Era::Gba);
+        assert_eq!(hack.rules(), FEGame::FE8.rules());
+        assert_eq!(hack.hit_rate(12, 7, 90), FEGame::FE8.hit_rate(12, 7, 90));
+        assert_eq!(hack.platform(), None);
+        let json = serde_json::to_string(&hack).unwrap();
+        assert_eq!(serde_json::from_str::<FEGame>(&json).unwrap(), hack);
+        assert!("Custom".parse::<FEGame>().is_err());
+        assert!(!FEGame::iter().any(|x| matches!(x, FEGame::Custom(_))));
+    }
+
+    #[test]
+    fn test_switch_and_aliases() {
+        assert_eq!("SoV".parse::<FEGame>(), Ok(FEGame::FE15));
+        assert_eq!(FEGame::FE15.to_string(), "FE15");
+        let game: FEGame = serde_json::from_str("\"SoV\"").unwrap();
+        assert_eq!(game, FEGame::FE15);
+        assert_eq!(FEGame::FE16.true_hit(70), 0.7);
+        assert_eq!(FEGame::FE17.true_hit(70), FEGame::FE14.true_hit(70));
+    }
+}
@@ -0,0 +1,99 @@
+//! Turning probabilities into text. Different audiences want different things:
+//! a forecast wants "82.34%", a spreadsheet wants "0.8234", and an ironman
+//! player deciding whether to risk a 0.02% death wants "1 in 5000". Everything
+//! in the crate that prints probabilities goes through `ProbFormat` so these
+//! choices only need to be made once.
+
+use serde::{Deserialize, Serialize};
+
+/// How to write a probability.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize)]
+pub enum ProbStyle {
+    /// As a percentage, like "82.34%".
+    #[default]
+    Percent,
+
+    /// As a number between 0 and 1, like "0.8234".
+    Ratio,
+
+    /// As odds, like "1 in 50". Impossible events are written as "never".
+    OneInN,
+}
+
+/// The settings for writing probabilities.
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct ProbFormat {
+    /// The style to write probabilities in.
+    pub style: ProbStyle,
+
+    /// The number of decimal places to write. For `OneInN`, this applies to
+    /// the N.
+    pub decimals: usize,
+
+    /// Probabilities that are above 0 but below this are written in
+    /// scientific notation, so tail risks don't get rounded to 0. `None` never
+    /// uses scientific notation.
+    pub scientific_below: Option<f64>,
+}
+
+impl Default for ProbFormat {
+    fn default() -> ProbFormat {
+        ProbFormat{
+            style: ProbStyle::Percent,
+            decimals: 2,
+            scientific_below: Some(1e-4),
+        }
+    }
+}
+
+impl ProbFormat {
+    /// Creates a format with the given style and number of decimal places,
+    /// using scientific notation for the same tail risks as the default.
+    pub fn new(style: ProbStyle, decimals: usize) -> ProbFormat {
+        ProbFormat{
+            style,
+            decimals,
+            ..ProbFormat::default()
+        }
+    }
+
+    /// Writes the given probability (0-1).
+    pub fn format(&self, prob: f64) -> String {
+        let tiny = match self.scientific_below {
+            Some(threshold) => prob > 0.0 && prob < threshold,
+            None => false,
+        };
+        let number = |x: f64| if tiny {
+            format!("{:.*e}", self.decimals, x)
+        } else {
+            format!("{:.*}", self.decimals, x)
+        };
+        match self.style {
+            ProbStyle::Percent => format!("{}%", number(prob * 100.0)),
+            ProbStyle::Ratio => number(prob),
+            ProbStyle::OneInN => if prob <= 0.0 {
+                "never".to_string()
+            } else {
+                format!("1 in {}", number(1.0 / prob))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_styles() {
+        assert_eq!(ProbFormat::default().format(0.8234), "82.34%");
+        assert_eq!(ProbFormat::new(ProbStyle::Ratio, 3).format(0.8234), "0.823");
+        assert_eq!(ProbFormat::new(ProbStyle::OneInN, 0).format(0.02), "1 in 50");
+        assert_eq!(ProbFormat::new(ProbStyle::OneInN, 0).format(0.0), "never");
+        // tail risks switch to scientific notation instead of rounding to 0
+        assert_eq!(ProbFormat::default().format(2e-7), "2.00e-5%");
+        let plain = ProbFormat{scientific_below: None, ..Default::default()};
+        assert_eq!(plain.format(2e-7), "0.00%");
+        assert_eq!(ProbFormat::default().format(0.0), "0.00%");
+    }
+}
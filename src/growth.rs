@@ -0,0 +1,226 @@
+//! Planning how a unit's stats grow as they level up and promote. Units gain
+//! each stat on level up with a chance given by their growth rate, so a unit's
+//! expected stats at any point are their bases plus their growths times the
+//! number of levels gained. Promotion adds fixed bonuses and raises the caps.
+//!
+//! Some games let units pick between classes when they promote: trainees in
+//! *The Sacred Stones* promote twice, and every promoted class there has two
+//! options. This makes a tree of class paths, and the planner here compares
+//! where a unit ends up along each branch.
+
+use crate::stats::StatBlock;
+use crate::unit::UnitType;
+use crate::weapon::{WeaponRank, WeaponType};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A unit stat. Not every game has every stat: see `FEGame::stats`, and
+/// `FEGame::stat_name` for what each game calls them.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display,
+         EnumString, EnumIter, Deserialize, Serialize)]
+pub enum Stat {
+    HP,
+    Str,
+    Mag,
+    Skl,
+    Spd,
+    Lck,
+    Def,
+    Res,
+    Con,
+    Cha,
+}
+
+/// Values for each stat. Stats that aren't listed are 0.
+pub type Stats = BTreeMap<Stat, f64>;
+
+/// A class a unit can be in, along with the classes it can promote into.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Class {
+    /// The name of the class.
+    pub name: String,
+
+    /// The class's base stats, which a unit's personal bases are added to in
+    /// most games.
+    #[serde(default)]
+    pub bases: StatBlock,
+
+    /// How many tiles units in this class can move.
+    #[serde(default)]
+    pub mov: u32,
+
+    /// The kinds of unit this class is, for effective weapons.
+    #[serde(default)]
+    pub unit_types: Vec<UnitType>,
+
+    /// The highest rank units in this class can reach with each weapon type
+    /// they can use. Weapon types that aren't listed can't be used.
+    #[serde(default)]
+    pub weapon_ranks: BTreeMap<WeaponType, WeaponRank>,
+
+    /// The highest level a unit can reach in this class.
+    pub max_level: u32,
+
+    /// The level a unit has to reach before they can promote out of this
+    /// class.
+    pub promote_level: u32,
+
+    /// The stats gained when promoting into this class.
+    pub promotion_gains: StatBlock,
+
+    /// The stat caps of this class. Stats that aren't listed are uncapped.
+    pub caps: StatBlock,
+
+    /// The classes this class can promote into.
+    pub promotions: Vec<Class>,
+}
+
+impl Class {
+    /// Returns every path through the promotion tree starting at this class,
+    /// from this class to a class that can't promote any further.
+    pub fn paths(&self) -> Vec<Vec<&Class>> {
+        if self.promotions.is_empty() {
+            return vec!(vec!(self));
+        }
+        self.promotions.iter().flat_map(|promo| promo.paths()).map(|path| {
+            let mut full = vec!(self);
+            full.extend(path);
+            full
+        }).collect()
+    }
+
+    /// Returns whether units in this class can reach the given rank with the
+    /// given weapon type.
+    pub fn can_use(&self, weapon_type: WeaponType, rank: WeaponRank) -> bool {
+        matches!(self.weapon_ranks.get(&weapon_type), Some(max) if rank <= *max)
+    }
+
+    /// Returns the given personal stats with this class's bases added.
+    pub fn add_bases(&self, personal: &StatBlock) -> StatBlock {
+        let mut stats = personal.clone();
+        for (stat, base) in self.bases.values.iter() {
+            stats.set(*stat, personal.get(*stat) + base);
+        }
+        stats
+    }
+
+    /// Caps the given stats to this class's caps.
+    fn cap(&self, stats: &mut Stats) {
+        for (stat, cap) in self.caps.values.iter() {
+            if let Some(value) = stats.get_mut(stat) {
+                *value = value.min(*cap as f64);
+            }
+        }
+    }
+}
+
+/// A unit with the information needed to plan their growth.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GrowthUnit {
+    /// The unit's level when they join.
+    pub base_level: u32,
+
+    /// The unit's stats when they join.
+    pub bases: Stats,
+
+    /// The unit's growth rates, as percentages.
+    pub growths: BTreeMap<Stat, u32>,
+}
+
+impl GrowthUnit {
+    /// Adds the expected stat gains from the given number of levels to the
+    /// given stats. The expectation is capped to the class caps after every
+    /// level, which slightly overestimates units who are near their caps.
+    fn level_up(&self, stats: &mut Stats, levels: u32, class: &Class) {
+        for _ in 0..levels {
+            for (stat, growth) in self.growths.iter() {
+                *stats.entry(*stat).or_insert(0.0) += *growth as f64 / 100.0;
+            }
+            class.cap(stats);
+        }
+    }
+
+    /// Returns the unit's expected stats after following the given class path
+    /// to the max level of its last class, promoting out of each class as soon
+    /// as possible. The unit starts in the first class of the path at their
+    /// base level.
+    pub fn end_stats(&self, path: &[&Class]) -> Stats {
+        let mut stats = self.bases.clone();
+        let mut level = self.base_level;
+        for (i, class) in path.iter().enumerate() {
+            if i > 0 {
+                for (stat, gain) in class.promotion_gains.values.iter() {
+                    *stats.entry(*stat).or_insert(0.0) += *gain as f64;
+                }
+                class.cap(&mut stats);
+                level = 1;
+            }
+            let target = if i + 1 == path.len() {
+                class.max_level
+            } else {
+                class.promote_level.max(level)
+            };
+            self.level_up(&mut stats, target.saturating_sub(level), class);
+            level = target;
+        }
+        stats
+    }
+
+    /// Returns the names of each class path out of the given starting class,
+    /// paired with the unit's expected stats at the end of that path.
+    pub fn compare_branches(&self, start: &Class) -> Vec<(Vec<String>, Stats)> {
+        start.paths().into_iter().map(|path| {
+            let names = path.iter().map(|class| class.name.clone()).collect();
+            (names, self.end_stats(&path))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(name: &str, gains: &[(Stat, u32)], caps: &[(Stat, u32)],
+             promotions: Vec<Class>) -> Class {
+        Class{
+            name: name.to_string(),
+            bases: StatBlock::default(),
+            mov: 5,
+            unit_types: vec!(),
+            weapon_ranks: BTreeMap::new(),
+            max_level: if promotions.is_empty() { 20 } else { 10 },
+            promote_level: 10,
+            promotion_gains: StatBlock::new(gains),
+            caps: StatBlock::new(caps),
+            promotions,
+        }
+    }
+
+    #[test]
+    fn test_trainee_branches() {
+        // a trainee path like Ross's, trimmed to two of the final classes
+        let berserker = class("Berserker", &[(Stat::Str, 1)], &[(Stat::Str, 30)],
+                              vec!());
+        let hero = class("Hero", &[(Stat::Str, 2)], &[(Stat::Str, 25)], vec!());
+        let fighter = class("Fighter", &[(Stat::Str, 2)], &[(Stat::Str, 20)],
+                            vec!(berserker, hero));
+        let journeyman = class("Journeyman", &[], &[(Stat::Str, 10)],
+                               vec!(fighter));
+        assert_eq!(journeyman.paths().len(), 2);
+
+        let ross = GrowthUnit{
+            base_level: 1,
+            bases: vec![(Stat::Str, 5.0)].into_iter().collect(),
+            growths: vec![(Stat::Str, 50)].into_iter().collect(),
+        };
+        let branches = ross.compare_branches(&journeyman);
+        assert_eq!(branches[0].0, vec!["Journeyman", "Fighter", "Berserker"]);
+        // 9 levels as a journeyman: 5 + 4.5, then +2, 9 levels: 16, then +1
+        // and 19 levels: 26.5
+        assert!((branches[0].1[&Stat::Str] - 26.5).abs() < 1e-9);
+        // the hero gets a bigger bonus but hits their lower cap
+        assert!((branches[1].1[&Stat::Str] - 25.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,68 @@
+//! How each game turns growth rates into stat gains on level up. Most games
+//! roll each stat separately, but some let the player switch to fixed growths
+//! or reroll level-ups that gain nothing, which changes the distribution of
+//! stats a unit ends up with.
+
+use crate::fegame::FEGame;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+/// A way of turning growth rates into stat gains on level up.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, Deserialize,
+         Serialize)]
+pub enum LevelUpSystem {
+    /// Each stat rolls against its growth rate on its own, as in
+    /// `rng::growth_distribution`.
+    Random {
+        /// Whether a level-up that gains no stats is rolled again until it
+        /// gains at least one.
+        reroll_empty: bool,
+    },
+
+    /// Growth rates add up on every level, and a stat goes up whenever its
+    /// total passes 100, so there's no randomness at all.
+    Fixed,
+
+    /// Growth rates change as the unit levels, pushing stats back towards
+    /// their expected values.
+    Dynamic,
+}
+
+impl FEGame {
+    /// Returns the level-up system this game uses by default. Only the
+    /// Tellius games are known to reroll empty level-ups; games after them
+    /// are listed as plain random growths until they've been checked, which
+    /// `FEGame::support` reports.
+    pub fn levelup_system(&self) -> LevelUpSystem {
+        match self {
+            FEGame::FE9 | FEGame::FE10 => {
+                LevelUpSystem::Random{reroll_empty: true}
+            }
+            _ => LevelUpSystem::Random{reroll_empty: false},
+        }
+    }
+
+    /// Returns every level-up system the player can pick in this game,
+    /// starting with the default.
+    pub fn levelup_systems(&self) -> Vec<LevelUpSystem> {
+        match self {
+            FEGame::FE9 => vec!(self.levelup_system(), LevelUpSystem::Fixed),
+            _ => vec!(self.levelup_system()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levelup_systems() {
+        assert_eq!(FEGame::FE7.levelup_systems(),
+                   vec!(LevelUpSystem::Random{reroll_empty: false}));
+        assert!(FEGame::FE9.levelup_systems().contains(&LevelUpSystem::Fixed));
+        assert_eq!(FEGame::FE9.levelup_systems()[0],
+                   LevelUpSystem::Random{reroll_empty: true});
+    }
+}
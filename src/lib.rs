@@ -1,8 +1,39 @@
 pub mod rng;
 pub mod fegame;
 pub mod simple_calc;
+pub mod round;
 pub mod weapon;
 pub mod unit;
+pub mod growth;
+pub mod levelup;
+pub mod mechanics;
+pub mod farming;
+pub mod affinity;
+pub mod calc;
+pub mod caps;
+pub mod damage;
+pub mod durability;
+pub mod effective;
+pub mod format;
+pub mod plan;
+pub mod preview;
+pub mod provenance;
+pub mod query;
+pub mod report;
+pub mod scenario;
+pub mod skill;
+pub mod staff;
+pub mod stats;
+pub mod support;
+pub mod terrain;
+pub mod triangle;
+pub mod validation;
+#[cfg(feature = "bincode")]
+pub mod compact;
+#[cfg(feature = "batch")]
+pub mod batch;
+
+pub use crate::calc::calc;
 
 
 #[cfg(test)]
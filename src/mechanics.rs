@@ -0,0 +1,144 @@
+//! Which mechanics exist in each game at all, as opposed to how well the crate
+//! models them (see the `support` module). Frontends can use this to hide
+//! inputs that don't apply, like pair-up outside of the 3DS games, and
+//! calculators can use it to refuse combat that can't happen in the game.
+
+use crate::fegame::FEGame;
+use crate::simple_calc::CombatStats;
+
+use std::error::Error;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A mechanic that only some games have.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Capability {
+    /// The weapon triangle changes hit or damage.
+    WeaponTriangle,
+
+    /// Strikes can crit.
+    Crits,
+
+    /// Units have skills that affect combat.
+    Skills,
+
+    /// Units can pair up, letting the partner add stats and join in combat.
+    PairUp,
+
+    /// Weapons have a limited number of uses.
+    WeaponDurability,
+
+    /// Brave weapons strike twice on every attack their wielder makes,
+    /// including follow-ups and counterattacks, instead of only when their
+    /// wielder initiates combat.
+    BraveFollowUp,
+}
+
+impl FEGame {
+    /// Returns whether this game has the given mechanic.
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::WeaponTriangle => self.has_triangle(),
+            Capability::Crits => true,
+            Capability::Skills => !matches!(
+                self,
+                FEGame::FE1 | FEGame::FE2 | FEGame::FE3 | FEGame::FE6
+                    | FEGame::FE7 | FEGame::FE11 | FEGame::FE12
+            ),
+            Capability::PairUp => matches!(self, FEGame::FE13 | FEGame::FE14),
+            Capability::WeaponDurability => !matches!(
+                self,
+                FEGame::FE2 | FEGame::FE14 | FEGame::FE15 | FEGame::FE17
+            ),
+            Capability::BraveFollowUp => !matches!(
+                self,
+                FEGame::FE14 | FEGame::FE16 | FEGame::FE17
+            ),
+        }
+    }
+
+    /// Returns every mechanic this game has.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        Capability::iter().filter(|x| self.has_capability(*x)).collect()
+    }
+
+    /// Checks that combat between an attacker and defender with the given
+    /// stats can happen in this game, returning the first mechanic one of them
+    /// uses that the game doesn't have. Skill procs need skills, crits need
+    /// crits, and a defender can only counter with a brave weapon in games
+    /// where brave weapons work on every attack.
+    pub fn check_combat(&self, atk: &CombatStats,
+                        def: &CombatStats) -> Result<(), MissingCapability> {
+        let require = |capability, used: bool| {
+            if used && !self.has_capability(capability) {
+                Err(MissingCapability(capability))
+            } else {
+                Ok(())
+            }
+        };
+        for stats in &[atk, def] {
+            require(Capability::Crits,
+                    stats.crit > 0 || stats.crit_dmg.is_some())?;
+            require(Capability::Skills,
+                    stats.strike_proc.is_some() || stats.dmg_proc.is_some()
+                    || stats.guard.is_some())?;
+        }
+        require(Capability::BraveFollowUp,
+                def.is_brave && !def.cannot_counter)
+    }
+}
+
+/// The error returned for combat that uses a mechanic the game doesn't have.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MissingCapability(pub Capability);
+
+impl fmt::Display for MissingCapability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the game doesn't have {}", self.0)
+    }
+}
+
+impl Error for MissingCapability {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_calc::StrikeProc;
+
+    #[test]
+    fn test_capabilities() {
+        assert!(FEGame::FE7.has_capability(Capability::WeaponTriangle));
+        assert!(!FEGame::FE7.has_capability(Capability::PairUp));
+        assert!(FEGame::FE13.has_capability(Capability::WeaponTriangle));
+        assert!(!FEGame::FE16.has_capability(Capability::WeaponTriangle));
+        assert_eq!(FEGame::FE14.capabilities(), vec!(
+            Capability::WeaponTriangle, Capability::Crits, Capability::Skills,
+            Capability::PairUp,
+        ));
+    }
+
+    #[test]
+    fn test_check_combat() {
+        let plain = CombatStats{dmg: 5, hit: 80, ..Default::default()};
+        let astra = CombatStats{
+            strike_proc: Some(StrikeProc{rate: 20, strikes: 5,
+                                         dmg_percent: 50}),
+            ..plain
+        };
+        let brave = CombatStats{is_brave: true, ..plain};
+        assert_eq!(FEGame::FE7.check_combat(&plain, &plain), Ok(()));
+        assert_eq!(FEGame::FE7.check_combat(&astra, &plain),
+                   Err(MissingCapability(Capability::Skills)));
+        assert_eq!(FEGame::FE9.check_combat(&astra, &plain), Ok(()));
+        // brave weapons only work when initiating in Three Houses
+        assert_eq!(FEGame::FE16.check_combat(&brave, &plain), Ok(()));
+        assert_eq!(FEGame::FE16.check_combat(&plain, &brave),
+                   Err(MissingCapability(Capability::BraveFollowUp)));
+        let asleep = CombatStats{cannot_counter: true, ..brave};
+        assert_eq!(FEGame::FE16.check_combat(&plain, &asleep), Ok(()));
+    }
+}
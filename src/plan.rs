@@ -0,0 +1,284 @@
+//! A plan is a scripted sequence of events that all have to go right: "Lyn
+//! hits the 80 to kill the brigand, then Florina dodges the archer, then..."
+//! Each step is treated as independent of the others, which is true as long as
+//! the steps use different random numbers.
+//!
+//! The point of writing a plan down is finding where it's weakest, so that's
+//! what most of this module does. For ironman runs, a `RiskBudget` does the
+//! same for deaths across a whole chapter or run, and an `ActionChain` handles
+//! turns where each kill has to work to earn the next action.
+
+use crate::fegame::FEGame;
+use crate::simple_calc::{Outcome, OutcomeDist, Side};
+
+use serde::{Deserialize, Serialize};
+
+/// A single event that a plan needs to happen.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// A description of the event, like "Lyn kills the brigand".
+    pub label: String,
+
+    /// The probability the event happens, as a number between 0 and 1.
+    pub prob: f64,
+}
+
+/// A sequence of independent events that all need to happen.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    /// The required events, in order.
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    /// Creates an empty plan.
+    pub fn new() -> Plan {
+        Plan::default()
+    }
+
+    /// Adds a step with the given probability (0-1) to the end of the plan.
+    pub fn add_step(&mut self, label: &str, prob: f64) {
+        self.steps.push(Step{
+            label: label.to_string(),
+            prob,
+        });
+    }
+
+    /// Adds a step that requires a single attack with the given listed hit to
+    /// connect, using the given game's RN system.
+    pub fn add_hit(&mut self, label: &str, game: FEGame, listed_hit: u32) {
+        self.add_step(label, game.true_hit(listed_hit));
+    }
+
+    /// The probability that every step of the plan goes right.
+    pub fn prob_success(&self) -> f64 {
+        self.steps.iter().map(|x| x.prob).product()
+    }
+
+    /// Returns the step least likely to happen, or `None` if the plan is empty.
+    /// Ties go to the earliest step.
+    pub fn worst_step(&self) -> Option<&Step> {
+        self.steps.iter().fold(None, |worst: Option<&Step>, step| {
+            match worst {
+                Some(w) if w.prob <= step.prob => Some(w),
+                _ => Some(step)
+            }
+        })
+    }
+
+    /// Returns every step whose probability is below the given threshold, in
+    /// plan order: for example, a threshold of 0.8 finds every sub-80% roll.
+    pub fn steps_below(&self, threshold: f64) -> Vec<&Step> {
+        self.steps.iter().filter(|x| x.prob < threshold).collect()
+    }
+}
+
+/// A unit's kills over a single turn when a kill can earn another action, like
+/// Galeforce in *Awakening* and *Fates*. Each kill is only attempted if every
+/// kill before it worked, so the chance of each extra action depends on the
+/// chance of the kill before it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionChain {
+    /// The kills the unit goes for, in order.
+    pub kills: Vec<Step>,
+
+    /// The number of extra actions kills can earn in a turn. Galeforce can
+    /// only activate once per turn, so this is 1 for it.
+    pub extra_actions: u32,
+}
+
+impl ActionChain {
+    /// Creates an empty chain that can earn the given number of extra actions.
+    pub fn new(extra_actions: u32) -> ActionChain {
+        ActionChain{
+            kills: vec!(),
+            extra_actions,
+        }
+    }
+
+    /// Adds a kill with the given probability (0-1) to the end of the chain.
+    pub fn add_kill(&mut self, label: &str, prob: f64) {
+        self.kills.push(Step{
+            label: label.to_string(),
+            prob,
+        });
+    }
+
+    /// The kills the unit can actually get to: the first, plus one for each
+    /// extra action.
+    fn reachable(&self) -> &[Step] {
+        let actions = (self.extra_actions as usize).saturating_add(1);
+        &self.kills[..self.kills.len().min(actions)]
+    }
+
+    /// Returns the distribution of the number of kills made this turn, where
+    /// the value at index `i` is the probability of getting exactly `i` kills.
+    pub fn kill_distribution(&self) -> Vec<f64> {
+        let mut dist = vec!();
+        // the chance every kill so far has worked
+        let mut prob_reached = 1.0;
+        for step in self.reachable() {
+            dist.push(prob_reached * (1.0 - step.prob));
+            prob_reached *= step.prob;
+        }
+        dist.push(prob_reached);
+        dist
+    }
+
+    /// The expected number of kills this turn.
+    pub fn expected_kills(&self) -> f64 {
+        self.kill_distribution().iter().enumerate()
+            .map(|(i, prob)| i as f64 * prob)
+            .sum()
+    }
+
+    /// The probability of making every kill in the chain this turn. This is 0
+    /// if there are more kills than actions to make them with.
+    pub fn prob_all_kills(&self) -> f64 {
+        if self.reachable().len() < self.kills.len() {
+            0.0
+        } else {
+            self.kills.iter().map(|x| x.prob).product()
+        }
+    }
+}
+
+/// A single engagement that risks a unit's life.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Engagement {
+    /// A description of the engagement, like "Kent tanks the ch. 12 archers".
+    pub label: String,
+
+    /// The probability that a unit dies, as a number between 0 and 1.
+    pub prob_death: f64,
+}
+
+/// Keeps track of every engagement in a planned chapter or run that could get
+/// someone killed, and how much each one adds to the total risk.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RiskBudget {
+    /// The engagements, in the order they were added.
+    pub engagements: Vec<Engagement>,
+}
+
+impl RiskBudget {
+    /// Creates an empty budget.
+    pub fn new() -> RiskBudget {
+        RiskBudget::default()
+    }
+
+    /// Adds an engagement with the given chance (0-1) of someone dying.
+    pub fn add_engagement(&mut self, label: &str, prob_death: f64) {
+        self.engagements.push(Engagement{
+            label: label.to_string(),
+            prob_death,
+        });
+    }
+
+    /// Adds a combat from its possible outcomes, where the player's unit is on
+    /// the given side.
+    pub fn add_combat(&mut self, label: &str, outcomes: &[Outcome], unit: Side) {
+        let prob_death = match unit {
+            Side::Atk => outcomes.prob_atk_dies(),
+            Side::Def => outcomes.prob_def_dies(),
+        };
+        self.add_engagement(label, prob_death);
+    }
+
+    /// The probability of getting through every engagement without a death.
+    pub fn prob_deathless(&self) -> f64 {
+        self.engagements.iter().map(|x| 1.0 - x.prob_death).product()
+    }
+
+    /// Returns each engagement alongside its share of the total risk, from the
+    /// biggest share to the smallest. Shares are measured on a log scale, which
+    /// is the only way they add up: an engagement with a share of 0.5 accounts
+    /// for half of the total risk in the sense that removing it would square
+    /// root the deathless probability. Certain deaths get the whole share.
+    /// A probability that isn't a number gives a share that isn't either, but
+    /// doesn't stop the rest from sorting.
+    pub fn contributions(&self) -> Vec<(&Engagement, f64)> {
+        let certain = self.engagements.iter().filter(|x| x.prob_death >= 1.0).count();
+        let total_log: f64 = self.engagements.iter()
+            .map(|x| (1.0 - x.prob_death).ln())
+            .sum();
+        let mut shares: Vec<(&Engagement, f64)> = self.engagements.iter().map(|x| {
+            let share = if certain > 0 {
+                if x.prob_death >= 1.0 { 1.0 / certain as f64 } else { 0.0 }
+            } else if total_log == 0.0 {
+                0.0
+            } else {
+                (1.0 - x.prob_death).ln() / total_log
+            };
+            (x, share)
+        }).collect();
+        shares.sort_by(|a, b| b.1.total_cmp(&a.1));
+        shares
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_points() {
+        let mut plan = Plan::new();
+        plan.add_step("Lyn kills the brigand", 0.9);
+        plan.add_hit("Florina hits the archer", FEGame::FE1, 70);
+        plan.add_step("Kent survives enemy phase", 0.75);
+        plan.add_step("Sain survives enemy phase", 0.75);
+
+        assert!((plan.prob_success() - 0.9 * 0.7 * 0.75 * 0.75).abs() < 1e-9);
+        assert_eq!(plan.worst_step().unwrap().label, "Florina hits the archer");
+        let weak: Vec<&str> = plan.steps_below(0.8).iter()
+            .map(|x| x.label.as_str())
+            .collect();
+        assert_eq!(weak, vec!["Florina hits the archer",
+                              "Kent survives enemy phase",
+                              "Sain survives enemy phase"]);
+        assert_eq!(Plan::new().worst_step(), None);
+    }
+
+    #[test]
+    fn test_galeforce() {
+        let mut chain = ActionChain::new(1);
+        chain.add_kill("Lucina kills the wyvern", 0.8);
+        chain.add_kill("Lucina kills the sorcerer", 0.5);
+        chain.add_kill("Lucina kills the general", 0.9);
+
+        // the general is out of reach: Galeforce only activates once
+        let dist = chain.kill_distribution();
+        assert_eq!(dist.len(), 3);
+        assert!((dist[0] - 0.2).abs() < 1e-9);
+        assert!((dist[1] - 0.4).abs() < 1e-9);
+        assert!((dist[2] - 0.4).abs() < 1e-9);
+        assert!((chain.expected_kills() - 1.2).abs() < 1e-9);
+        assert_eq!(chain.prob_all_kills(), 0.0);
+
+        chain.extra_actions = 2;
+        assert!((chain.prob_all_kills() - 0.36).abs() < 1e-9);
+        assert_eq!(ActionChain::new(1).kill_distribution(), vec![1.0]);
+    }
+
+    #[test]
+    fn test_risk_budget() {
+        let mut budget = RiskBudget::new();
+        budget.add_engagement("Kent tanks the archers", 0.02);
+        budget.add_combat("Lyn fights the boss", &[
+            Outcome{prob: 0.9, atk_hp: 10, def_hp: 0},
+            Outcome{prob: 0.1, atk_hp: 0, def_hp: 5},
+        ], Side::Atk);
+        budget.add_engagement("Florina rescues Sain", 0.0);
+
+        assert!((budget.prob_deathless() - 0.98 * 0.9).abs() < 1e-9);
+        let contributions = budget.contributions();
+        assert_eq!(contributions[0].0.label, "Lyn fights the boss");
+        assert_eq!(contributions[2].1, 0.0);
+        let total: f64 = contributions.iter().map(|x| x.1).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        // a bad probability doesn't bring down the sort
+        budget.add_engagement("Wil guesses", f64::NAN);
+        assert_eq!(budget.contributions().len(), 4);
+    }
+}
@@ -0,0 +1,161 @@
+//! The numbers shown in a game's combat preview. Formulas for hit, crit, and
+//! damage often produce values the game would never show, like negative
+//! damage, hit rates above 100, or fractional values from halved stats. This
+//! module converts those raw values into what the preview actually displays,
+//! so tools can show players the numbers they're used to seeing.
+
+use crate::fegame::FEGame;
+use crate::simple_calc::CombatStats;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// How a game turns fractional values into the whole numbers it displays.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Rounding {
+    /// Fractions are dropped, so 71.5 shows as 71.
+    Floor,
+
+    /// Values are rounded to the nearest whole number, with halves rounding
+    /// up, so 71.5 shows as 72.
+    Nearest,
+}
+
+impl Rounding {
+    /// Rounds the value to a whole number.
+    pub fn round(&self, value: f64) -> f64 {
+        match self {
+            Rounding::Floor => value.floor(),
+            Rounding::Nearest => value.round(),
+        }
+    }
+}
+
+/// The raw results of the combat formulas for one side of combat, before any
+/// rounding or clamping.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawPreview {
+    /// The hit rate, after subtracting the target's avoid.
+    pub hit: f64,
+
+    /// The crit rate, after subtracting the target's crit avoid.
+    pub crit: f64,
+
+    /// The damage, after subtracting the target's Def or Res.
+    pub dmg: f64,
+
+    /// The attack speed, after weapon weight.
+    pub attack_speed: f64,
+}
+
+/// One side of a combat preview as the game displays it.
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Preview {
+    /// The displayed hit rate (0-100).
+    pub hit: u32,
+
+    /// The displayed crit rate (0-100).
+    pub crit: u32,
+
+    /// The displayed damage.
+    pub dmg: u32,
+
+    /// The displayed attack speed.
+    pub attack_speed: u32,
+}
+
+impl Preview {
+    /// Returns the stats needed for the simple calculator from this preview,
+    /// with no brave effect.
+    pub fn combat_stats(&self) -> CombatStats {
+        CombatStats{
+            dmg: self.dmg,
+            hit: self.hit,
+            crit: self.crit,
+            ..Default::default()
+        }
+    }
+}
+
+impl FEGame {
+    /// How this game rounds fractional values in the combat preview. Only the
+    /// GBA games are known to drop fractions; the others are assumed to as
+    /// well until they've been checked, which `FEGame::support` reports.
+    pub fn preview_rounding(&self) -> Rounding {
+        match self {
+            FEGame::Custom(config) => config.preview_rounding,
+            _ => Rounding::Floor,
+        }
+    }
+
+    /// Returns the hit rate the preview shows for a raw hit rate, rounded with
+    /// `preview_rounding` and clamped to the game's `hit_bounds`.
+    pub fn displayed_hit(&self, raw_hit: f64) -> u32 {
+        let (min, max) = self.hit_bounds();
+        self.clamp_round(raw_hit, min, max)
+    }
+
+    /// Returns the crit rate the preview shows for a raw crit rate, rounded
+    /// with `preview_rounding` and clamped between 0 and 100.
+    pub fn displayed_crit_rate(&self, raw_crit: f64) -> u32 {
+        self.clamp_round(raw_crit, 0, 100)
+    }
+
+    /// Converts the raw results of the combat formulas into the numbers this
+    /// game's combat preview would display. Fractional values are rounded
+    /// with `preview_rounding`, hit and crit are clamped as in `displayed_hit`
+    /// and `displayed_crit_rate`, and damage and attack speed can't go below
+    /// 0.
+    pub fn preview(&self, raw: RawPreview) -> Preview {
+        Preview{
+            hit: self.displayed_hit(raw.hit),
+            crit: self.displayed_crit_rate(raw.crit),
+            dmg: self.clamp_round(raw.dmg, 0, u32::MAX),
+            attack_speed: self.clamp_round(raw.attack_speed, 0, u32::MAX),
+        }
+    }
+
+    /// Rounds the value the way this game does and clamps it between the
+    /// given bounds.
+    fn clamp_round(&self, value: f64, min: u32, max: u32) -> u32 {
+        self.preview_rounding().round(value).max(min as f64).min(max as f64)
+            as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        let raw = RawPreview{
+            hit: 113.5,
+            crit: -4.0,
+            dmg: 12.5,
+            attack_speed: -2.0,
+        };
+        assert_eq!(FEGame::FE8.preview(raw), Preview{
+            hit: 100, crit: 0, dmg: 12, attack_speed: 0,
+        });
+        assert_eq!(FEGame::FE5.preview(raw).hit, 99);
+        assert_eq!(FEGame::FE5.displayed_hit(-10.0), 1);
+        assert_eq!(FEGame::FE7.displayed_crit_rate(7.9), 7);
+        assert_eq!(Rounding::Nearest.round(71.5), 72.0);
+
+        let raw = RawPreview{
+            hit: 71.5,
+            crit: 9.0,
+            dmg: -3.0,
+            attack_speed: 11.0,
+        };
+        let preview = FEGame::FE8.preview(raw);
+        assert_eq!(preview, Preview{
+            hit: 71, crit: 9, dmg: 0, attack_speed: 11,
+        });
+        assert_eq!(preview.combat_stats(), CombatStats{
+            dmg: 0, hit: 71, crit: 9, ..Default::default()
+        });
+    }
+}
@@ -0,0 +1,169 @@
+//! Metadata recording which version of the crate's rules produced a result.
+//! Game mechanics get corrected over time, so a forecast saved today might not
+//! match what the same inputs give next year. Wrapping serialized results in
+//! `Versioned` records where the numbers came from, and lets loaders refuse
+//! results computed under rules that have since changed.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The version of the game rules implemented by the crate. This is bumped
+/// whenever a change would make previously computed results differ, and each
+/// bump gets an entry in `RULES_CHANGES`.
+pub const RULES_VERSION: u32 = 8;
+
+/// Every rules version, oldest first, with what changed in it. Mechanics the
+/// crate models only partially are listed by `FEGame::support` instead; this
+/// only records changes to results that were already being computed.
+pub const RULES_CHANGES: &[(u32, &str)] = &[
+    (1, "First versioned rules."),
+    (2, "Listed hit is clamped to each game's hit bounds, so FE5 hit rates \
+         stay between 1 and 99."),
+    (3, "Shadows of Valentia uses the Fates RN system instead of 2RN."),
+    (4, "Strikes that can't miss always land, even where hit bounds would \
+         keep them below 100."),
+    (5, "The Tellius weapon triangle grows with the attacker's weapon rank, \
+         and Radiant Dawn's S rank bonus depends on the weapon type."),
+    (6, "Traced outcomes record skills like Astra as Flurry events instead of \
+         Proc, which is kept for procs on a strike that landed."),
+    (7, "Combat stops as soon as either side dies, so traced outcomes no \
+         longer record strikes against a unit that is already dead."),
+    (8, "Jugdral units only attack twice if they have Pursuit, and then \
+         whenever they're faster than the enemy."),
+];
+
+/// Where a result came from: the crate version, rules version, and any
+/// configuration the caller wants to record alongside them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// The version of the crate that produced the result.
+    pub crate_version: String,
+
+    /// The rules version that produced the result.
+    pub rules_version: u32,
+
+    /// Free-form configuration, like the game or crit model used.
+    pub config: BTreeMap<String, String>,
+}
+
+impl Provenance {
+    /// Returns the provenance of results computed by this build of the crate,
+    /// with no configuration recorded.
+    pub fn current() -> Provenance {
+        Provenance{
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            rules_version: RULES_VERSION,
+            config: BTreeMap::new(),
+        }
+    }
+
+    /// Returns whether results with this provenance match what this build of
+    /// the crate would compute.
+    pub fn is_compatible(&self) -> bool {
+        self.rules_version == RULES_VERSION
+    }
+}
+
+/// A result tagged with its provenance.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Versioned<T> {
+    /// Where the data came from.
+    pub provenance: Provenance,
+
+    /// The result itself.
+    pub data: T,
+}
+
+impl<T> Versioned<T> {
+    /// Tags the data with the current provenance.
+    pub fn new(data: T) -> Versioned<T> {
+        Versioned{
+            provenance: Provenance::current(),
+            data,
+        }
+    }
+
+    /// Records a configuration value in the provenance.
+    pub fn with_config(mut self, key: &str, value: &str) -> Versioned<T> {
+        self.provenance.config.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+impl<T: Serialize> Versioned<T> {
+    /// Serializes the result and its provenance as JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+impl<T: DeserializeOwned> Versioned<T> {
+    /// Loads a result from JSON, failing if it was computed under rules that
+    /// differ from this build's.
+    pub fn from_json(json: &str) -> Result<Versioned<T>, LoadError> {
+        let versioned: Versioned<T> = serde_json::from_str(json)
+            .map_err(LoadError::Json)?;
+        if versioned.provenance.is_compatible() {
+            Ok(versioned)
+        } else {
+            Err(LoadError::Incompatible(versioned.provenance))
+        }
+    }
+}
+
+/// An error loading a versioned result.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The JSON couldn't be parsed.
+    Json(serde_json::Error),
+
+    /// The result was computed under different rules than this build's.
+    Incompatible(Provenance),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Json(err) => write!(f, "invalid JSON: {}", err),
+            LoadError::Incompatible(provenance) => write!(
+                f, "computed with rules version {} (crate {}), but this is rules version {}",
+                provenance.rules_version, provenance.crate_version, RULES_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_calc::Outcome;
+
+    #[test]
+    fn test_versioned() {
+        let outcomes = vec![Outcome{prob: 1.0, atk_hp: 10, def_hp: 0}];
+        let versioned = Versioned::new(outcomes).with_config("game", "FE7");
+        let json = versioned.to_json().unwrap();
+        let loaded: Versioned<Vec<Outcome>> = Versioned::from_json(&json).unwrap();
+        assert_eq!(loaded, versioned);
+        assert_eq!(loaded.provenance.config["game"], "FE7");
+
+        let mut old = versioned;
+        old.provenance.rules_version = 0;
+        let err = Versioned::<Vec<Outcome>>::from_json(&old.to_json().unwrap());
+        assert!(matches!(err, Err(LoadError::Incompatible(_))));
+        assert!(matches!(Versioned::<Vec<Outcome>>::from_json("{"),
+                         Err(LoadError::Json(_))));
+    }
+
+    #[test]
+    fn test_rules_changes() {
+        // every bump to the rules version needs a changelog entry
+        let versions: Vec<u32> = RULES_CHANGES.iter().map(|x| x.0).collect();
+        assert_eq!(versions, (1..=RULES_VERSION).collect::<Vec<u32>>());
+    }
+}
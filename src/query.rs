@@ -0,0 +1,137 @@
+//! A small language for asking questions about the outcomes of combat. Simple
+//! questions like "what's the chance the boss dies?" are covered by
+//! `OutcomeDist`, but compound ones like "what's the chance the boss dies and
+//! I'm left with at least 10 HP?" would otherwise need a closure written by
+//! hand. Events can be combined with `and`, `or`, and `not`, and because
+//! they're plain data they can be saved alongside scenarios.
+
+use crate::scenario::Scenario;
+use crate::simple_calc::{Outcome, OutcomeDist};
+
+use serde::{Deserialize, Serialize};
+
+/// Something that can be true or false of the outcome of combat.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Event {
+    /// The attacker ends with at least the given HP.
+    AtkHpAtLeast(u32),
+
+    /// The attacker ends with at most the given HP.
+    AtkHpAtMost(u32),
+
+    /// The defender ends with at least the given HP.
+    DefHpAtLeast(u32),
+
+    /// The defender ends with at most the given HP.
+    DefHpAtMost(u32),
+
+    /// Both events happen.
+    And(Box<Event>, Box<Event>),
+
+    /// At least one of the events happens.
+    Or(Box<Event>, Box<Event>),
+
+    /// The event doesn't happen.
+    Not(Box<Event>),
+}
+
+/// The attacker dies.
+pub fn atk_dead() -> Event {
+    Event::AtkHpAtMost(0)
+}
+
+/// The defender dies.
+pub fn def_dead() -> Event {
+    Event::DefHpAtMost(0)
+}
+
+/// The attacker ends with at least the given HP.
+pub fn atk_hp_at_least(hp: u32) -> Event {
+    Event::AtkHpAtLeast(hp)
+}
+
+/// The attacker ends with at most the given HP.
+pub fn atk_hp_at_most(hp: u32) -> Event {
+    Event::AtkHpAtMost(hp)
+}
+
+/// The defender ends with at least the given HP.
+pub fn def_hp_at_least(hp: u32) -> Event {
+    Event::DefHpAtLeast(hp)
+}
+
+/// The defender ends with at most the given HP.
+pub fn def_hp_at_most(hp: u32) -> Event {
+    Event::DefHpAtMost(hp)
+}
+
+impl Event {
+    /// Returns the event that both this and the other event happen.
+    pub fn and(self, other: Event) -> Event {
+        Event::And(Box::new(self), Box::new(other))
+    }
+
+    /// Returns the event that at least one of this and the other event
+    /// happens.
+    pub fn or(self, other: Event) -> Event {
+        Event::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Returns the event that this event doesn't happen.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Event {
+        Event::Not(Box::new(self))
+    }
+
+    /// Returns whether the event happens in the given outcome.
+    pub fn holds(&self, outcome: &Outcome) -> bool {
+        match self {
+            Event::AtkHpAtLeast(hp) => outcome.atk_hp >= *hp,
+            Event::AtkHpAtMost(hp) => outcome.atk_hp <= *hp,
+            Event::DefHpAtLeast(hp) => outcome.def_hp >= *hp,
+            Event::DefHpAtMost(hp) => outcome.def_hp <= *hp,
+            Event::And(a, b) => a.holds(outcome) && b.holds(outcome),
+            Event::Or(a, b) => a.holds(outcome) || b.holds(outcome),
+            Event::Not(a) => !a.holds(outcome),
+        }
+    }
+
+    /// Returns the probability (0-1) of the event given the possible outcomes.
+    pub fn prob(&self, outcomes: &[Outcome]) -> f64 {
+        outcomes.prob_where(|x| self.holds(x))
+    }
+
+    /// Returns the probability (0-1) of the event in the given scenario.
+    pub fn prob_in(&self, scenario: &Scenario) -> f64 {
+        self.prob(&scenario.outcomes())
+    }
+}
+
+/// Returns the probability (0-1) of the event given the possible outcomes, so
+/// queries read like `P(&def_dead().and(atk_hp_at_least(10)), &outcomes)`.
+#[allow(non_snake_case)]
+pub fn P(event: &Event, outcomes: &[Outcome]) -> f64 {
+    event.prob(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compound_events() {
+        let outcomes = vec![
+            Outcome{prob: 0.5, atk_hp: 15, def_hp: 0},
+            Outcome{prob: 0.2, atk_hp: 5, def_hp: 0},
+            Outcome{prob: 0.2, atk_hp: 15, def_hp: 10},
+            Outcome{prob: 0.1, atk_hp: 0, def_hp: 10},
+        ];
+        let clean_kill = def_dead().and(atk_hp_at_least(10));
+        assert!((P(&clean_kill, &outcomes) - 0.5).abs() < 1e-9);
+        let bad = atk_dead().or(def_hp_at_least(1).and(atk_hp_at_most(10)));
+        assert!((P(&bad, &outcomes) - 0.1).abs() < 1e-9);
+        assert!((P(&clean_kill.not(), &outcomes) - 0.5).abs() < 1e-9);
+        let low = P(&def_hp_at_most(5), &outcomes);
+        assert!((low - outcomes.prob_def_dies()).abs() < 1e-9);
+    }
+}
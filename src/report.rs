@@ -0,0 +1,164 @@
+//! A full report on a single combat: the possible outcomes, plus a table
+//! breaking down each strike. The table is what lets a UI say things like
+//! "80% of your kills happen on the follow-up".
+
+use crate::fegame::FEGame;
+use crate::format::ProbFormat;
+use crate::simple_calc::{attack_sequence, side_strike, CombatStats, Outcome,
+                         OutcomeDist, Side, SpeedDiff};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The statistics for a single strike in combat.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrikeRow {
+    /// The position of the strike in combat, counting from 0.
+    pub index: usize,
+
+    /// The side making the strike.
+    pub striker: Side,
+
+    /// The true chance the strike hits (0-1).
+    pub true_hit: f64,
+
+    /// The true chance the strike crits (0-1), which includes the chance it
+    /// hits.
+    pub true_crit: f64,
+
+    /// The chance the strike happens at all, meaning both sides are still
+    /// alive when it comes up.
+    pub prob_happens: f64,
+
+    /// The chance this is the strike that kills the target.
+    pub prob_kills: f64,
+}
+
+/// The possible outcomes of a combat along with a strike-by-strike breakdown.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CombatReport {
+    /// The game whose rules were used.
+    pub game: FEGame,
+
+    /// The possible outcomes of combat.
+    pub outcomes: Vec<Outcome>,
+
+    /// Every strike that can happen in combat, in order. Brave weapons get a
+    /// row for each strike, and strikes from procs like Astra are counted as
+    /// part of the strike they replace.
+    pub strikes: Vec<StrikeRow>,
+}
+
+impl CombatReport {
+    /// Computes the report for the given combat.
+    pub fn new(game: FEGame, atk: CombatStats, atk_hp: u32,
+               def: CombatStats, def_hp: u32, speed: SpeedDiff) -> CombatReport {
+        let mut states = vec![Outcome{
+            prob: 1.0,
+            atk_hp,
+            def_hp,
+        }];
+        let mut strikes = vec![];
+        for (side, stats, guard) in attack_sequence(atk, def, speed) {
+            for n in 0..stats.strikes_per_attack() {
+                let target_dead = |x: &[Outcome]| match side {
+                    Side::Atk => x.prob_def_dies(),
+                    Side::Def => x.prob_atk_dies(),
+                };
+                let dead_before = target_dead(&states);
+                let prob_happens = states.prob_where(|x| {
+                    x.atk_hp > 0 && x.def_hp > 0
+                });
+                states = side_strike(game, side, &stats, guard, states, n);
+                strikes.push(StrikeRow{
+                    index: strikes.len(),
+                    striker: side,
+                    true_hit: stats.true_hit(game),
+                    true_crit: stats.prob_crit(game),
+                    prob_happens,
+                    prob_kills: target_dead(&states) - dead_before,
+                });
+            }
+        }
+
+        CombatReport{
+            game,
+            // stepping through strike by strike gives the same outcomes as
+            // running the whole combat at once
+            outcomes: states,
+            strikes,
+        }
+    }
+
+    /// Writes the strike table and the overall chances of each side dying,
+    /// using the given format for probabilities.
+    pub fn render(&self, format: &ProbFormat) -> String {
+        let mut lines = vec!(
+            format!("{:<6} {:<8} {:>12} {:>12} {:>12} {:>12}",
+                    "strike", "striker", "hit", "crit", "happens", "kills")
+        );
+        for row in self.strikes.iter() {
+            lines.push(format!(
+                "{:<6} {:<8} {:>12} {:>12} {:>12} {:>12}",
+                row.index + 1,
+                match row.striker {
+                    Side::Atk => "attacker",
+                    Side::Def => "defender",
+                },
+                format.format(row.true_hit),
+                format.format(row.true_crit),
+                format.format(row.prob_happens),
+                format.format(row.prob_kills),
+            ));
+        }
+        lines.push(format!("attacker dies: {}",
+                           format.format(self.outcomes.prob_atk_dies())));
+        lines.push(format!("defender dies: {}",
+                           format.format(self.outcomes.prob_def_dies())));
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for CombatReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(&ProbFormat::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strike_table() {
+        let atk = CombatStats{
+            dmg: 10, hit: 50, crit: 0, is_brave: true, ..Default::default()
+        };
+        let def = CombatStats{
+            dmg: 5, hit: 100, crit: 0, ..Default::default()
+        };
+        let report = CombatReport::new(FEGame::FE1, atk, 20, def, 20,
+                                       SpeedDiff::Even);
+        let strikers: Vec<Side> = report.strikes.iter().map(|x| x.striker).collect();
+        assert_eq!(strikers, vec![Side::Atk, Side::Atk, Side::Def]);
+        // the second brave strike is the only one that can kill
+        assert_eq!(report.strikes[0].prob_kills, 0.0);
+        assert!((report.strikes[1].prob_kills - 0.25).abs() < 1e-9);
+        // and the counter only happens if the defender survived
+        assert!((report.strikes[2].prob_happens - 0.75).abs() < 1e-9);
+        assert_eq!(report.strikes[2].prob_kills, 0.0);
+
+        let total_kills: f64 = report.strikes.iter()
+            .filter(|x| x.striker == Side::Atk)
+            .map(|x| x.prob_kills)
+            .sum();
+        assert!((total_kills - report.outcomes.prob_def_dies()).abs() < 1e-9);
+        assert_eq!(report.outcomes, crate::simple_calc::possible_outcomes(
+            FEGame::FE1, atk, 20, def, 20, SpeedDiff::Even
+        ));
+
+        let text = report.to_string();
+        assert!(text.contains("defender dies: 25.00%"));
+        assert_eq!(text.lines().count(), 6);
+    }
+}
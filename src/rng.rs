@@ -21,7 +21,19 @@
 //! randomness, and so a unified approach is difficult. This file tries to make
 //! that easier.
 
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
+#[cfg(feature = "rand")]
+use rand::Rng;
+
 /// One of the different RN systems used to compute hits and misses.
+// custom tables are stored inline rather than boxed so the system never needs
+// to allocate
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
 pub enum RNSystem {
     /// The honest approach: a 95% hit rate means a 95% chance of hitting, using
     /// a single random number for the calculation.
@@ -38,30 +50,115 @@ pub enum RNSystem {
     /// two numbers 0-100 average to above 90 is much smaller than a single
     /// number being above 90).
     TwoRN,
+
+    /// A house rule, like in a ROM hack, given as the true hit for every
+    /// listed hit. This can't be parsed from a string, and iterating over
+    /// the systems skips it.
+    #[strum(disabled)]
+    Custom(HitTable),
+
+    /// Every strike hits, regardless of listed hit, and crits at the listed
+    /// crit. Together with `AlwaysMiss`, this gives the best and worst cases
+    /// around a forecast, and it makes for simple tests.
+    AlwaysHit,
+
+    /// Every strike misses, regardless of listed hit.
+    AlwaysMiss,
+
+    /// Strikes hit at exactly the listed hit, like `OneRN`, but never crit.
+    NoCrits,
+}
+
+/// The true hit for every listed hit from 0 to 100, for RN systems that
+/// aren't built in. Rates are stored out of 10,000, which is exact for 2RN.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+#[serde(into = "Vec<u16>", try_from = "Vec<u16>")]
+pub struct HitTable([u16; 101]);
+
+impl HitTable {
+    /// Creates a table from the true hit (0-1) for each listed hit. Values
+    /// outside 0-1 are clamped.
+    pub fn from_fn<F: Fn(u32) -> f64>(true_hit: F) -> HitTable {
+        let mut table = [0; 101];
+        for (listed_hit, entry) in table.iter_mut().enumerate() {
+            let prob = true_hit(listed_hit as u32).clamp(0.0, 1.0);
+            *entry = (prob * 10_000.0).round() as u16;
+        }
+        HitTable(table)
+    }
+
+    /// Returns the true hit, as a number between 0 and 1, for the given
+    /// listed hit. Listed hits above 100 are treated as 100.
+    pub fn true_hit(&self, listed_hit: u32) -> f64 {
+        self.0[listed_hit.min(100) as usize] as f64 / 10_000.0
+    }
+}
+
+impl From<HitTable> for Vec<u16> {
+    fn from(table: HitTable) -> Vec<u16> {
+        table.0.to_vec()
+    }
+}
+
+impl TryFrom<Vec<u16>> for HitTable {
+    type Error = String;
+
+    fn try_from(rates: Vec<u16>) -> Result<HitTable, String> {
+        let len = rates.len();
+        let table: [u16; 101] = rates.try_into()
+            .map_err(|_| format!("expected 101 hit rates, got {}", len))?;
+        if table.iter().any(|x| *x > 10_000) {
+            return Err("hit rates are out of 10,000".to_string());
+        }
+        Ok(HitTable(table))
+    }
+}
+
+/// Returns the number of pairs of RNs from 0-99, out of 10,000, that hit at
+/// the given listed hit in 2RN games. The games hit when `(rn1 + rn2) / 2 <
+/// hit` with integer division, which is the same as `rn1 + rn2 < 2 * hit`.
+/// Counting the pairs whose sum is below `2 * hit` gives a triangle up to a
+/// listed hit of 50, and past that it's easier to subtract the triangle of
+/// pairs that miss, so 0 never hits and 100 always does.
+const fn two_rn_hits(listed_hit: u32) -> u32 {
+    let hit = if listed_hit > 100 { 100 } else { listed_hit };
+    if hit <= 50 {
+        hit * (2 * hit + 1)
+    } else {
+        // with m = 100 - hit, there are m * (2m - 1) misses
+        let m = 100 - hit;
+        100 * 100 + m - 2 * m * m
+    }
+}
+
+/// How the crit roll of a strike relates to its hit roll.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize)]
+pub enum CritModel {
+    /// The crit roll uses its own RN, independent of the hit roll, so the
+    /// chance of a crit is the true hit times the listed crit.
+    #[default]
+    Independent,
+
+    /// The crit roll reuses the RNs from the hit roll: a strike crits if those
+    /// same RNs would also have hit at the listed crit. This makes crits
+    /// follow the RN system's distortion instead of being a flat percentage.
+    SharedRn,
 }
 
 impl RNSystem {
     /// Returns the true hit rate, as a number between 0 and 1, for a listed hit
-    /// rate as described in the enum declaration.
+    /// rate as described in the enum declaration. Listed hits above 100 are
+    /// treated as 100, so the result never goes above 1.
     pub fn true_hit(&self, listed_hit: u32) -> f64 {
+        let listed_hit = listed_hit.min(100);
         let lh = listed_hit as f64;
         match self {
             RNSystem::OneRN => lh / 100.0,
-            // there's no formula for this that's easier than just enumerating
-            // the possibilities
-            // if this is a performance bottleneck, just store the values,
-            // there's only 101 of them
-            RNSystem::TwoRN => {
-                let mut num_hits = 0;
-                for i in 0..100 {
-                    for j in 0..100 {
-                        if i + j < listed_hit * 2 {
-                            num_hits += 1;
-                        }
-                    }
-                }
-                (num_hits as f64) / (100.0 * 100.0)
-            }
+            RNSystem::TwoRN => two_rn_hits(listed_hit) as f64 / (100.0 * 100.0),
+            RNSystem::Custom(table) => table.true_hit(listed_hit),
+            RNSystem::AlwaysHit => 1.0,
+            RNSystem::AlwaysMiss => 0.0,
+            RNSystem::NoCrits => lh / 100.0,
 
             RNSystem::FatesRN => if listed_hit < 50 {
                 lh / 100.0
@@ -72,12 +169,508 @@ impl RNSystem {
             }
         }
     }
+
+    /// Returns the true hit for every listed hit from 0 to 100, indexed by the
+    /// listed hit.
+    pub fn true_hit_table(&self) -> [f64; 101] {
+        let mut table = [0.0; 101];
+        for (listed_hit, entry) in table.iter_mut().enumerate() {
+            *entry = self.true_hit(listed_hit as u32);
+        }
+        table
+    }
+
+    /// Returns the table from `true_hit_table` as CSV text, with a header and
+    /// one row per listed hit.
+    pub fn true_hit_csv(&self) -> String {
+        let mut csv = String::from("listed_hit,true_hit\n");
+        for (listed_hit, true_hit) in self.true_hit_table().iter().enumerate() {
+            csv.push_str(&format!("{},{}\n", listed_hit, true_hit));
+        }
+        csv
+    }
+
+    /// Returns the lowest listed hit that gives at least the given true hit
+    /// (0-1), like the listed hit needed for a 95% reliable attack. Returns
+    /// `None` if no listed hit is enough.
+    pub fn listed_hit_needed(&self, target_true_hit: f64) -> Option<u32> {
+        // searching upwards still finds the lowest if a custom table isn't
+        // increasing
+        (0..=100).find(|x| self.true_hit(*x) >= target_true_hit)
+    }
+
+    /// Returns the true crit rate, as a number between 0 and 1, for a listed
+    /// crit rate, given that the strike hits. Every system rolls crits with a
+    /// single RN, even the ones that use two for hit, so this is the listed
+    /// crit as a plain percentage, capped at 100.
+    pub fn true_crit(&self, listed_crit: u32) -> f64 {
+        match self {
+            RNSystem::NoCrits => 0.0,
+            _ => listed_crit.min(100) as f64 / 100.0,
+        }
+    }
+
+    /// Returns the probability, as a number between 0 and 1, that a strike
+    /// with the given listed hit and crit lands as a crit, using the given
+    /// model for how the crit roll relates to the hit roll.
+    pub fn prob_crit(&self, listed_hit: u32, listed_crit: u32,
+                     model: CritModel) -> f64 {
+        match (self, model) {
+            // the degenerate systems don't roll RNs, so there's nothing to
+            // share
+            (RNSystem::AlwaysHit, _) | (RNSystem::AlwaysMiss, _)
+                | (RNSystem::NoCrits, _) | (_, CritModel::Independent) =>
+                self.true_hit(listed_hit) * self.true_crit(listed_crit),
+            // the RNs have to clear both the hit and the crit check
+            (_, CritModel::SharedRn) =>
+                self.true_hit(listed_hit.min(listed_crit)),
+        }
+    }
+
+    /// Randomly decides whether a strike with the given listed hit lands, with
+    /// the same chance as `true_hit`. This doesn't draw RNs the way the games
+    /// do: use an `RnStream` to follow a game's actual RN sequence.
+    #[cfg(feature = "rand")]
+    pub fn roll_hit(&self, rng: &mut impl Rng, listed_hit: u32) -> bool {
+        rng.gen_bool(self.true_hit(listed_hit))
+    }
+
+    /// Randomly decides whether a strike that hit also crits, with the same
+    /// chance as `true_crit`.
+    #[cfg(feature = "rand")]
+    pub fn roll_crit(&self, rng: &mut impl Rng, listed_crit: u32) -> bool {
+        rng.gen_bool(self.true_crit(listed_crit))
+    }
+}
+
+/// Convolves two probability distributions over the non-negative integers,
+/// where the value at index `i` is the probability of getting `i`. The result
+/// is the distribution of the sum of two independent values drawn from each.
+pub fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    if a.is_empty() || b.is_empty() {
+        return vec!();
+    }
+    let mut sum = vec![0.0; a.len() + b.len() - 1];
+    for (i, pa) in a.iter().enumerate() {
+        for (j, pb) in b.iter().enumerate() {
+            sum[i + j] += pa * pb;
+        }
+    }
+    sum
+}
+
+/// Returns the distribution of the sum of `n` RNs, each drawn uniformly from
+/// 0-99, indexed by the sum.
+pub fn rn_sum_distribution(n: u32) -> Vec<f64> {
+    let single = vec![0.01; 100];
+    (0..n).fold(vec![1.0], |acc, _| convolve(&acc, &single))
+}
+
+/// Returns the true hit rate, as a number between 0 and 1, for a system that
+/// averages `n` RNs and hits if the average is below the listed hit. One RN is
+/// the same as `RNSystem::OneRN`, two is the same as `RNSystem::TwoRN`, and
+/// higher numbers push hit rates even further from 50%. Listed hit above 100
+/// counts as 100. Panics if `n` is 0.
+pub fn true_hit_n(listed_hit: u32, n: u32) -> f64 {
+    assert!(n > 0, "at least one RN is needed to roll for a hit");
+    let below = listed_hit.min(100).saturating_mul(n);
+    rn_sum_distribution(n).iter().take(below as usize).sum()
+}
+
+/// Returns the chance, as a number between 0 and 1, that a stat with the given
+/// growth rate goes up on a single level up. Growths roll a single RN in every
+/// game, so unlike hit rates they're never distorted.
+pub fn prob_growth(growth: u32) -> f64 {
+    growth.min(100) as f64 / 100.0
+}
+
+/// Returns the distribution of the total points a stat with the given growth
+/// rate gains over the given number of level ups, indexed by the total. Growths
+/// above 100 give a guaranteed point for every full 100 and roll the rest as
+/// usual.
+pub fn growth_distribution(growth: u32, levels: u32) -> Vec<f64> {
+    let mut single = vec![0.0; (growth / 100) as usize];
+    let rest = (growth % 100) as f64 / 100.0;
+    single.push(1.0 - rest);
+    if rest > 0.0 {
+        single.push(rest);
+    }
+    (0..levels).fold(vec![1.0], |acc, _| convolve(&acc, &single))
+}
+
+/// Where an `RnStream` gets numbers from once its known list runs out.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+enum RnSource {
+    /// Nothing: the stream ends after the known list.
+    Exhausted,
+
+    /// A SplitMix64 generator with the given state, for reproducible streams
+    /// that don't need to match any particular game.
+    Seeded(u64),
+
+    /// The generator from the GBA games, with its three 16-bit seeds as they
+    /// appear in RAM.
+    Gba([u16; 3]),
+}
+
+impl RnSource {
+    /// Returns the next RN from 0-99, if there is one.
+    fn next_rn(&mut self) -> Option<u32> {
+        match self {
+            RnSource::Exhausted => None,
+            RnSource::Seeded(state) => {
+                *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                let mut z = *state;
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                Some(((z ^ (z >> 31)) % 100) as u32)
+            }
+            RnSource::Gba(seeds) => {
+                // each step mixes the first two seeds, folds in the third
+                // shifted left with the top bit of the second carried in,
+                // and then pushes the result onto the front of the seeds
+                let mut rn = (seeds[1] << 11).wrapping_add(seeds[0] >> 5);
+                let carry = seeds[1] >> 15;
+                rn ^= (seeds[2] << 1) | carry;
+                *seeds = [rn, seeds[0], seeds[1]];
+                // scale the 16-bit number down to 0-99
+                Some((rn as u32 * 100) >> 16)
+            }
+        }
+    }
+}
+
+/// The fixed sequence of RNs a game will use, as described in the module docs.
+/// Every roll reads off the next numbers in the list, so knowing the upcoming
+/// numbers tells you exactly which actions will succeed. RNs are from 0-99,
+/// and rolls succeed if the RN is below the listed rate.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub struct RnStream {
+    /// RNs that are already known, in order, starting at the next one.
+    upcoming: VecDeque<u32>,
+
+    /// Where more RNs come from after the known ones.
+    source: RnSource,
+
+    /// The number of RNs used so far.
+    position: usize,
+}
+
+impl RnStream {
+    /// Creates a stream that reads off the given RNs and then ends. RNs above
+    /// 99 are treated as 99.
+    pub fn from_rns(rns: &[u32]) -> RnStream {
+        RnStream{
+            upcoming: rns.iter().map(|x| *x.min(&99)).collect(),
+            source: RnSource::Exhausted,
+            position: 0,
+        }
+    }
+
+    /// Creates an endless stream from the given seed. The same seed always
+    /// gives the same RNs, but they don't match any game's generator.
+    pub fn from_seed(seed: u64) -> RnStream {
+        RnStream{
+            upcoming: VecDeque::new(),
+            source: RnSource::Seeded(seed),
+            position: 0,
+        }
+    }
+
+    /// Creates an endless stream using the GBA games' generator from the
+    /// given seeds, in the order they're stored in RAM. Reading the seeds from
+    /// an emulator gives the exact RNs the game will use from that point.
+    pub fn gba(seeds: [u16; 3]) -> RnStream {
+        RnStream{
+            upcoming: VecDeque::new(),
+            source: RnSource::Gba(seeds),
+            position: 0,
+        }
+    }
+
+    /// The number of RNs used so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Makes sure at least `n` RNs are known ahead of time, if the stream has
+    /// that many left.
+    fn fill(&mut self, n: usize) {
+        while self.upcoming.len() < n {
+            match self.source.next_rn() {
+                Some(rn) => self.upcoming.push_back(rn),
+                None => break,
+            }
+        }
+    }
+
+    /// Returns the next RN without using it.
+    pub fn peek(&mut self) -> Option<u32> {
+        self.fill(1);
+        self.upcoming.front().copied()
+    }
+
+    /// Returns the next `n` RNs without using them. This is shorter than `n`
+    /// if the stream runs out.
+    pub fn peek_n(&mut self, n: usize) -> Vec<u32> {
+        self.fill(n);
+        self.upcoming.iter().take(n).copied().collect()
+    }
+
+    /// Uses up and returns the next RN.
+    pub fn next_rn(&mut self) -> Option<u32> {
+        self.fill(1);
+        let rn = self.upcoming.pop_front()?;
+        self.position += 1;
+        Some(rn)
+    }
+
+    /// Uses up the next `n` RNs without rolling anything, like moving a unit
+    /// back and forth or waiting a turn does in some games. Returns the number
+    /// actually burned, which is less than `n` if the stream runs out.
+    pub fn burn(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.next_rn().is_some()).count()
+    }
+
+    /// Uses up `n` RNs at once, or none if there aren't that many left.
+    fn take(&mut self, n: usize) -> Option<Vec<u32>> {
+        self.fill(n);
+        if self.upcoming.len() < n {
+            return None;
+        }
+        self.position += n;
+        Some(self.upcoming.drain(..n).collect())
+    }
+
+    /// Rolls for a hit using the given RN system, using up as many RNs as the
+    /// game would. 2RN games average two RNs. *Fates* uses one RN below 50
+    /// listed hit, and above that weights two RNs as `(3 * rn1 + rn2) / 4`.
+    /// Custom systems use one RN against the true hit as a percentage, since
+    /// there's no way to know how the house rule uses RNs. `AlwaysHit` and
+    /// `AlwaysMiss` don't use any RNs. Returns `None` if the stream runs out.
+    pub fn roll_hit(&mut self, system: &RNSystem,
+                    listed_hit: u32) -> Option<bool> {
+        match system {
+            RNSystem::OneRN | RNSystem::NoCrits => {
+                Some(self.take(1)?[0] < listed_hit)
+            }
+            RNSystem::AlwaysHit => Some(true),
+            RNSystem::AlwaysMiss => Some(false),
+            RNSystem::TwoRN => {
+                let rns = self.take(2)?;
+                Some((rns[0] + rns[1]) / 2 < listed_hit)
+            }
+            RNSystem::FatesRN => if listed_hit < 50 {
+                Some(self.take(1)?[0] < listed_hit)
+            } else {
+                let rns = self.take(2)?;
+                Some((3 * rns[0] + rns[1]) / 4 < listed_hit)
+            },
+            RNSystem::Custom(table) => {
+                let rn = self.take(1)?[0];
+                Some((rn as f64) < table.true_hit(listed_hit) * 100.0)
+            }
+        }
+    }
+
+    /// Rolls for a crit on a strike that hit, which always uses a single RN.
+    pub fn roll_crit(&mut self, listed_crit: u32) -> Option<bool> {
+        Some(self.take(1)?[0] < listed_crit)
+    }
+
+    /// Rolls for a single stat's growth on level up, which always uses a
+    /// single RN.
+    pub fn roll_growth(&mut self, growth: u32) -> Option<bool> {
+        Some(self.take(1)?[0] < growth)
+    }
+
+    /// Rolls for the number of points a stat gains on level up, including the
+    /// guaranteed points from growths above 100 as in `growth_distribution`.
+    /// This always uses a single RN for the rest of the growth, even if it's 0.
+    pub fn roll_growth_points(&mut self, growth: u32) -> Option<u32> {
+        let extra = self.roll_growth(growth % 100)?;
+        Some(growth / 100 + extra as u32)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_crit_models() {
+        let independent = RNSystem::TwoRN.prob_crit(80, 20, CritModel::Independent);
+        assert!((independent - RNSystem::TwoRN.true_hit(80) * 0.2).abs() < 1e-9);
+        // sharing 2RN rolls makes low crit rates even less likely
+        let shared = RNSystem::TwoRN.prob_crit(80, 20, CritModel::SharedRn);
+        assert!((shared - RNSystem::TwoRN.true_hit(20)).abs() < 1e-9);
+        assert!(shared < independent);
+        // crit itself is never distorted
+        assert_eq!(RNSystem::TwoRN.true_crit(20), 0.2);
+        assert_eq!(RNSystem::FatesRN.true_crit(120), 1.0);
+        // and with one RN, it's just the lower of the two
+        let one = RNSystem::OneRN.prob_crit(80, 20, CritModel::SharedRn);
+        assert!((one - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_n_rn() {
+        for hit in [0, 13, 50, 70, 99, 100].iter() {
+            assert!((true_hit_n(*hit, 1) - RNSystem::OneRN.true_hit(*hit)).abs() < 1e-9);
+            assert!((true_hit_n(*hit, 2) - RNSystem::TwoRN.true_hit(*hit)).abs() < 1e-9);
+        }
+        // 3RN makes a 70 even more reliable than 2RN does
+        assert!(true_hit_n(70, 3) > true_hit_n(70, 2));
+        assert!((true_hit_n(100, 3) - 1.0).abs() < 1e-9);
+        assert!((true_hit_n(u32::MAX, 3) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_growths() {
+        assert_eq!(prob_growth(45), 0.45);
+        assert_eq!(prob_growth(130), 1.0);
+        let dist = growth_distribution(50, 2);
+        assert_eq!(dist, vec![0.25, 0.5, 0.25]);
+        // 130% is one point for sure and a 30% chance at a second
+        let dist = growth_distribution(130, 1);
+        assert!((dist[1] - 0.7).abs() < 1e-9 && (dist[2] - 0.3).abs() < 1e-9);
+        assert_eq!(growth_distribution(0, 3), vec![1.0]);
+
+        let mut stream = RnStream::from_rns(&[29, 30, 0]);
+        assert_eq!(stream.roll_growth_points(130), Some(2));
+        assert_eq!(stream.roll_growth_points(130), Some(1));
+        assert_eq!(stream.roll_growth_points(0), Some(0));
+        assert_eq!(stream.roll_growth_points(50), None);
+    }
+
+    #[test]
+    fn test_rn_stream() {
+        let mut stream = RnStream::from_rns(&[30, 90, 10, 55, 99]);
+        assert_eq!(stream.peek_n(2), vec![30, 90]);
+        // 2RN averages the first two to 60, which misses at 60 hit
+        assert_eq!(stream.roll_hit(&RNSystem::TwoRN, 60), Some(false));
+        assert_eq!(stream.roll_crit(11), Some(true));
+        assert_eq!(stream.position(), 3);
+        assert_eq!(stream.burn(1), 1);
+        assert_eq!(stream.roll_hit(&RNSystem::TwoRN, 100), None);
+        // a roll that runs out of RNs doesn't use any up
+        assert_eq!(stream.roll_growth(100), Some(true));
+        assert_eq!(stream.next_rn(), None);
+        assert_eq!(stream.burn(3), 0);
+
+        let mut a = RnStream::from_seed(17);
+        let mut b = RnStream::from_seed(17);
+        assert_eq!(a.peek_n(100), b.peek_n(100));
+        assert!(a.peek_n(100).iter().all(|x| *x < 100));
+        a.burn(50);
+        assert_eq!(a.peek(), b.peek_n(51).pop());
+
+        // computed separately from the generator's definition
+        let mut gba = RnStream::gba([0x1496, 0x9A0D, 0x5F8C]);
+        assert_eq!(gba.peek_n(5), vec![84, 51, 77, 56, 75]);
+    }
+
+    #[test]
+    fn test_custom() {
+        // a house rule where everything is 10 points more accurate
+        let table = HitTable::from_fn(|x| (x + 10) as f64 / 100.0);
+        let custom = RNSystem::Custom(table);
+        assert_eq!(custom.true_hit(50), 0.6);
+        assert_eq!(custom.true_hit(95), 1.0);
+        let one_rn = HitTable::from_fn(|x| RNSystem::OneRN.true_hit(x));
+        assert_eq!(RNSystem::Custom(one_rn).true_hit(37), 0.37);
+
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(serde_json::from_str::<HitTable>(&json).unwrap(), table);
+        assert!(serde_json::from_str::<HitTable>("[1, 2, 3]").is_err());
+    }
+
+    #[test]
+    fn test_hit_saturates() {
+        use strum::IntoEnumIterator;
+        for system in RNSystem::iter() {
+            assert_eq!(system.true_hit(120), system.true_hit(100));
+            assert!(system.true_hit(u32::MAX) <= 1.0);
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_sampling() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let n = 20_000;
+        let hits = (0..n).filter(|_| RNSystem::TwoRN.roll_hit(&mut rng, 80))
+            .count();
+        let rate = hits as f64 / n as f64;
+        assert!((rate - RNSystem::TwoRN.true_hit(80)).abs() < 0.01);
+        assert!(RNSystem::OneRN.roll_hit(&mut rng, 100));
+        assert!(!RNSystem::AlwaysMiss.roll_hit(&mut rng, 100));
+        assert!(!RNSystem::NoCrits.roll_crit(&mut rng, 100));
+        assert!(RNSystem::TwoRN.roll_crit(&mut rng, 120));
+    }
+
+    #[test]
+    fn test_true_hit_table() {
+        let table = RNSystem::TwoRN.true_hit_table();
+        assert_eq!(table[70], RNSystem::TwoRN.true_hit(70));
+        assert_eq!(table[100], 1.0);
+        let csv = RNSystem::OneRN.true_hit_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 102);
+        assert_eq!(lines[0], "listed_hit,true_hit");
+        assert_eq!(lines[51], "50,0.5");
+    }
+
+    #[test]
+    fn test_listed_hit_needed() {
+        assert_eq!(RNSystem::OneRN.listed_hit_needed(0.95), Some(95));
+        // 2RN needs much less for the same reliability
+        let needed = RNSystem::TwoRN.listed_hit_needed(0.95).unwrap();
+        assert!(RNSystem::TwoRN.true_hit(needed) >= 0.95);
+        assert!(RNSystem::TwoRN.true_hit(needed - 1) < 0.95);
+        assert!(needed < 85);
+        assert_eq!(RNSystem::TwoRN.listed_hit_needed(0.0), Some(0));
+        assert_eq!(RNSystem::AlwaysMiss.listed_hit_needed(0.5), None);
+        assert_eq!(RNSystem::OneRN.listed_hit_needed(1.5), None);
+    }
+
+    #[test]
+    fn test_degenerate_systems() {
+        for model in [CritModel::Independent, CritModel::SharedRn].iter() {
+            let sure = RNSystem::AlwaysHit.prob_crit(0, 30, *model);
+            assert!((sure - 0.3).abs() < 1e-9);
+            assert_eq!(RNSystem::AlwaysMiss.prob_crit(100, 30, *model), 0.0);
+            assert_eq!(RNSystem::NoCrits.prob_crit(100, 100, *model), 0.0);
+        }
+        assert_eq!(RNSystem::AlwaysHit.true_hit(0), 1.0);
+        assert_eq!(RNSystem::NoCrits.true_hit(45), 0.45);
+        let mut stream = RnStream::from_rns(&[]);
+        assert_eq!(stream.roll_hit(&RNSystem::AlwaysHit, 0), Some(true));
+    }
+
+    #[test]
+    fn test_rn_system_derives() {
+        use std::str::FromStr;
+        use strum::IntoEnumIterator;
+
+        assert_eq!(RNSystem::from_str("TwoRN"), Ok(RNSystem::TwoRN));
+        assert!(RNSystem::from_str("Custom").is_err());
+        assert_eq!(RNSystem::FatesRN.to_string(), "FatesRN");
+        let systems: Vec<RNSystem> = RNSystem::iter().collect();
+        assert!(systems.contains(&RNSystem::NoCrits));
+        assert!(!systems.iter().any(|x| matches!(x, RNSystem::Custom(_))));
+        let json = serde_json::to_string(&RNSystem::OneRN).unwrap();
+        assert_eq!(serde_json::from_str::<RNSystem>(&json).unwrap(),
+                   RNSystem::OneRN);
+    }
+
+    #[test]
+    fn test_convolve() {
+        assert_eq!(convolve(&[0.5, 0.5], &[0.5, 0.5]), vec![0.25, 0.5, 0.25]);
+        assert_eq!(convolve(&[], &[1.0]), Vec::<f64>::new());
+    }
+
     #[test]
     fn test_onern_rng() {
         assert!((RNSystem::OneRN.true_hit(70) - 0.7).abs() <= 0.01);
@@ -91,5 +684,15 @@ mod tests {
     #[test]
     fn test_tworn_rng() {
         assert!((RNSystem::TwoRN.true_hit(70) - 0.823).abs() <= 0.01);
+        // the closed form matches enumerating every pair of RNs
+        for listed_hit in 0..=100 {
+            let hits = (0..100).flat_map(|i| (0..100).map(move |j| i + j))
+                .filter(|sum| *sum < listed_hit * 2)
+                .count();
+            assert_eq!(two_rn_hits(listed_hit) as usize, hits);
+        }
+        assert_eq!(RNSystem::TwoRN.true_hit(0), 0.0);
+        assert_eq!(RNSystem::TwoRN.true_hit(100), 1.0);
+        assert_eq!(RNSystem::TwoRN.true_hit(150), 1.0);
     }
 }
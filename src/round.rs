@@ -10,7 +10,16 @@
 //! calculations.
 
 
+use crate::durability::DurableOutcome;
+use crate::fegame::{CritDamage, FEGame, MechanicsConfig};
 use crate::rng::RNSystem;
+use crate::simple_calc::{percent_of, side_strike, Branch, CombatStats,
+                         DefenseProc, Outcome, OutcomeDist, Side};
+use crate::weapon::{CounterSkill, Range};
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
 
 /// Describes how many times the attacker/defender will strike. There are two
 /// kinds of doubling: repeated attacks, that occur when the striker outspeeds
@@ -20,15 +29,64 @@ use crate::rng::RNSystem;
 /// if they outspeed their target and are using a brave weapon, for example. For
 /// ease of reference, continued attacks are called "brave" after the name of
 /// the weapon type that most commonly produces them.
+///
+/// In general, a side makes some number of attacks, each of which is some
+/// number of strikes in a row. The two sides take turns attacking, starting
+/// with the attacker, until both are out of attacks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AttackRepeat {
-    /// Whether the attacker naturally outspeeds the defender.
-    outspeeds: bool,
-    /// Whether the attacker gets continued attacks from a brave weapon or brave
-    /// combat art.
-    is_brave: bool
+    /// The number of separate attacks: 2 if the side outspeeds the other, and
+    /// 1 otherwise. A side that can't counter makes 0 attacks.
+    attacks: u32,
+
+    /// The number of strikes in each attack: 2 for brave weapons, and 1
+    /// otherwise. Skills like Astra can raise this further.
+    strikes_per_attack: u32,
+}
+
+impl AttackRepeat {
+    /// Returns the usual repeat structure for a side that may outspeed the
+    /// other and may have a brave weapon.
+    pub fn new(outspeeds: bool, is_brave: bool) -> AttackRepeat {
+        AttackRepeat{
+            attacks: if outspeeds { 2 } else { 1 },
+            strikes_per_attack: if is_brave { 2 } else { 1 },
+        }
+    }
+
+    /// Returns a repeat structure with the given number of attacks of the
+    /// given number of strikes each.
+    pub fn with_strikes(attacks: u32, strikes_per_attack: u32) -> AttackRepeat {
+        AttackRepeat{
+            attacks,
+            strikes_per_attack,
+        }
+    }
+
+    /// The number of separate attacks the side makes.
+    pub fn attacks(&self) -> u32 {
+        self.attacks
+    }
+
+    /// The number of strikes in each attack.
+    pub fn strikes_per_attack(&self) -> u32 {
+        self.strikes_per_attack
+    }
+
+    /// The total number of strikes the side makes if combat goes the distance.
+    pub fn total_strikes(&self) -> u32 {
+        self.attacks * self.strikes_per_attack
+    }
+}
+
+impl Default for AttackRepeat {
+    fn default() -> AttackRepeat {
+        AttackRepeat::new(false, false)
+    }
 }
 
 /// The statistics for a single strike in a round of combat.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Attack {
     /// The listed hit rate as a percentage between 0 and 100.
     hit: u32,
@@ -38,45 +96,1224 @@ pub struct Attack {
 
     /// The damage dealt by the attack.
     dmg: u32,
+
+    /// The damage dealt by a critical hit: usually triple `dmg`.
+    crit_dmg: u32,
+
+    /// Whether the strike can't miss. Crit is still rolled.
+    always_hits: bool,
+
+    /// The target's skill that can reduce the strike's damage, like Pavise.
+    target_guard: Option<DefenseProc>,
 }
 
 impl Attack {
+    /// Creates an attack with the given listed hit, crit, and damage, where
+    /// crits do triple damage, capped at `u32::MAX`. Unlike `RoundBuilder`,
+    /// this doesn't check that hit and crit are at most 100.
+    pub fn new(hit: u32, crit: u32, dmg: u32) -> Attack {
+        Attack{
+            hit,
+            crit,
+            dmg,
+            crit_dmg: dmg.saturating_mul(3),
+            always_hits: false,
+            target_guard: None,
+        }
+    }
+
+    /// Returns the same attack against a target with the given defensive
+    /// skill, like Pavise or Great Shield.
+    pub fn with_target_guard(self, guard: DefenseProc) -> Attack {
+        Attack{target_guard: Some(guard), ..self}
+    }
+
+    /// Returns the same attack, except that it can't miss, like a gambit or
+    /// a scripted hit.
+    pub fn with_always_hits(self) -> Attack {
+        Attack{always_hits: true, ..self}
+    }
+
+    /// Returns the same attack with crit damage worked out by the given model
+    /// against a target with the given Def. When crits double Atk, like in FE4
+    /// and FE5, they do `2 * dmg + def` damage.
+    pub fn with_crit_damage(self, model: CritDamage, def: u32) -> Attack {
+        let crit_dmg = match model {
+            CritDamage::Triple => self.dmg.saturating_mul(3),
+            CritDamage::DoubleAtk => {
+                self.dmg.saturating_mul(2).saturating_add(def)
+            }
+        };
+        Attack{crit_dmg, ..self}
+    }
+
+    /// The listed hit rate as a percentage between 0 and 100.
+    pub fn hit(&self) -> u32 {
+        self.hit
+    }
+
+    /// The critical rate as a percentage between 0 and 100.
+    pub fn crit(&self) -> u32 {
+        self.crit
+    }
+
+    /// The damage dealt by the attack.
+    pub fn dmg(&self) -> u32 {
+        self.dmg
+    }
+
+    /// The damage dealt by a critical hit.
+    pub fn crit_dmg(&self) -> u32 {
+        self.crit_dmg
+    }
+
+    /// Whether the strike can't miss.
+    pub fn always_hits(&self) -> bool {
+        self.always_hits
+    }
+
+    /// The target's skill that can reduce the strike's damage, if any.
+    pub fn target_guard(&self) -> Option<DefenseProc> {
+        self.target_guard
+    }
+
     /// Returns the probability (as a number 0-1) that an attack with these
     /// stats will kill a target with the given HP and RNG system.
     pub fn prob_kills(&self, rn: RNSystem, def_hp: u32) -> f64 {
-        let prob_hit = rn.true_hit(self.hit);
-        let prob_miss = 1.0 - prob_hit;
-        let prob_crit = prob_hit * (self.crit as f64 / 100.0);
-        let prob_normal_hit = prob_hit - prob_crit;
+        self.prob_leaves_below(rn, def_hp, 1)
+    }
+
+    /// Returns the probability (as a number 0-1) that an attack with these
+    /// stats will leave a target with the given HP below `hp`, for planning
+    /// chip damage before a kill.
+    pub fn prob_leaves_below(&self, rn: RNSystem, def_hp: u32, hp: u32) -> f64 {
+        let prob_hit = if self.always_hits {
+            1.0
+        } else {
+            rn.true_hit(self.hit)
+        };
+        let prob_crit = prob_hit * rn.true_crit(self.crit);
+        let (prob_guard, guard_percent) = match self.target_guard {
+            Some(guard) => (guard.rate.min(100) as f64 / 100.0,
+                            guard.dmg_percent),
+            None => (0.0, 100),
+        };
+        let below = |dmg: u32| def_hp.saturating_sub(dmg) < hp;
+        if below(0) {
+            return 1.0;
+        }
+        // a landed strike does full damage unless the target's skill reduces
+        // it
+        let landed = |prob: f64, dmg: u32| {
+            let full = if below(dmg) { 1.0 - prob_guard } else { 0.0 };
+            let reduced = if below(percent_of(dmg, guard_percent)) {
+                prob_guard
+            } else {
+                0.0
+            };
+            prob * (full + reduced)
+        };
+        landed(prob_hit - prob_crit, self.dmg)
+            + landed(prob_crit, self.crit_dmg)
     }
+
+    /// Returns the equivalent stats for a single strike in the `simple_calc`
+    /// engine.
+    fn combat_stats(&self) -> CombatStats {
+        CombatStats{
+            dmg: self.dmg,
+            hit: self.hit,
+            crit: self.crit,
+            crit_dmg: Some(self.crit_dmg),
+            always_hits: self.always_hits,
+            ..Default::default()
+        }
+    }
+}
+
+/// The order the two sides attack in. Skills that give extra attacks, like
+/// Quick Riposte, change `AttackRepeat` instead, since they don't change who
+/// goes first.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StrikeOrder {
+    /// The attacker attacks first, and the sides take turns from there.
+    #[default]
+    Standard,
+
+    /// The defender attacks first, like with Vantage.
+    Vantage,
+
+    /// The attacker makes all of their attacks before the defender gets to
+    /// counter, like with Desperation.
+    Desperation,
+
+    /// The defender attacks first, and then the attacker makes all of their
+    /// attacks in a row.
+    VantageDesperation,
 }
 
+impl StrikeOrder {
+    /// Returns the side making each attack, in order, given how many attacks
+    /// each side makes.
+    pub fn attacks(&self, atk_attacks: u32, def_attacks: u32) -> Vec<Side> {
+        let vantage = matches!(self, StrikeOrder::Vantage
+                               | StrikeOrder::VantageDesperation);
+        let desperation = matches!(self, StrikeOrder::Desperation
+                                   | StrikeOrder::VantageDesperation);
+        let (mut atk_left, mut def_left) = (atk_attacks, def_attacks);
+        let mut turn = if vantage { Side::Def } else { Side::Atk };
+        let mut order = vec!();
+        while atk_left > 0 || def_left > 0 {
+            match turn {
+                Side::Atk if atk_left > 0 => {
+                    let attacks = if desperation { atk_left } else { 1 };
+                    order.extend((0..attacks).map(|_| Side::Atk));
+                    atk_left -= attacks;
+                }
+                Side::Def if def_left > 0 => {
+                    order.push(Side::Def);
+                    def_left -= 1;
+                }
+                _ => {}
+            }
+            turn = turn.other();
+        }
+        order
+    }
+}
+
+/// A single round of combat between an attacker and a defender, as shown in a
+/// combat preview. Each side has a list of strikes, so that individual strikes
+/// can differ from the rest, like a Sure Strike that only activates once.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Round {
     /// The attacker HP before combat starts.
     atk_hp: u32,
 
-    /// The hit, crit, and damage of the attacker's action.
-    attacker: Attack,
+    /// The hit, crit, and damage of each of the attacker's strikes, in order.
+    attacker: Vec<Attack>,
 
     /// The defender HP before combat starts.
     def_hp: u32,
 
-    /// The hit, crit, and damage of the defender's action.
-    defender: Attack,
+    /// The hit, crit, and damage of each of the defender's strikes, in order.
+    defender: Vec<Attack>,
 
     /// Describes how the attacker will strike, with possible multiple strikes.
     atk_repeat: AttackRepeat,
 
-    /// Describes how the attacker will strike, with possible multiple strikes.
-    def_repeat: AttackRepeat
+    /// Describes how the defender will strike, with possible multiple strikes.
+    def_repeat: AttackRepeat,
+
+    /// The order the two sides take their attacks in.
+    order: StrikeOrder,
 }
 
 impl Round {
+    /// Creates a round directly from each side's strikes. Use `RoundBuilder`
+    /// instead to have the numbers checked. If a side's list of strikes is
+    /// shorter than its repeat structure calls for, the missing strikes are
+    /// skipped.
+    pub fn new(atk_hp: u32, attacker: Vec<Attack>, atk_repeat: AttackRepeat,
+               def_hp: u32, defender: Vec<Attack>, def_repeat: AttackRepeat,
+               order: StrikeOrder) -> Round {
+        Round{
+            atk_hp,
+            attacker,
+            def_hp,
+            defender,
+            atk_repeat,
+            def_repeat,
+            order,
+        }
+    }
+
+    /// The attacker HP before combat starts.
+    pub fn atk_hp(&self) -> u32 {
+        self.atk_hp
+    }
+
+    /// The attacker's strikes, in order.
+    pub fn attacker(&self) -> &[Attack] {
+        &self.attacker
+    }
+
+    /// The defender HP before combat starts.
+    pub fn def_hp(&self) -> u32 {
+        self.def_hp
+    }
+
+    /// The defender's strikes, in order.
+    pub fn defender(&self) -> &[Attack] {
+        &self.defender
+    }
+
+    /// How the attacker strikes.
+    pub fn atk_repeat(&self) -> AttackRepeat {
+        self.atk_repeat
+    }
+
+    /// How the defender strikes.
+    pub fn def_repeat(&self) -> AttackRepeat {
+        self.def_repeat
+    }
+
+    /// The order the two sides take their attacks in.
+    pub fn order(&self) -> StrikeOrder {
+        self.order
+    }
+
+    /// Returns the possible outcomes of the round using the given randomness
+    /// system. The sides attack in the round's strike order until both are out
+    /// of attacks. Critical hits do each strike's `crit_dmg`.
+    pub fn outcome_distribution(&self, rn: RNSystem) -> Vec<Outcome> {
+        self.outcomes_from(engine(rn), vec!(Outcome{
+            prob: 1.0,
+            atk_hp: self.atk_hp,
+            def_hp: self.def_hp,
+        }), |_, _| true)
+    }
+
+    /// Returns the possible outcomes of the round, with each side's weapon
+    /// uses, starting from the given uses. Each strike that lands uses one up,
+    /// and a side whose weapon breaks makes no more strikes. Give `None` for
+    /// weapons that don't break, including in games without durability.
+    pub fn durable_outcomes(&self, rn: RNSystem, atk_uses: Option<u32>,
+                            def_uses: Option<u32>) -> Vec<DurableOutcome> {
+        let outcome = Outcome{
+            prob: 1.0,
+            atk_hp: self.atk_hp,
+            def_hp: self.def_hp,
+        };
+        self.durable_outcomes_after(rn, vec!(DurableOutcome{
+            outcome,
+            atk_uses,
+            def_uses,
+        }))
+    }
+
+    /// Like `durable_outcomes`, but starts from an existing list of possible
+    /// states, so HP and weapon uses carry over from earlier combat.
+    pub fn durable_outcomes_after(&self, rn: RNSystem,
+                                  states: Vec<DurableOutcome>)
+                                  -> Vec<DurableOutcome> {
+        self.outcomes_from(engine(rn), states, |x, side| !x.is_broken(side))
+    }
+
+    /// Returns the possible outcomes of the round starting from the given
+    /// distribution of states instead of full HP. States where the given
+    /// function says a side can't strike skip that side's strikes.
+    fn outcomes_from<B: Branch>(&self, engine: FEGame, mut states: Vec<B>,
+                                can_strike: impl Fn(&B, Side) -> bool)
+                                -> Vec<B> {
+        let order = self.order.attacks(self.atk_repeat.attacks,
+                                       self.def_repeat.attacks);
+        // the number of strikes each side has made so far
+        let (mut atk_made, mut def_made) = (0, 0);
+        for side in order {
+            let (strikes, repeat, made) = match side {
+                Side::Atk => (&self.attacker, self.atk_repeat, &mut atk_made),
+                Side::Def => (&self.defender, self.def_repeat, &mut def_made),
+            };
+            for n in 0..repeat.strikes_per_attack {
+                if let Some(strike) = strikes.get(*made) {
+                    let stats = strike.combat_stats();
+                    let (ready, idle): (Vec<B>, Vec<B>) = states.into_iter()
+                        .partition(|x| can_strike(x, side));
+                    states = side_strike(engine, side, &stats,
+                                         strike.target_guard, ready,
+                                         n as usize);
+                    if !idle.is_empty() {
+                        states.extend(idle);
+                        states = B::merge(states);
+                    }
+                }
+                *made += 1;
+            }
+        }
+        states
+    }
+
     /// The probability that the attacker survives after combat concludes using
     /// the given randomness system, as a number between 0 and 1.
     pub fn prob_atk_survival(&self, rn: RNSystem) -> f64 {
-        // attacker gets first strike: determine probability that this strike
-        // kills
-        let kills_in_one =
+        self.outcome_distribution(rn).prob_where(|x| x.atk_hp > 0)
+    }
+
+    /// The probability that the defender survives after combat concludes using
+    /// the given randomness system, as a number between 0 and 1.
+    pub fn prob_def_survival(&self, rn: RNSystem) -> f64 {
+        self.outcome_distribution(rn).prob_where(|x| x.def_hp > 0)
+    }
+
+    /// The probability that the defender dies in combat using the given
+    /// randomness system, as a number between 0 and 1.
+    pub fn prob_def_dies(&self, rn: RNSystem) -> f64 {
+        self.outcome_distribution(rn).prob_def_dies()
+    }
+
+    /// The probability that the defender dies within the given number of
+    /// rounds of this same combat, as a number between 0 and 1. Each round
+    /// starts with the HP both sides were left with after the last, and the
+    /// rolls are fresh each time. Nobody heals between rounds.
+    pub fn prob_def_dies_within(&self, n_rounds: u32, rn: RNSystem) -> f64 {
+        let engine = engine(rn);
+        let mut states = vec!(Outcome{
+            prob: 1.0,
+            atk_hp: self.atk_hp,
+            def_hp: self.def_hp,
+        });
+        for _ in 0..n_rounds {
+            // combat is over for good once someone dies
+            let (done, alive): (Vec<Outcome>, Vec<Outcome>) = states.into_iter()
+                .partition(|x| x.atk_hp == 0 || x.def_hp == 0);
+            states = done;
+            states.extend(self.outcomes_from(engine, alive, |_, _| true));
+            states = Outcome::collect(states);
+        }
+        states.prob_def_dies()
+    }
+
+    /// The expected damage the attacker deals to the defender using the given
+    /// randomness system. Strikes that would happen after someone dies don't
+    /// count, and neither does damage past the defender's remaining HP.
+    pub fn expected_damage_dealt(&self, rn: RNSystem) -> f64 {
+        self.expected_damage(&self.outcome_distribution(rn)).0
+    }
+
+    /// The expected damage the attacker takes from the defender using the
+    /// given randomness system, counted the same way as
+    /// `expected_damage_dealt`.
+    pub fn expected_damage_taken(&self, rn: RNSystem) -> f64 {
+        self.expected_damage(&self.outcome_distribution(rn)).1
+    }
+
+    /// Returns the expected damage dealt and taken over the given outcomes of
+    /// the round. Outcomes that somehow end above the starting HP count as no
+    /// damage rather than wrapping around.
+    fn expected_damage(&self, outcomes: &[Outcome]) -> (f64, f64) {
+        outcomes.iter().fold((0.0, 0.0), |(dealt, taken), x| (
+            dealt + x.prob * self.def_hp.saturating_sub(x.def_hp) as f64,
+            taken + x.prob * self.atk_hp.saturating_sub(x.atk_hp) as f64,
+        ))
+    }
+
+    /// The probability that both sides are still alive after combat concludes
+    /// using the given randomness system, as a number between 0 and 1.
+    pub fn prob_both_survive(&self, rn: RNSystem) -> f64 {
+        self.outcome_distribution(rn)
+            .prob_where(|x| x.atk_hp > 0 && x.def_hp > 0)
+    }
+
+    /// Returns the headline numbers for the round using the given randomness
+    /// system, computing the outcomes only once.
+    pub fn summary(&self, rn: RNSystem) -> RoundSummary {
+        self.summary_in(engine(rn))
+    }
+
+    /// Returns the headline numbers for the round, run through the engine
+    /// with the given game.
+    fn summary_in(&self, engine: FEGame) -> RoundSummary {
+        let outcomes = self.outcomes_from(engine, vec!(Outcome{
+            prob: 1.0,
+            atk_hp: self.atk_hp,
+            def_hp: self.def_hp,
+        }), |_, _| true);
+        let (expected_damage_dealt, expected_damage_taken) =
+            self.expected_damage(&outcomes);
+        RoundSummary{
+            prob_kill: outcomes.prob_def_dies(),
+            prob_survival: 1.0 - outcomes.prob_atk_dies(),
+            expected_damage_dealt,
+            expected_damage_taken,
+        }
+    }
+}
+
+/// The numbers a forecast shows for a single round, from the attacker's point
+/// of view.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoundSummary {
+    /// The probability the defender dies (0-1).
+    pub prob_kill: f64,
+
+    /// The probability the attacker survives (0-1).
+    pub prob_survival: f64,
+
+    /// The expected damage the attacker deals.
+    pub expected_damage_dealt: f64,
+
+    /// The expected damage the attacker takes.
+    pub expected_damage_taken: f64,
+}
+
+impl RoundSummary {
+    /// Returns whether this is at least as good as the other summary in every
+    /// way: at least as likely to kill and survive, dealing at least as much
+    /// damage and taking no more.
+    pub fn at_least_as_good_as(&self, other: &RoundSummary) -> bool {
+        self.prob_kill >= other.prob_kill
+            && self.prob_survival >= other.prob_survival
+            && self.expected_damage_dealt >= other.expected_damage_dealt
+            && self.expected_damage_taken <= other.expected_damage_taken
+    }
+}
+
+/// Which of two rounds is better, if either.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Dominance {
+    /// The first round is at least as good in every way and better in some.
+    First,
+
+    /// The second round is at least as good in every way and better in some.
+    Second,
+
+    /// The rounds have the same numbers.
+    Equal,
+
+    /// Each round is better in some way, so the choice is a tradeoff.
+    Neither,
+}
+
+/// Two rounds side by side, like the same enemy attacked with two weapons.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Comparison {
+    /// The numbers for the first round.
+    pub first: RoundSummary,
+
+    /// The numbers for the second round.
+    pub second: RoundSummary,
+
+    /// Which round is better, if either.
+    pub dominant: Dominance,
+}
+
+/// Compares two candidate rounds using the given randomness system, like an
+/// Iron Sword and a Killing Edge against the same enemy.
+pub fn compare(first: &Round, second: &Round, rn: RNSystem) -> Comparison {
+    let engine = engine(rn);
+    let first = first.summary_in(engine);
+    let second = second.summary_in(engine);
+    let dominant = match (first.at_least_as_good_as(&second),
+                          second.at_least_as_good_as(&first)) {
+        (true, true) => Dominance::Equal,
+        (true, false) => Dominance::First,
+        (false, true) => Dominance::Second,
+        (false, false) => Dominance::Neither,
+    };
+    Comparison{first, second, dominant}
+}
+
+/// Returns the game to run the `simple_calc` engine with for the given RN
+/// system: a custom ruleset that uses the RN system as it is, so degenerate
+/// systems like `AlwaysHit` and `NoCrits` work the same as anywhere else. Each
+/// strike gives its crit damage directly, so the game's crit formula doesn't
+/// matter.
+fn engine(rn: RNSystem) -> FEGame {
+    FEGame::Custom(MechanicsConfig{rn_system: rn, ..FEGame::FE7.mechanics()})
+}
+
+/// Builds a `Round`, checking that the numbers make sense. Hit and crit have to
+/// be between 0 and 100, and both sides need to start with some HP.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RoundBuilder {
+    atk_hp: u32,
+    attacker: (u32, u32, u32),
+    atk_repeat: AttackRepeat,
+    def_hp: u32,
+    defender: (u32, u32, u32),
+    def_repeat: AttackRepeat,
+    order: StrikeOrder,
+    /// Strikes that differ from the rest, by side and index.
+    overrides: Vec<(Side, usize, (u32, u32, u32))>,
+    /// The distance between the two sides.
+    distance: Option<u32>,
+    /// The range of the attacker's weapon.
+    atk_range: Option<Range>,
+    /// The range of the defender's weapon.
+    def_range: Option<Range>,
+    /// Whether the defender can counter regardless of range.
+    counter_override: Option<bool>,
+    /// The defender's skills that let them counter at more ranges.
+    counter_skills: Vec<CounterSkill>,
+    /// How crits do damage, with the attacker's and defender's Def.
+    crits: Option<(CritDamage, u32, u32)>,
+    /// Strikes that can't miss, by side and index.
+    sure_hits: Vec<(Side, usize)>,
+    /// The attacker's defensive skill.
+    atk_guard: Option<DefenseProc>,
+    /// The defender's defensive skill.
+    def_guard: Option<DefenseProc>,
+}
+
+impl RoundBuilder {
+    /// Creates a builder where both sides have no HP and never hit.
+    pub fn new() -> RoundBuilder {
+        RoundBuilder::default()
+    }
+
+    /// Sets the attacker's HP before combat starts.
+    pub fn atk_hp(mut self, hp: u32) -> RoundBuilder {
+        self.atk_hp = hp;
+        self
+    }
+
+    /// Sets the defender's HP before combat starts.
+    pub fn def_hp(mut self, hp: u32) -> RoundBuilder {
+        self.def_hp = hp;
+        self
+    }
+
+    /// Sets the attacker's listed hit, listed crit, and damage.
+    pub fn attacker(mut self, hit: u32, crit: u32, dmg: u32) -> RoundBuilder {
+        self.attacker = (hit, crit, dmg);
+        self
+    }
+
+    /// Sets the defender's listed hit, listed crit, and damage.
+    pub fn defender(mut self, hit: u32, crit: u32, dmg: u32) -> RoundBuilder {
+        self.defender = (hit, crit, dmg);
+        self
+    }
+
+    /// Sets whether the attacker outspeeds the defender and whether they have a
+    /// brave weapon.
+    pub fn atk_repeat(mut self, outspeeds: bool, is_brave: bool) -> RoundBuilder {
+        self.atk_repeat = AttackRepeat::new(outspeeds, is_brave);
+        self
+    }
+
+    /// Sets whether the defender outspeeds the attacker and whether they have a
+    /// brave weapon.
+    pub fn def_repeat(mut self, outspeeds: bool, is_brave: bool) -> RoundBuilder {
+        self.def_repeat = AttackRepeat::new(outspeeds, is_brave);
+        self
+    }
+
+    /// Sets the attacker's repeat structure directly, for more strikes than
+    /// doubling and brave weapons allow.
+    pub fn atk_strikes(mut self, repeat: AttackRepeat) -> RoundBuilder {
+        self.atk_repeat = repeat;
+        self
+    }
+
+    /// Sets the defender's repeat structure directly, for more strikes than
+    /// doubling and brave weapons allow.
+    pub fn def_strikes(mut self, repeat: AttackRepeat) -> RoundBuilder {
+        self.def_repeat = repeat;
+        self
+    }
+
+    /// Gives the attacker's strike with the given index, counting from 0,
+    /// its own listed hit, listed crit, and damage.
+    pub fn atk_strike(mut self, index: usize, hit: u32, crit: u32,
+                      dmg: u32) -> RoundBuilder {
+        self.overrides.push((Side::Atk, index, (hit, crit, dmg)));
+        self
+    }
+
+    /// Gives the defender's strike with the given index, counting from 0,
+    /// its own listed hit, listed crit, and damage.
+    pub fn def_strike(mut self, index: usize, hit: u32, crit: u32,
+                      dmg: u32) -> RoundBuilder {
+        self.overrides.push((Side::Def, index, (hit, crit, dmg)));
+        self
+    }
+
+    /// Sets the distance combat happens at and the range of the defender's
+    /// weapon. If the defender can't reach, they don't counter at all.
+    pub fn engagement(mut self, distance: u32, def_range: Range) -> RoundBuilder {
+        self.distance = Some(distance);
+        self.def_range = Some(def_range);
+        self
+    }
+
+    /// Sets the distance combat happens at.
+    pub fn distance(mut self, distance: u32) -> RoundBuilder {
+        self.distance = Some(distance);
+        self
+    }
+
+    /// Sets the range of the attacker's weapon. If the distance isn't set and
+    /// the weapon only reaches one distance, like a bow, combat is assumed to
+    /// happen at that distance.
+    pub fn atk_range(mut self, range: Range) -> RoundBuilder {
+        self.atk_range = Some(range);
+        self
+    }
+
+    /// Sets the range of the defender's weapon, which decides whether they can
+    /// counter.
+    pub fn def_range(mut self, range: Range) -> RoundBuilder {
+        self.def_range = Some(range);
+        self
+    }
+
+    /// Returns the distance combat happens at, if it's known.
+    fn resolved_distance(&self) -> Option<u32> {
+        match (self.distance, self.atk_range) {
+            (Some(distance), _) => Some(distance),
+            (None, Some(range)) if range.min == range.max => Some(range.min),
+            _ => None,
+        }
+    }
+
+    /// Sets whether the defender can counter regardless of the engagement
+    /// range, for special cases that the weapon's range doesn't capture.
+    pub fn counter_override(mut self, can_counter: bool) -> RoundBuilder {
+        self.counter_override = Some(can_counter);
+        self
+    }
+
+    /// Gives the defender a skill that lets them counter at ranges their
+    /// weapon can't reach. This only matters if the distance and the defender's
+    /// range are known.
+    pub fn def_counter_skill(mut self, skill: CounterSkill) -> RoundBuilder {
+        self.counter_skills.push(skill);
+        self
+    }
+
+    /// Returns whether the defender can counter at all.
+    fn can_counter(&self) -> bool {
+        let engagement = self.resolved_distance().zip(self.def_range);
+        match (self.counter_override, engagement) {
+            (Some(can_counter), _) => can_counter,
+            (None, Some((distance, range))) => {
+                range.contains(distance) || self.counter_skills.iter()
+                    .any(|x| x.counter_range(range).contains(distance))
+            }
+            (None, None) => true,
+        }
+    }
+
+    /// Makes the given strike of the given side unable to miss, counting from
+    /// 0. This is applied after `atk_strike` and `def_strike`.
+    pub fn always_hits(mut self, side: Side, index: usize) -> RoundBuilder {
+        self.sure_hits.push((side, index));
+        self
+    }
+
+    /// Gives the attacker a skill that can reduce the damage of the defender's
+    /// strikes, like Pavise or Great Shield.
+    pub fn atk_guard(mut self, guard: DefenseProc) -> RoundBuilder {
+        self.atk_guard = Some(guard);
+        self
+    }
+
+    /// Gives the defender a skill that can reduce the damage of the
+    /// attacker's strikes.
+    pub fn def_guard(mut self, guard: DefenseProc) -> RoundBuilder {
+        self.def_guard = Some(guard);
+        self
+    }
+
+    /// Sets how crits do damage, along with each side's Def, which crits that
+    /// double Atk need. Without this, crits do triple damage.
+    pub fn crit_damage(mut self, model: CritDamage, atk_def: u32,
+                       def_def: u32) -> RoundBuilder {
+        self.crits = Some((model, atk_def, def_def));
+        self
+    }
+
+    /// Sets the order the sides attack in.
+    pub fn order(mut self, order: StrikeOrder) -> RoundBuilder {
+        self.order = order;
+        self
+    }
+
+    /// Returns the round, or the first problem with it.
+    pub fn build(self) -> Result<Round, RoundError> {
+        let crits = self.crits;
+        let (atk_guard, def_guard) = (self.atk_guard, self.def_guard);
+        let attack = |side, hp, (hit, crit, dmg)| {
+            if hp == 0 {
+                Err(RoundError::NoHp(side))
+            } else if hit > 100 {
+                Err(RoundError::Hit(side, hit))
+            } else if crit > 100 {
+                Err(RoundError::Crit(side, crit))
+            } else {
+                let attack = Attack{
+                    target_guard: match side {
+                        Side::Atk => def_guard,
+                        Side::Def => atk_guard,
+                    },
+                    ..Attack::new(hit, crit, dmg)
+                };
+                // crits are against the other side's Def
+                Ok(match (crits, side) {
+                    (Some((model, _, def)), Side::Atk)
+                        | (Some((model, def, _)), Side::Def) => {
+                        attack.with_crit_damage(model, def)
+                    }
+                    (None, _) => attack,
+                })
+            }
+        };
+        if let (Some(distance), Some(range)) = (self.resolved_distance(),
+                                                self.atk_range) {
+            if !range.contains(distance) {
+                return Err(RoundError::OutOfRange(distance));
+            }
+        }
+        let base_atk = attack(Side::Atk, self.atk_hp, self.attacker)?;
+        let base_def = attack(Side::Def, self.def_hp, self.defender)?;
+        let atk_strikes = self.atk_repeat.total_strikes() as usize;
+        let def_repeat = if self.can_counter() {
+            self.def_repeat
+        } else {
+            AttackRepeat::with_strikes(0, self.def_repeat.strikes_per_attack)
+        };
+        let def_strikes = def_repeat.total_strikes() as usize;
+        let mut attacker = vec![base_atk; atk_strikes];
+        let mut defender = vec![base_def; def_strikes];
+        for (side, index, stats) in self.overrides {
+            let (strikes, hp) = match side {
+                Side::Atk => (&mut attacker, self.atk_hp),
+                Side::Def => (&mut defender, self.def_hp),
+            };
+            let strike = strikes.get_mut(index)
+                .ok_or(RoundError::NoSuchStrike(side, index))?;
+            *strike = attack(side, hp, stats)?;
+        }
+        for (side, index) in self.sure_hits {
+            let strikes = match side {
+                Side::Atk => &mut attacker,
+                Side::Def => &mut defender,
+            };
+            let strike = strikes.get_mut(index)
+                .ok_or(RoundError::NoSuchStrike(side, index))?;
+            *strike = strike.with_always_hits();
+        }
+        Ok(Round{
+            atk_hp: self.atk_hp,
+            attacker,
+            def_hp: self.def_hp,
+            defender,
+            atk_repeat: self.atk_repeat,
+            def_repeat,
+            order: self.order,
+        })
+    }
+}
+
+/// A problem with the numbers given to a `RoundBuilder`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RoundError {
+    /// The side's listed hit is above 100.
+    Hit(Side, u32),
+
+    /// The side's listed crit is above 100.
+    Crit(Side, u32),
+
+    /// The side starts combat with no HP.
+    NoHp(Side),
+
+    /// A strike was given its own stats, but the side doesn't make that many
+    /// strikes.
+    NoSuchStrike(Side, usize),
+
+    /// The attacker's weapon can't reach the distance combat happens at.
+    OutOfRange(u32),
+}
+
+impl fmt::Display for RoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |side: &Side| match side {
+            Side::Atk => "attacker",
+            Side::Def => "defender",
+        };
+        match self {
+            RoundError::Hit(side, hit) => write!(
+                f, "{} hit must be between 0 and 100, got {}", name(side), hit
+            ),
+            RoundError::Crit(side, crit) => write!(
+                f, "{} crit must be between 0 and 100, got {}", name(side), crit
+            ),
+            RoundError::NoHp(side) => write!(
+                f, "{} HP must be greater than 0", name(side)
+            ),
+            RoundError::NoSuchStrike(side, index) => write!(
+                f, "{} has no strike {} to override", name(side), index
+            ),
+            RoundError::OutOfRange(distance) => write!(
+                f, "attacker can't attack at a distance of {}", distance
+            ),
+        }
+    }
+}
+
+impl Error for RoundError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::HitTable;
+    use crate::simple_calc::{possible_outcomes, SpeedDiff};
+
+    fn round(atk_repeat: AttackRepeat, def_repeat: AttackRepeat) -> Round {
+        RoundBuilder::new()
+            .atk_hp(20).attacker(100, 0, 5).atk_strikes(atk_repeat)
+            .def_hp(20).defender(50, 0, 10).def_strikes(def_repeat)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_prob_kills() {
+        let attack = Attack::new(80, 10, 10);
+        assert!((attack.prob_kills(RNSystem::OneRN, 10) - 0.8).abs() < 1e-9);
+        assert!((attack.prob_kills(RNSystem::OneRN, 25) - 0.08).abs() < 1e-9);
+        assert_eq!(attack.prob_kills(RNSystem::OneRN, 31), 0.0);
+        // doubling Atk against 5 Def does 25 damage instead of 30
+        let jugdral = attack.with_crit_damage(CritDamage::DoubleAtk, 5);
+        assert_eq!(jugdral.crit_dmg(), 25);
+        assert_eq!(jugdral.prob_kills(RNSystem::OneRN, 26), 0.0);
+        // absurd damage caps instead of overflowing
+        assert_eq!(Attack::new(80, 10, u32::MAX).crit_dmg(), u32::MAX);
+        // leaving a 25 HP target below 20 only takes a hit
+        let below = attack.prob_leaves_below(RNSystem::OneRN, 25, 20);
+        assert!((below - 0.8).abs() < 1e-9);
+        assert_eq!(attack.prob_leaves_below(RNSystem::OneRN, 25, 26), 1.0);
+
+        let round = RoundBuilder::new()
+            .atk_hp(20).attacker(100, 100, 10)
+            .def_hp(25).defender(0, 0, 0)
+            .crit_damage(CritDamage::DoubleAtk, 0, 4)
+            .build()
+            .unwrap();
+        assert_eq!(round.outcome_distribution(RNSystem::TwoRN),
+                   vec!(Outcome{prob: 1.0, atk_hp: 20, def_hp: 1}));
+    }
+
+    #[test]
+    fn test_atk_survival() {
+        let plain = AttackRepeat::new(false, false);
+        let doubles = AttackRepeat::new(true, false);
+        let brave = AttackRepeat::new(false, true);
+        let both = AttackRepeat::new(true, true);
+
+        // the defender needs to hit twice to kill
+        let survival = round(plain, plain).prob_atk_survival(RNSystem::OneRN);
+        assert!((survival - 1.0).abs() < 1e-9);
+        let survival = round(plain, doubles).prob_atk_survival(RNSystem::OneRN);
+        assert!((survival - 0.75).abs() < 1e-9);
+        let survival = round(plain, brave).prob_atk_survival(RNSystem::OneRN);
+        assert!((survival - 0.75).abs() < 1e-9);
+
+        // four strikes from the attacker kill before the defender can double
+        let survival = round(both, doubles).prob_atk_survival(RNSystem::OneRN);
+        assert!((survival - 1.0).abs() < 1e-9);
+        // without doubling, the attacker can't kill and has to dodge at least
+        // three of four brave strikes
+        let survival = round(brave, both).prob_atk_survival(RNSystem::OneRN);
+        assert!((survival - 5.0 / 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_outcome_distribution() {
+        let round = RoundBuilder::new()
+            .atk_hp(20).attacker(70, 10, 8).atk_repeat(true, false)
+            .def_hp(20).defender(60, 5, 6)
+            .build()
+            .unwrap();
+        let atk = CombatStats{dmg: 8, hit: 70, crit: 10, ..Default::default()};
+        let def = CombatStats{dmg: 6, hit: 60, crit: 5, ..Default::default()};
+        assert_eq!(round.outcome_distribution(RNSystem::TwoRN),
+                   possible_outcomes(FEGame::FE8, atk, 20, def, 20,
+                                     SpeedDiff::AtkDoubles));
+        let total: f64 = round.outcome_distribution(RNSystem::FatesRN).iter()
+            .map(|x| x.prob)
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_strike_counts() {
+        // Astra-style five strikes at 5 damage kill a 20 HP defender with
+        // only four hits, before the defender can counter
+        let round = RoundBuilder::new()
+            .atk_hp(10).attacker(50, 0, 5)
+            .atk_strikes(AttackRepeat::with_strikes(1, 5))
+            .def_hp(20).defender(100, 0, 10)
+            .build()
+            .unwrap();
+        assert_eq!(AttackRepeat::with_strikes(1, 5).total_strikes(), 5);
+        let kill = 6.0 / 32.0;
+        assert!((round.prob_def_dies(RNSystem::OneRN) - kill).abs() < 1e-9);
+        assert!((round.prob_atk_survival(RNSystem::OneRN) - kill).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_strike_order() {
+        use Side::{Atk, Def};
+        assert_eq!(StrikeOrder::Standard.attacks(2, 1), vec![Atk, Def, Atk]);
+        assert_eq!(StrikeOrder::Vantage.attacks(2, 1), vec![Def, Atk, Atk]);
+        assert_eq!(StrikeOrder::Vantage.attacks(2, 2),
+                   vec![Def, Atk, Def, Atk]);
+        assert_eq!(StrikeOrder::Desperation.attacks(2, 2),
+                   vec![Atk, Atk, Def, Def]);
+        assert_eq!(StrikeOrder::VantageDesperation.attacks(2, 1),
+                   vec![Def, Atk, Atk]);
+
+        // with Vantage, a defender who always hits for lethal never gets
+        // attacked at all
+        let round = RoundBuilder::new()
+            .atk_hp(10).attacker(100, 0, 20).atk_repeat(true, false)
+            .def_hp(20).defender(100, 0, 10)
+            .order(StrikeOrder::Vantage)
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_def_survival(RNSystem::OneRN), 1.0);
+        // but without it, the attacker strikes first and kills
+        let round = RoundBuilder::new()
+            .atk_hp(10).attacker(100, 0, 20)
+            .def_hp(20).defender(100, 0, 10)
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_def_dies(RNSystem::OneRN), 1.0);
+    }
+
+    #[test]
+    fn test_strike_overrides() {
+        // a Sure Strike on the first of two brave strikes
+        let round = RoundBuilder::new()
+            .atk_hp(20).attacker(50, 0, 10).atk_repeat(false, true)
+            .atk_strike(0, 100, 0, 10)
+            .def_hp(20).defender(0, 0, 0)
+            .build()
+            .unwrap();
+        assert!((round.prob_def_dies(RNSystem::OneRN) - 0.5).abs() < 1e-9);
+        assert_eq!(round.attacker[1], Attack::new(50, 0, 10));
+
+        let err = RoundBuilder::new().atk_hp(20).def_hp(20)
+            .def_strike(1, 50, 0, 10).build();
+        assert_eq!(err, Err(RoundError::NoSuchStrike(Side::Def, 1)));
+        let err = RoundBuilder::new().atk_hp(20).def_hp(20)
+            .atk_strike(0, 120, 0, 10).build();
+        assert_eq!(err, Err(RoundError::Hit(Side::Atk, 120)));
+    }
+
+    #[test]
+    fn test_engagement() {
+        let builder = RoundBuilder::new()
+            .atk_hp(20).attacker(100, 0, 5)
+            .def_hp(20).defender(100, 0, 20);
+        // a melee defender can't counter an archer
+        let round = builder.clone().engagement(2, Range::melee())
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_atk_survival(RNSystem::OneRN), 1.0);
+        let round = builder.clone().engagement(1, Range::new(1, 2))
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_atk_survival(RNSystem::OneRN), 0.0);
+        // unless they have a skill for it
+        let round = builder.clone().engagement(2, Range::melee())
+            .def_counter_skill(CounterSkill::CloseCounter)
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_atk_survival(RNSystem::OneRN), 1.0);
+        let round = builder.clone().engagement(2, Range::melee())
+            .def_counter_skill(CounterSkill::DistantCounter)
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_atk_survival(RNSystem::OneRN), 0.0);
+        // or something else says otherwise
+        let round = builder.engagement(2, Range::melee())
+            .counter_override(true)
+            .build()
+            .unwrap();
+        assert_eq!(round.prob_atk_survival(RNSystem::OneRN), 0.0);
+    }
+
+    #[test]
+    fn test_prob_def_dies_within() {
+        // the attacker needs two hits to kill, getting one chance a round
+        let round = RoundBuilder::new()
+            .atk_hp(20).attacker(50, 0, 10)
+            .def_hp(20).defender(0, 0, 10)
+            .build()
+            .unwrap();
+        let within = |n| round.prob_def_dies_within(n, RNSystem::OneRN);
+        assert_eq!(within(0), 0.0);
+        assert_eq!(within(1), 0.0);
+        assert!((within(2) - 0.25).abs() < 1e-9);
+        assert!((within(3) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_always_hits() {
+        let attack = Attack::new(30, 20, 10).with_always_hits();
+        assert_eq!(attack.prob_kills(RNSystem::TwoRN, 10), 1.0);
+        assert!((attack.prob_kills(RNSystem::TwoRN, 30) - 0.2).abs() < 1e-9);
+
+        let builder = RoundBuilder::new()
+            .atk_hp(20).attacker(0, 0, 10).atk_repeat(true, false)
+            .def_hp(20).defender(0, 0, 0);
+        // only the first strike is a sure hit
+        let round = builder.clone().always_hits(Side::Atk, 0).build().unwrap();
+        assert_eq!(round.outcome_distribution(RNSystem::TwoRN),
+                   vec!(Outcome{prob: 1.0, atk_hp: 20, def_hp: 10}));
+        assert_eq!(builder.always_hits(Side::Def, 1).build(),
+                   Err(RoundError::NoSuchStrike(Side::Def, 1)));
+    }
+
+    #[test]
+    fn test_guard() {
+        let pavise = DefenseProc{rate: 40, dmg_percent: 50};
+        let attack = Attack::new(100, 0, 20).with_target_guard(pavise);
+        assert!((attack.prob_kills(RNSystem::OneRN, 20) - 0.6).abs() < 1e-9);
+        assert_eq!(attack.prob_leaves_below(RNSystem::OneRN, 20, 11), 1.0);
+        // capped crit damage is still halved without overflowing
+        let huge = Attack::new(100, 100, u32::MAX).with_target_guard(pavise);
+        assert_eq!(huge.prob_kills(RNSystem::OneRN, 60), 1.0);
+
+        let round = RoundBuilder::new()
+            .atk_hp(20).attacker(100, 0, 10)
+            .def_hp(20).defender(100, 0, 10)
+            .atk_guard(DefenseProc{rate: 100, dmg_percent: 0})
+            .def_guard(pavise)
+            .build()
+            .unwrap();
+        assert_eq!(round.outcome_distribution(RNSystem::OneRN), vec!(
+            Outcome{prob: 0.6, atk_hp: 20, def_hp: 10},
+            Outcome{prob: 0.4, atk_hp: 20, def_hp: 15},
+        ));
+    }
+
+    #[test]
+    fn test_custom_rn() {
+        let round = round(AttackRepeat::new(false, false),
+                          AttackRepeat::new(false, false));
+        let table = HitTable::from_fn(|x| RNSystem::TwoRN.true_hit(x));
+        assert_eq!(round.outcome_distribution(RNSystem::Custom(table)),
+                   round.outcome_distribution(RNSystem::TwoRN));
+        let sure = HitTable::from_fn(|_| 1.0);
+        assert_eq!(round.prob_atk_survival(RNSystem::Custom(sure)), 1.0);
+
+        // the attacker does 5 a hit, and the defender 10
+        assert_eq!(round.outcome_distribution(RNSystem::AlwaysHit),
+                   vec!(Outcome{prob: 1.0, atk_hp: 10, def_hp: 15}));
+        assert_eq!(round.outcome_distribution(RNSystem::AlwaysMiss),
+                   vec!(Outcome{prob: 1.0, atk_hp: 20, def_hp: 20}));
+        let crits = RoundBuilder::new()
+            .atk_hp(20).attacker(100, 100, 5)
+            .def_hp(20).defender(0, 0, 0)
+            .build()
+            .unwrap();
+        assert_eq!(crits.expected_damage_dealt(RNSystem::NoCrits), 5.0);
+    }
+
+    #[test]
+    fn test_compare() {
+        let enemy = RoundBuilder::new()
+            .atk_hp(30).def_hp(15).defender(80, 0, 8);
+        // the Iron Sword does more damage, but only the Killing Edge can kill
+        let iron = enemy.clone().attacker(90, 0, 10).build().unwrap();
+        let killing = enemy.clone().attacker(50, 50, 5).build().unwrap();
+        let broken = enemy.attacker(0, 0, 0).build().unwrap();
+
+        let comparison = compare(&iron, &killing, RNSystem::OneRN);
+        assert_eq!(comparison.first, iron.summary(RNSystem::OneRN));
+        assert!(comparison.second.prob_kill > comparison.first.prob_kill);
+        assert_eq!(comparison.dominant, Dominance::Neither);
+        let comparison = compare(&broken, &iron, RNSystem::OneRN);
+        assert_eq!(comparison.dominant, Dominance::Second);
+        let comparison = compare(&iron, &iron, RNSystem::OneRN);
+        assert_eq!(comparison.dominant, Dominance::Equal);
+    }
+
+    #[test]
+    fn test_serde() {
+        let round = round(AttackRepeat::new(true, false),
+                          AttackRepeat::new(false, true));
+        let json = serde_json::to_string(&round).unwrap();
+        assert_eq!(serde_json::from_str::<Round>(&json).unwrap(), round);
+        let copy = Round::new(round.atk_hp(), round.attacker().to_vec(),
+                              round.atk_repeat(), round.def_hp(),
+                              round.defender().to_vec(), round.def_repeat(),
+                              round.order());
+        assert_eq!(copy, round);
+        let first = round.attacker()[0];
+        assert_eq!(Attack::new(first.hit(), first.crit(), first.dmg()), first);
+        assert_eq!(round.def_repeat().strikes_per_attack(), 2);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let builder = RoundBuilder::new()
+            .atk_hp(20).attacker(100, 0, 5)
+            .def_hp(20).defender(100, 0, 20).def_range(Range::melee());
+        // a bow can only attack from 2 away, so a sword can't counter it
+        let round = builder.clone().atk_range(Range::new(2, 2))
+            .build()
+            .unwrap();
+        assert_eq!(round.defender(), &[]);
+        // a hand axe could be used from either distance
+        let round = builder.clone().atk_range(Range::new(1, 2))
+            .build()
+            .unwrap();
+        assert_eq!(round.defender().len(), 1);
+        let round = builder.clone().atk_range(Range::new(1, 2)).distance(2)
+            .build()
+            .unwrap();
+        assert_eq!(round.defender(), &[]);
+        assert_eq!(builder.atk_range(Range::melee()).distance(2).build(),
+                   Err(RoundError::OutOfRange(2)));
+    }
+
+    #[test]
+    fn test_builder() {
+        let built = RoundBuilder::new()
+            .atk_hp(20).attacker(100, 0, 5).atk_repeat(false, false)
+            .def_hp(20).defender(50, 0, 10).def_repeat(true, false)
+            .build();
+        let plain = AttackRepeat::new(false, false);
+        let doubles = AttackRepeat::new(true, false);
+        assert_eq!(built, Ok(round(plain, doubles)));
+
+        let err = RoundBuilder::new().atk_hp(20).def_hp(20)
+            .defender(50, 101, 10).build();
+        assert_eq!(err, Err(RoundError::Crit(Side::Def, 101)));
+        assert_eq!(err.unwrap_err().to_string(),
+                   "defender crit must be between 0 and 100, got 101");
+        assert_eq!(RoundBuilder::new().def_hp(20).build(),
+                   Err(RoundError::NoHp(Side::Atk)));
+    }
+
+    #[test]
+    fn test_expected_damage() {
+        let round = RoundBuilder::new()
+            .atk_hp(30).attacker(50, 10, 4).atk_repeat(false, true)
+            .def_hp(30).defender(100, 0, 3)
+            .build()
+            .unwrap();
+        // each strike does 0.45 * 4 + 0.05 * 12 = 2.4 on average
+        let dealt = round.expected_damage_dealt(RNSystem::OneRN);
+        assert!((dealt - 4.8).abs() < 1e-9);
+        let taken = round.expected_damage_taken(RNSystem::OneRN);
+        assert!((taken - 3.0).abs() < 1e-9);
+
+        // damage past the defender's HP doesn't count
+        let overkill = Round{def_hp: 4, ..round};
+        let dealt = overkill.expected_damage_dealt(RNSystem::OneRN);
+        assert!((dealt - 4.0 * 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_def_survival() {
+        let round = RoundBuilder::new()
+            .atk_hp(10).attacker(50, 0, 10).atk_repeat(true, false)
+            .def_hp(10).defender(50, 0, 10)
+            .build()
+            .unwrap();
+        // the attacker kills on the first strike half the time, and otherwise
+        // needs to dodge and then hit
+        assert!((round.prob_def_dies(RNSystem::OneRN) - 0.625).abs() < 1e-9);
+        assert!((round.prob_def_survival(RNSystem::OneRN) - 0.375).abs() < 1e-9);
+        assert!((round.prob_atk_survival(RNSystem::OneRN) - 0.75).abs() < 1e-9);
+        // nobody dies only if all three strikes miss
+        assert!((round.prob_both_survive(RNSystem::OneRN) - 0.125).abs() < 1e-9);
     }
 }
@@ -0,0 +1,195 @@
+//! A library of saved combat scenarios. Scenarios have names and tags ("ch17",
+//! "boss", "ironman") so that a collection of them can be searched later, and
+//! the whole library can be saved and loaded as JSON.
+
+use crate::durability::{durable_outcomes, ArmedSide, DurableOutcome};
+use crate::fegame::FEGame;
+use crate::simple_calc::{possible_outcomes, CombatStats, Outcome, SpeedDiff};
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// A single named combat, with everything needed to compute its outcomes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Scenario {
+    /// The name of the scenario, which is unique within a library.
+    pub name: String,
+
+    /// Tags used to find the scenario later.
+    pub tags: Vec<String>,
+
+    /// The game whose rules are used.
+    pub game: FEGame,
+
+    /// The attacker's stats.
+    pub atk: CombatStats,
+
+    /// The attacker's HP before combat.
+    pub atk_hp: u32,
+
+    /// The defender's stats.
+    pub def: CombatStats,
+
+    /// The defender's HP before combat.
+    pub def_hp: u32,
+
+    /// The speed differential between attacker and defender.
+    pub speed: SpeedDiff,
+
+    /// The uses the attacker's weapon has left, if it can break.
+    #[serde(default)]
+    pub atk_uses: Option<u32>,
+
+    /// The uses the defender's weapon has left, if it can break.
+    #[serde(default)]
+    pub def_uses: Option<u32>,
+}
+
+impl Scenario {
+    /// Returns the possible outcomes of the scenario's combat. If either
+    /// weapon's uses are given, a weapon that breaks stops striking.
+    pub fn outcomes(&self) -> Vec<Outcome> {
+        if self.atk_uses.is_none() && self.def_uses.is_none() {
+            return possible_outcomes(self.game, self.atk, self.atk_hp,
+                                     self.def, self.def_hp, self.speed);
+        }
+        Outcome::collect(self.durable_outcomes().into_iter()
+                         .map(|x| x.outcome).collect())
+    }
+
+    /// Returns the possible outcomes of the scenario's combat, with the uses
+    /// each weapon has left.
+    pub fn durable_outcomes(&self) -> Vec<DurableOutcome> {
+        let armed = |stats, uses| ArmedSide{stats, uses, fallback: None};
+        durable_outcomes(self.game, &armed(self.atk, self.atk_uses),
+                         self.atk_hp, &armed(self.def, self.def_uses),
+                         self.def_hp, self.speed)
+    }
+
+    /// Returns whether the scenario has the given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|x| x == tag)
+    }
+}
+
+/// A collection of scenarios, keyed by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScenarioLibrary {
+    scenarios: BTreeMap<String, Scenario>,
+}
+
+impl ScenarioLibrary {
+    /// Creates an empty library.
+    pub fn new() -> ScenarioLibrary {
+        ScenarioLibrary::default()
+    }
+
+    /// Adds the scenario to the library, returning the scenario it replaced if
+    /// one with the same name already existed.
+    pub fn insert(&mut self, scenario: Scenario) -> Option<Scenario> {
+        self.scenarios.insert(scenario.name.clone(), scenario)
+    }
+
+    /// Removes the scenario with the given name, returning it if it existed.
+    pub fn remove(&mut self, name: &str) -> Option<Scenario> {
+        self.scenarios.remove(name)
+    }
+
+    /// Returns the scenario with the given name.
+    pub fn get(&self, name: &str) -> Option<&Scenario> {
+        self.scenarios.get(name)
+    }
+
+    /// Returns every scenario in the library, sorted by name.
+    pub fn scenarios(&self) -> impl Iterator<Item = &Scenario> {
+        self.scenarios.values()
+    }
+
+    /// Returns every scenario that has all of the given tags, sorted by name.
+    pub fn with_tags(&self, tags: &[&str]) -> Vec<&Scenario> {
+        self.scenarios()
+            .filter(|x| tags.iter().all(|tag| x.has_tag(tag)))
+            .collect()
+    }
+
+    /// Returns every scenario whose name or tags contain the query, ignoring
+    /// case, sorted by name.
+    pub fn search(&self, query: &str) -> Vec<&Scenario> {
+        let query = query.to_lowercase();
+        self.scenarios().filter(|x| {
+            x.name.to_lowercase().contains(&query) ||
+                x.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+        }).collect()
+    }
+
+    /// Serializes the library as JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    /// Loads a library from JSON produced by `to_json`.
+    pub fn from_json(json: &str) -> serde_json::Result<ScenarioLibrary> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario(name: &str, tags: &[&str]) -> Scenario {
+        Scenario{
+            name: name.to_string(),
+            tags: tags.iter().map(|x| x.to_string()).collect(),
+            game: FEGame::FE7,
+            atk: CombatStats{
+                dmg: 10, hit: 80, crit: 5, ..Default::default()
+            },
+            atk_hp: 30,
+            def: CombatStats{
+                dmg: 8, hit: 60, crit: 0, ..Default::default()
+            },
+            def_hp: 25,
+            speed: SpeedDiff::AtkDoubles,
+            atk_uses: None,
+            def_uses: None,
+        }
+    }
+
+    #[test]
+    fn test_library() {
+        let mut library = ScenarioLibrary::new();
+        library.insert(scenario("Lyn vs Batta", &["ch1", "boss"]));
+        library.insert(scenario("Kent vs brigand", &["ch1"]));
+        library.insert(scenario("Hector vs Darin", &["ch30", "boss", "ironman"]));
+
+        let names = |found: Vec<&Scenario>| -> Vec<String> {
+            found.into_iter().map(|x| x.name.clone()).collect()
+        };
+        assert_eq!(names(library.with_tags(&["boss"])),
+                   vec!["Hector vs Darin", "Lyn vs Batta"]);
+        assert_eq!(names(library.with_tags(&["ch1", "boss"])),
+                   vec!["Lyn vs Batta"]);
+        assert_eq!(names(library.search("BRIGAND")), vec!["Kent vs brigand"]);
+        assert_eq!(names(library.search("iron")), vec!["Hector vs Darin"]);
+
+        let loaded = ScenarioLibrary::from_json(&library.to_json().unwrap()).unwrap();
+        assert_eq!(loaded, library);
+        let lyn = loaded.get("Lyn vs Batta").unwrap();
+        assert_eq!(lyn.outcomes(), scenario("x", &[]).outcomes());
+
+        // a weapon with one use left can't follow up
+        let last_use = Scenario{
+            atk: CombatStats{hit: 100, crit: 0, ..lyn.atk},
+            atk_uses: Some(1),
+            ..lyn.clone()
+        };
+        assert_eq!(last_use.outcomes().iter().map(|x| x.def_hp).min(),
+                   Some(15));
+        assert_eq!(last_use.durable_outcomes()[0].atk_uses, Some(0));
+
+        assert!(library.remove("Kent vs brigand").is_some());
+        assert_eq!(library.get("Kent vs brigand"), None);
+    }
+}
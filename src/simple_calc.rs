@@ -1,9 +1,17 @@
 //! This is a simple calculator that avoids the full complexity of the FE games
-//! (lifesteal, abilities, held items, personal weapons, etc.) to focus on the
-//! stats as they appear in all FE games, providing basic survival
-//! probabilities.
+//! (abilities, held items, personal weapons, etc.) to focus on the stats as
+//! they appear in all FE games, providing basic survival probabilities. A few
+//! effects that come up constantly, like lifesteal, Astra-style procs, and
+//! damage procs like Luna, are supported directly on `CombatStats`.
+//!
+//! Inputs that don't make sense in a real game still give sensible answers:
+//! listed hit and crit above 100 count as 100, strikes that can't happen
+//! (0 hit, 0 crit) never show up as branches, hits that do 0 damage end up
+//! merged with misses, and combat where either side starts at 0 HP doesn't
+//! happen at all, so the only outcome is the starting state.
 
 use crate::fegame::FEGame;
+use crate::rng::{CritModel, HitTable, RNSystem};
 
 use serde::{Deserialize, Serialize};
 
@@ -24,69 +32,433 @@ pub struct CombatStats {
     /// Whether the weapon strikes twice per normal strike. Although usually
     /// called brave weapons, other weapons like gauntlets or the Amiti do this
     /// as well.
-    pub is_brave: bool
+    pub is_brave: bool,
+
+    /// Strikes added to each attack on top of the usual one, or two with a
+    /// brave weapon, like skills that strike more than twice per attack.
+    #[serde(default)]
+    pub extra_strikes: u32,
+
+    /// The striker's Atk and the target's Def, if known. FE4 and FE5 compute
+    /// critical damage from these instead of from the final damage, so crits
+    /// in those games are only correct if this is given. Otherwise, crits do
+    /// triple damage.
+    pub atk_def: Option<(u32, u32)>,
+
+    /// A skill that can activate to replace the first strike of each attack
+    /// with a flurry of strikes, like Adept or Astra.
+    pub strike_proc: Option<StrikeProc>,
+
+    /// How the crit roll relates to the hit roll.
+    #[serde(default)]
+    pub crit_model: CritModel,
+
+    /// The percentage of damage dealt that heals the striker, like Nosferatu
+    /// (100) or Sol. Damage beyond what the target has left doesn't count.
+    #[serde(default)]
+    pub drain_percent: u32,
+
+    /// The striker's max HP, which caps healing from `drain_percent`. If this
+    /// isn't given, healing isn't capped.
+    pub max_hp: Option<u32>,
+
+    /// Whether this side is unable to counterattack at all, like a melee unit
+    /// attacked from range or a sleeping target. This only matters for the
+    /// defender, whose strikes are skipped entirely.
+    #[serde(default)]
+    pub cannot_counter: bool,
+
+    /// Whether this side can't be crit, like with Iote's Shield or Nihil. This
+    /// applies to strikes against this side, so it zeroes out the other side's
+    /// crit regardless of what it's listed as.
+    #[serde(default)]
+    pub crit_immune: bool,
+
+    /// A chance for strikes that hit to damage the wielder instead of the
+    /// target, like the Devil Axe.
+    pub backfire: Option<Backfire>,
+
+    /// A skill that can activate on a strike that lands to change how much
+    /// damage that strike does, like Luna or Lethality.
+    pub dmg_proc: Option<DamageProc>,
+
+    /// The damage a critical hit does, if it's already known. This takes
+    /// precedence over `atk_def` and the game's crit formula, and damage procs
+    /// don't change it.
+    pub crit_dmg: Option<u32>,
+
+    /// Whether strikes can't miss, like gambits in Three Houses or scripted
+    /// hits. Crit is still rolled as if the listed hit were 100.
+    #[serde(default)]
+    pub always_hits: bool,
+
+    /// A skill that can activate on strikes against this side to reduce their
+    /// damage, like Pavise or Great Shield.
+    pub guard: Option<DefenseProc>,
+
+    /// A house-ruled hit table to use for this side's strikes instead of the
+    /// game's RN system.
+    pub hit_table: Option<HitTable>,
+}
+
+/// A chance for a weapon to turn on its wielder. In the GBA games, the Devil
+/// Axe has a (31 - Luck)% chance to do this on any strike that hits.
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Backfire {
+    /// The chance (0-100) that a strike that hits backfires.
+    pub rate: u32,
+
+    /// The damage the wielder takes when the weapon backfires. Crits don't
+    /// change this.
+    pub dmg: u32,
+}
+
+/// A skill that has a chance to activate at the start of an attack and replace
+/// its first strike with a number of strikes at some percentage of normal
+/// damage. Adept is 2 strikes at full damage, and Tellius's Astra is 5 strikes
+/// at half damage.
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct StrikeProc {
+    /// The activation chance (0-100).
+    pub rate: u32,
+
+    /// The number of strikes made when the skill activates.
+    pub strikes: u32,
+
+    /// The damage of each of those strikes, as a percentage of normal damage.
+    /// Crits are scaled the same way.
+    pub dmg_percent: u32,
+}
+
+/// A skill that has a chance to activate on a strike that lands and change the
+/// damage of that strike alone. In most games these roll on every strike, so
+/// they're treated as one more split of the hit and crit branches.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct DamageProc {
+    /// The activation chance (0-100).
+    pub rate: u32,
+
+    /// What happens to the strike's damage when the skill activates.
+    pub effect: DamageEffect,
+}
+
+/// A skill that has a chance to activate on a strike against its user and
+/// reduce the damage of that strike, like Pavise and Aegis (half damage in
+/// later games) or Great Shield (no damage).
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct DefenseProc {
+    /// The activation chance (0-100).
+    pub rate: u32,
+
+    /// The damage the strike does when the skill activates, as a percentage
+    /// of normal damage: 50 halves it and 0 negates it.
+    pub dmg_percent: u32,
+}
+
+/// How a damage proc changes the damage of a strike.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum DamageEffect {
+    /// Ignores the given percentage of the target's Def, like Luna. This needs
+    /// `atk_def` to know how much Def there is to ignore, and does nothing
+    /// without it. Percentages above 100 count as 100.
+    IgnoreDef(u32),
+
+    /// Adds flat damage before crits are applied, like Ignis.
+    BonusDmg(u32),
+
+    /// Kills the target outright, like Lethality or Silencer.
+    Lethal,
 }
 
 impl CombatStats {
+    /// Returns the stats this side actually strikes with against the given
+    /// target, accounting for the target's defensive properties.
+    pub(crate) fn against(&self, target: &CombatStats) -> CombatStats {
+        CombatStats{
+            crit: if target.crit_immune { 0 } else { self.crit },
+            ..*self
+        }
+    }
+
     /// Computes possible outcomes for a single round of combat using the given
-    /// statistics. Doesn't deal with FE4 or FE5 crit damage correctly.
-    pub fn possible_outcomes(&self, game: FEGame, outcomes: Vec<Outcome>) -> Vec<Outcome> {
-        let after_one = self.after_single_strike(game, outcomes);
-        if self.is_brave {
-            // strike again
-            self.after_single_strike(game, after_one)
+    /// statistics, against a target without a defensive skill. FE4 and FE5
+    /// crit damage is only correct if `atk_def` is given.
+    pub fn possible_outcomes<B: Branch>(&self, game: FEGame,
+                                        outcomes: Vec<B>) -> Vec<B> {
+        (0..self.strikes_per_attack()).fold(outcomes, |states, n| {
+            self.nth_strike(game, states, n, None)
+        })
+    }
+
+    /// The number of strikes in each attack: two for brave weapons, and one
+    /// otherwise, plus any extra strikes.
+    pub fn strikes_per_attack(&self) -> usize {
+        let base = if self.is_brave { 2 } else { 1 };
+        base + self.extra_strikes as usize
+    }
+
+    /// Returns the possible states after the given strike (counting from 0) of
+    /// an attack against a target with the given defensive skill. Strike procs
+    /// can only activate on the first strike.
+    pub(crate) fn nth_strike<B: Branch>(&self, game: FEGame, outcomes: Vec<B>,
+                                        n: usize,
+                                        target_guard: Option<DefenseProc>)
+                                        -> Vec<B> {
+        match self.strike_proc {
+            Some(proc) if n == 0 => {
+                // branch on activation just like on hit or crit
+                let prob_proc = proc.rate.min(100) as f64 / 100.0;
+                let activated = outcomes.iter().map(|x| {
+                    let state = x.outcome();
+                    x.branch(prob_proc, state.atk_hp, state.def_hp,
+                             CombatEvent::Flurry(Side::Atk))
+                }).collect();
+                let activated = (0..proc.strikes).fold(
+                    activated,
+                    |states, _| self.after_single_strike(game, states,
+                                                         proc.dmg_percent,
+                                                         target_guard)
+                );
+                let inactive = outcomes.iter()
+                    .map(|x| x.scale(1.0 - prob_proc))
+                    .collect();
+                let mut new_states = self.after_single_strike(
+                    game, inactive, 100, target_guard
+                );
+                new_states.extend(activated);
+                B::merge(new_states)
+            }
+            _ => self.after_single_strike(game, outcomes, 100, target_guard)
+        }
+    }
+
+    /// Returns the listed hit actually used for strikes, which is 100 for
+    /// strikes that can't miss.
+    pub fn listed_hit(&self) -> u32 {
+        if self.always_hits {
+            100
         } else {
-            after_one
+            self.hit.min(100)
+        }
+    }
+
+    /// Returns the true chance that a strike hits in the given game. Strikes
+    /// that can't miss always land, even in games like FE5 whose hit bounds
+    /// never allow 100.
+    pub fn true_hit(&self, game: FEGame) -> f64 {
+        if self.always_hits {
+            return 1.0;
+        }
+        match self.hit_table {
+            Some(table) => table.true_hit(self.listed_hit()),
+            None => game.true_hit(self.listed_hit()),
+        }
+    }
+
+    /// Returns the true chance that a strike crits in the given game, which
+    /// includes the chance it hits.
+    pub fn prob_crit(&self, game: FEGame) -> f64 {
+        let rn = match self.hit_table {
+            Some(table) => RNSystem::Custom(table),
+            None => game.rn_system(),
+        };
+        if self.always_hits {
+            // the strike lands for sure, so only the crit roll is left
+            return match self.crit_model {
+                CritModel::Independent => rn.true_crit(self.crit),
+                CritModel::SharedRn => rn.true_hit(self.crit),
+            };
+        }
+        match self.hit_table {
+            Some(_) => rn.prob_crit(self.listed_hit(), self.crit,
+                                    self.crit_model),
+            None => game.prob_crit(self.listed_hit(), self.crit,
+                                   self.crit_model),
+        }
+    }
+
+    /// Returns the damage dealt by a critical hit in the given game.
+    fn crit_dmg(&self, game: FEGame) -> u32 {
+        match (self.crit_dmg, self.atk_def) {
+            (Some(dmg), _) => dmg,
+            (None, Some((atk, def))) => game.crit_damage(atk, def),
+            (None, None) => 3 * self.dmg
+        }
+    }
+
+    /// Returns the damage of a normal hit and a crit when the damage proc
+    /// activates, before scaling by the strike's damage percentage.
+    fn proc_dmg(&self, game: FEGame, effect: DamageEffect) -> (u32, u32) {
+        let boosted = match effect {
+            DamageEffect::IgnoreDef(percent) => {
+                let ignored = self.atk_def
+                    .map_or(0, |(_, def)| def * percent.min(100) / 100);
+                CombatStats{
+                    dmg: self.dmg.saturating_add(ignored),
+                    atk_def: self.atk_def.map(|(atk, def)| (atk, def - ignored)),
+                    ..*self
+                }
+            }
+            DamageEffect::BonusDmg(bonus) => CombatStats{
+                dmg: self.dmg.saturating_add(bonus),
+                atk_def: self.atk_def
+                    .map(|(atk, def)| (atk.saturating_add(bonus), def)),
+                ..*self
+            },
+            DamageEffect::Lethal => return (u32::MAX, u32::MAX),
+        };
+        (boosted.dmg, boosted.crit_dmg(game))
+    }
+
+    /// Returns the striker's HP after dealing the given damage to a target with
+    /// the given HP, accounting for lifesteal.
+    fn hp_after_dealing(&self, atk_hp: u32, dmg: u32, def_hp: u32) -> u32 {
+        let heal = dmg.min(def_hp) * self.drain_percent / 100;
+        let healed = atk_hp.saturating_add(heal);
+        match self.max_hp {
+            Some(max_hp) => healed.min(max_hp.max(atk_hp)),
+            None => healed
         }
     }
 
     /// Returns the possible states after a single strike given the previous
-    /// possible states. Critical damage is not handled correctly in FE4 and
-    /// FE5.
-    fn after_single_strike(&self, game: FEGame, states: Vec<Outcome>) -> Vec<Outcome> {
+    /// possible states, with the strike doing the given percentage of normal
+    /// damage against a target with the given defensive skill.
+    fn after_single_strike<B: Branch>(&self, game: FEGame, states: Vec<B>,
+                                      dmg_percent: u32,
+                                      target_guard: Option<DefenseProc>)
+                                      -> Vec<B> {
+        let dmg = percent_of(self.dmg, dmg_percent);
+        let crit_dmg = percent_of(self.crit_dmg(game), dmg_percent);
+        // the damage of hits and crits when the damage proc activates
+        let (prob_dmg_proc, proc_dmg, proc_crit_dmg) = match self.dmg_proc {
+            Some(dmg_proc) => {
+                let scale = |x: u32| if x == u32::MAX {
+                    x
+                } else {
+                    percent_of(x, dmg_percent)
+                };
+                let (proc_dmg, proc_crit_dmg) =
+                    self.proc_dmg(game, dmg_proc.effect);
+                (dmg_proc.rate.min(100) as f64 / 100.0,
+                 scale(proc_dmg), scale(proc_crit_dmg))
+            }
+            None => (0.0, dmg, crit_dmg)
+        };
         let mut new_states = vec!();
-        for state in states {
-            if state.atk_hp == 0 {
-                // dead attackers can't do anything
-                new_states.push(state);
+        for branch in states {
+            let state = branch.outcome();
+            if state.atk_hp == 0 || state.def_hp == 0 {
+                // combat ends as soon as someone dies
+                new_states.push(branch);
             } else {
                 // three possibilities: miss, non-crit hit, and crit
-                let prob_hit = game.true_hit(self.hit);
+                let prob_hit = self.true_hit(game).clamp(0.0, 1.0);
                 let prob_miss = 1.0 - prob_hit;
-                let prob_crit = prob_hit * self.crit as f64 / 100.0;
+                let prob_crit = self.prob_crit(game).clamp(0.0, prob_hit);
                 let prob_reg_hit = prob_hit - prob_crit;
 
+                // any hit can instead backfire onto the wielder
+                let (prob_backfire, backfire_dmg) = match self.backfire {
+                    Some(backfire) => (backfire.rate.min(100) as f64 / 100.0,
+                                       backfire.dmg),
+                    None => (0.0, 0)
+                };
+                let prob_reg_hit = prob_reg_hit * (1.0 - prob_backfire);
+                let prob_crit = prob_crit * (1.0 - prob_backfire);
+                new_states.push(branch.branch(
+                    prob_hit * prob_backfire,
+                    state.atk_hp.saturating_sub(backfire_dmg),
+                    state.def_hp,
+                    CombatEvent::Backfire(Side::Atk)
+                ));
+
                 // if miss, nothing happens
-                new_states.push(Outcome{
-                    prob: state.prob * prob_miss,
-                    atk_hp: state.atk_hp,
-                    def_hp: state.def_hp
-                });
+                new_states.push(branch.branch(
+                    prob_miss,
+                    state.atk_hp,
+                    state.def_hp,
+                    CombatEvent::Miss(Side::Atk)
+                ));
 
-                // if hit, normal damage: subtract damage, cannot go negative
-                new_states.push(Outcome{
-                    prob: state.prob * prob_reg_hit,
-                    atk_hp: state.atk_hp,
-                    def_hp: state.def_hp.saturating_sub(self.dmg)
-                });
+                // the strikes that land: normal damage, critical damage (this
+                // needs Atk and Def in FE4 and FE5, so it's only triple damage
+                // if those aren't given), and either one with a damage proc
+                let mut landed = vec!(
+                    (prob_reg_hit * (1.0 - prob_dmg_proc), dmg,
+                     CombatEvent::Hit(Side::Atk)),
+                    (prob_crit * (1.0 - prob_dmg_proc), crit_dmg,
+                     CombatEvent::Crit(Side::Atk)),
+                );
+                if prob_dmg_proc > 0.0 {
+                    landed.push((prob_reg_hit * prob_dmg_proc, proc_dmg,
+                                 CombatEvent::Proc(Side::Atk)));
+                    landed.push((prob_crit * prob_dmg_proc, proc_crit_dmg,
+                                 CombatEvent::Proc(Side::Atk)));
+                }
 
-                // if crit, critical damage: FE4 and FE5 critical damage
-                // requires knowing Def, which we don't have, so we just do
-                // triple damage like normal
-                new_states.push(Outcome{
-                    prob: state.prob * prob_crit,
-                    atk_hp: state.atk_hp,
-                    def_hp: state.def_hp.saturating_sub(3 * self.dmg)
-                });
+                // the target's defensive skill can reduce any of those
+                let (prob_guard, guard_percent) = match target_guard {
+                    Some(guard) => (guard.rate.min(100) as f64 / 100.0,
+                                    guard.dmg_percent),
+                    None => (0.0, 100)
+                };
+                let mut guarded = vec!();
+                for (prob, dmg, event) in landed {
+                    new_states.push(branch.branch(
+                        prob * (1.0 - prob_guard),
+                        self.hp_after_dealing(state.atk_hp, dmg, state.def_hp),
+                        state.def_hp.saturating_sub(dmg),
+                        event
+                    ));
+                    if prob_guard > 0.0 {
+                        // skills that kill outright go through
+                        let dmg = if dmg == u32::MAX {
+                            dmg
+                        } else {
+                            percent_of(dmg, guard_percent)
+                        };
+                        guarded.push(branch.branch(
+                            prob * prob_guard,
+                            self.hp_after_dealing(state.atk_hp, dmg,
+                                                  state.def_hp),
+                            state.def_hp.saturating_sub(dmg),
+                            CombatEvent::Guard(Side::Def)
+                        ));
+                    }
+                }
+                new_states.extend(guarded);
             }
         }
-        Outcome::collect(new_states)
+        B::merge(new_states)
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+/// One of the two sides of combat.
+pub enum Side {
+    /// The unit that initiates combat (A).
+    Atk,
+    /// The unit being attacked (B).
+    Def,
+}
+
+impl Side {
+    /// Returns the other side.
+    pub fn other(&self) -> Side {
+        match self {
+            Side::Atk => Side::Def,
+            Side::Def => Side::Atk,
+        }
     }
 }
 
 #[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 /// The results of different speed differentials between attacker (A) and
-/// defender (B), resulting in different attack patterns.
+/// defender (B), resulting in different attack patterns. The Vantage patterns
+/// cover a defender that strikes first: follow-ups still come after the
+/// initial exchange. Desperation instead moves the attacker's follow-up ahead
+/// of the counterattack.
 pub enum SpeedDiff {
     /// No one doubles: AB
     Even,
@@ -94,6 +466,30 @@ pub enum SpeedDiff {
     AtkDoubles,
     /// Defender doubles: ABB
     DefDoubles,
+    /// Defender has Vantage, no one doubles: BA
+    Vantage,
+    /// Defender has Vantage, attacker doubles: BAA
+    VantageAtkDoubles,
+    /// Defender has Vantage and doubles: BAB
+    VantageDefDoubles,
+    /// Attacker has Desperation and doubles: AAB
+    Desperation,
+}
+
+impl SpeedDiff {
+    /// Returns the order in which the two sides attack. Each entry is a full
+    /// attack, so brave weapons strike twice for each entry.
+    pub fn strike_order(&self) -> Vec<Side> {
+        match self {
+            SpeedDiff::Even => vec![Side::Atk, Side::Def],
+            SpeedDiff::AtkDoubles => vec![Side::Atk, Side::Def, Side::Atk],
+            SpeedDiff::DefDoubles => vec![Side::Atk, Side::Def, Side::Def],
+            SpeedDiff::Vantage => vec![Side::Def, Side::Atk],
+            SpeedDiff::VantageAtkDoubles => vec![Side::Def, Side::Atk, Side::Atk],
+            SpeedDiff::VantageDefDoubles => vec![Side::Def, Side::Atk, Side::Def],
+            SpeedDiff::Desperation => vec![Side::Atk, Side::Atk, Side::Def],
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -126,11 +522,11 @@ impl Outcome {
                 });
                 has_added = true;
             } else {
-                new_outcomes.push(outcome.clone());
+                new_outcomes.push(outcome);
             }
         }
         if !has_added {
-            new_outcomes.push(self.clone());
+            new_outcomes.push(*self);
         }
         new_outcomes
     }
@@ -145,6 +541,255 @@ impl Outcome {
     }
 }
 
+/// Something that happens during combat, along with the side that did it.
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum CombatEvent {
+    /// A strike that missed.
+    Miss(Side),
+    /// A strike that hit without critting.
+    Hit(Side),
+    /// A strike that crit.
+    Crit(Side),
+    /// A skill that activated on a strike that landed, like Luna.
+    Proc(Side),
+    /// A skill that replaced the first strike of an attack with a flurry of
+    /// strikes, like Astra. Each of those strikes has its own event. Traces
+    /// from before rules version 6 record these as `Proc` instead.
+    Flurry(Side),
+    /// A strike that hit the striker instead of the target, like the Devil
+    /// Axe.
+    Backfire(Side),
+    /// A skill that reduced the damage of a strike against this side, like
+    /// Pavise.
+    Guard(Side),
+}
+
+impl CombatEvent {
+    /// Returns the same event with attacker and defender switched.
+    pub fn switch(&self) -> CombatEvent {
+        match self {
+            CombatEvent::Miss(side) => CombatEvent::Miss(side.other()),
+            CombatEvent::Hit(side) => CombatEvent::Hit(side.other()),
+            CombatEvent::Crit(side) => CombatEvent::Crit(side.other()),
+            CombatEvent::Proc(side) => CombatEvent::Proc(side.other()),
+            CombatEvent::Flurry(side) => CombatEvent::Flurry(side.other()),
+            CombatEvent::Backfire(side) => CombatEvent::Backfire(side.other()),
+            CombatEvent::Guard(side) => CombatEvent::Guard(side.other()),
+        }
+    }
+
+    /// Returns the side whose strike landed, if this event is a strike that
+    /// landed. Guarded strikes and backfires still count as landing.
+    pub fn landed_by(&self) -> Option<Side> {
+        match self {
+            CombatEvent::Hit(side) | CombatEvent::Crit(side)
+                | CombatEvent::Proc(side) | CombatEvent::Backfire(side) => {
+                Some(*side)
+            }
+            CombatEvent::Guard(side) => Some(side.other()),
+            CombatEvent::Miss(_) | CombatEvent::Flurry(_) => None,
+        }
+    }
+}
+
+/// An outcome of combat along with the events that led to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TracedOutcome {
+    /// The final state and its probability.
+    pub outcome: Outcome,
+
+    /// Everything that happened in combat to get there, in order.
+    pub events: Vec<CombatEvent>,
+}
+
+/// A possible state of combat that the outcome engine can branch on. Plain
+/// `Outcome`s only keep track of HP, so identical ones can be merged, while
+/// `TracedOutcome`s also remember how they got there.
+pub trait Branch: Sized {
+    /// Returns the HP of both sides and the probability of this branch.
+    fn outcome(&self) -> Outcome;
+
+    /// Continues this branch with the given event, which happens with the
+    /// given probability and leaves the two sides with the given HP.
+    fn branch(&self, prob: f64, atk_hp: u32, def_hp: u32,
+              event: CombatEvent) -> Self;
+
+    /// Returns the same branch with its probability multiplied by the factor.
+    fn scale(&self, factor: f64) -> Self;
+
+    /// Switches attacker and defender.
+    fn switch(&self) -> Self;
+
+    /// Removes impossible branches from the list, combining any that can be
+    /// combined.
+    fn merge(branches: Vec<Self>) -> Vec<Self>;
+}
+
+impl Branch for Outcome {
+    fn outcome(&self) -> Outcome {
+        *self
+    }
+
+    fn branch(&self, prob: f64, atk_hp: u32, def_hp: u32,
+              _event: CombatEvent) -> Outcome {
+        Outcome{
+            prob: self.prob * prob,
+            atk_hp,
+            def_hp,
+        }
+    }
+
+    fn scale(&self, factor: f64) -> Outcome {
+        Outcome{
+            prob: self.prob * factor,
+            ..*self
+        }
+    }
+
+    fn switch(&self) -> Outcome {
+        Outcome::switch(self)
+    }
+
+    fn merge(branches: Vec<Outcome>) -> Vec<Outcome> {
+        Outcome::collect(branches)
+    }
+}
+
+impl Branch for TracedOutcome {
+    fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    fn branch(&self, prob: f64, atk_hp: u32, def_hp: u32,
+              event: CombatEvent) -> TracedOutcome {
+        let mut events = self.events.clone();
+        events.push(event);
+        TracedOutcome{
+            outcome: self.outcome.branch(prob, atk_hp, def_hp, event),
+            events,
+        }
+    }
+
+    fn scale(&self, factor: f64) -> TracedOutcome {
+        TracedOutcome{
+            outcome: self.outcome.scale(factor),
+            events: self.events.clone(),
+        }
+    }
+
+    fn switch(&self) -> TracedOutcome {
+        TracedOutcome{
+            outcome: self.outcome.switch(),
+            events: self.events.iter().map(|x| x.switch()).collect(),
+        }
+    }
+
+    fn merge(branches: Vec<TracedOutcome>) -> Vec<TracedOutcome> {
+        // different event histories are never combined
+        branches.into_iter().filter(|x| x.outcome.prob != 0.0).collect()
+    }
+}
+
+/// Probability queries on a list of possible outcomes, like the ones returned
+/// by `possible_outcomes`.
+pub trait OutcomeDist {
+    /// The total probability of the outcomes that satisfy the predicate.
+    fn prob_where<F: Fn(&Outcome) -> bool>(&self, pred: F) -> f64;
+
+    /// The probability that the attacker ends with at least the given HP.
+    fn prob_atk_hp_at_least(&self, hp: u32) -> f64 {
+        self.prob_where(|x| x.atk_hp >= hp)
+    }
+
+    /// The probability that the attacker ends with at most the given HP.
+    fn prob_atk_hp_at_most(&self, hp: u32) -> f64 {
+        self.prob_where(|x| x.atk_hp <= hp)
+    }
+
+    /// The probability that the defender ends with at least the given HP.
+    fn prob_def_hp_at_least(&self, hp: u32) -> f64 {
+        self.prob_where(|x| x.def_hp >= hp)
+    }
+
+    /// The probability that the defender ends with at most the given HP: for
+    /// example, the chance a boss is left low enough for a finisher to kill.
+    fn prob_def_hp_at_most(&self, hp: u32) -> f64 {
+        self.prob_where(|x| x.def_hp <= hp)
+    }
+
+    /// The probability that the attacker dies.
+    fn prob_atk_dies(&self) -> f64 {
+        self.prob_atk_hp_at_most(0)
+    }
+
+    /// The probability that the defender dies.
+    fn prob_def_dies(&self) -> f64 {
+        self.prob_def_hp_at_most(0)
+    }
+
+    /// The cumulative distribution of the given side's remaining HP: each
+    /// possible HP value, in increasing order, with the probability that the
+    /// side ends with that much HP or less.
+    fn hp_cdf(&self, side: Side) -> Vec<(u32, f64)>;
+
+    /// The cumulative distribution of the attacker's remaining HP.
+    fn atk_hp_cdf(&self) -> Vec<(u32, f64)> {
+        self.hp_cdf(Side::Atk)
+    }
+
+    /// The cumulative distribution of the defender's remaining HP.
+    fn def_hp_cdf(&self) -> Vec<(u32, f64)> {
+        self.hp_cdf(Side::Def)
+    }
+
+    /// Returns the given percentile (0-1) of the side's remaining HP: the
+    /// lowest HP such that the side ends with at most that much with at least
+    /// the given probability. The 0.05 percentile is a worst case that happens
+    /// one time in twenty. Returns 0 if there are no outcomes.
+    fn hp_percentile(&self, side: Side, percentile: f64) -> u32 {
+        let cdf = self.hp_cdf(side);
+        // allow for rounding error in the cumulative sums
+        cdf.iter()
+            .find(|(_, prob)| *prob >= percentile - 1e-12)
+            .or_else(|| cdf.last())
+            .map_or(0, |(hp, _)| *hp)
+    }
+
+    /// Returns the given percentile (0-1) of the attacker's remaining HP.
+    fn atk_hp_percentile(&self, percentile: f64) -> u32 {
+        self.hp_percentile(Side::Atk, percentile)
+    }
+
+    /// Returns the given percentile (0-1) of the defender's remaining HP.
+    fn def_hp_percentile(&self, percentile: f64) -> u32 {
+        self.hp_percentile(Side::Def, percentile)
+    }
+}
+
+impl OutcomeDist for [Outcome] {
+    fn prob_where<F: Fn(&Outcome) -> bool>(&self, pred: F) -> f64 {
+        self.iter().filter(|x| pred(x)).map(|x| x.prob).sum()
+    }
+
+    fn hp_cdf(&self, side: Side) -> Vec<(u32, f64)> {
+        let mut hps: Vec<(u32, f64)> = self.iter().map(|x| match side {
+            Side::Atk => (x.atk_hp, x.prob),
+            Side::Def => (x.def_hp, x.prob),
+        }).collect();
+        hps.sort_by_key(|x| x.0);
+
+        let mut cdf: Vec<(u32, f64)> = vec![];
+        let mut total = 0.0;
+        for (hp, prob) in hps {
+            total += prob;
+            match cdf.last_mut() {
+                Some(last) if last.0 == hp => last.1 = total,
+                _ => cdf.push((hp, total)),
+            }
+        }
+        cdf
+    }
+}
 
 /// Returns a list of all of the possible outcomes of combat with associated
 /// probability, using the given game's rules.
@@ -157,29 +802,328 @@ pub fn possible_outcomes(game: FEGame, atk: CombatStats, atk_hp: u32,
         def_hp,
     });
 
-    let after_atk = atk.possible_outcomes(game, initial);
-    let after_def = def.possible_outcomes(
-        game,
-        after_atk.into_iter().map(|x| x.switch()).collect()
-    ).into_iter().map(|x| x.switch()).collect();
+    outcomes_after_combat(game, atk, def, speed, initial)
+}
 
-    match speed {
-        SpeedDiff::Even => {
-            // AB attack pattern
-            after_def
-        },
-        SpeedDiff::AtkDoubles => {
-            // ABA attack pattern
-            atk.possible_outcomes(game, after_def)
-        },
-        SpeedDiff::DefDoubles => {
-            // ABB attack pattern
-            def.possible_outcomes(
-                game,
-                after_def.into_iter().map(|x| x.switch()).collect()
-            ).into_iter().map(|x| x.switch()).collect()
+/// Like `possible_outcomes`, but keeps every branch of combat separate and
+/// records the events along each one: which strikes hit, missed, or crit. This
+/// can produce many more outcomes, so it's best used for explaining a forecast
+/// rather than computing one.
+pub fn traced_outcomes(game: FEGame, atk: CombatStats, atk_hp: u32,
+                       def: CombatStats, def_hp: u32,
+                       speed: SpeedDiff) -> Vec<TracedOutcome> {
+    let initial = vec!(TracedOutcome{
+        outcome: Outcome{
+            prob: 1.0,
+            atk_hp,
+            def_hp,
         },
+        events: vec!(),
+    });
+
+    outcomes_after_combat(game, atk, def, speed, initial)
+}
+
+/// Returns the possible outcomes of a round of combat that starts from an
+/// existing list of possible states instead of a single known one, using the
+/// given game's rules.
+pub fn outcomes_after_combat<B: Branch>(game: FEGame, atk: CombatStats,
+                                        def: CombatStats, speed: SpeedDiff,
+                                        initial: Vec<B>) -> Vec<B> {
+    let attacks = attack_sequence(atk, def, speed);
+    attacks.into_iter().fold(initial, |states, (side, stats, guard)| {
+        (0..stats.strikes_per_attack()).fold(states, |states, n| {
+            side_strike(game, side, &stats, guard, states, n)
+        })
+    })
+}
+
+/// Returns each attack made in combat, in order, along with the stats the
+/// striking side actually uses against the other and the other side's
+/// defensive skill.
+pub(crate) fn attack_sequence(atk: CombatStats, def: CombatStats,
+                              speed: SpeedDiff)
+                              -> Vec<(Side, CombatStats, Option<DefenseProc>)> {
+    let (atk_vs, def_vs) = (atk.against(&def), def.against(&atk));
+    speed.strike_order().into_iter()
+        .filter(|side| *side == Side::Atk || !def.cannot_counter)
+        .map(|side| match side {
+            Side::Atk => (side, atk_vs, def.guard),
+            Side::Def => (side, def_vs, atk.guard),
+        })
+        .collect()
+}
+
+/// Returns the possible states after the given side makes the given strike of
+/// an attack using the given stats, against a target with the given defensive
+/// skill.
+pub(crate) fn side_strike<B: Branch>(game: FEGame, side: Side,
+                                     stats: &CombatStats,
+                                     target_guard: Option<DefenseProc>,
+                                     states: Vec<B>, n: usize) -> Vec<B> {
+    match side {
+        Side::Atk => stats.nth_strike(game, states, n, target_guard),
+        // the defender strikes from the attacker's perspective, so switch
+        // sides before and after
+        Side::Def => switch_all(stats.nth_strike(game, switch_all(states), n,
+                                                 target_guard)),
+    }
+}
+
+/// Returns the given percentage of the damage, rounding down. Damage too big to
+/// fit is capped at `u32::MAX` instead of overflowing.
+pub(crate) fn percent_of(dmg: u32, percent: u32) -> u32 {
+    (dmg as u64 * percent as u64 / 100).min(u32::MAX as u64) as u32
+}
+
+/// Switches attacker and defender in every branch in the list.
+fn switch_all<B: Branch>(branches: Vec<B>) -> Vec<B> {
+    branches.into_iter().map(|x| x.switch()).collect()
+}
+
+/// Flat damage dealt to each side once the strikes of combat are over, like
+/// poison weapons, fire terrain, or debuffs that chip away at HP.
+#[derive(Default, Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct ChipDamage {
+    /// The damage dealt to the attacker.
+    pub atk: u32,
+
+    /// The damage dealt to the defender.
+    pub def: u32,
+
+    /// Whether the damage can kill. If not, it can bring units down to 1 HP
+    /// but no lower, which is how most poison works.
+    pub lethal: bool,
+}
+
+impl ChipDamage {
+    /// Applies the chip damage to each of the outcomes, combining any that end
+    /// up identical. Units that died in combat stay dead.
+    pub fn apply(&self, outcomes: Vec<Outcome>) -> Vec<Outcome> {
+        let outcomes = apply_hp_change(outcomes, Side::Atk,
+                                       &[(1.0, self.change(self.atk))], None);
+        apply_hp_change(outcomes, Side::Def, &[(1.0, self.change(self.def))],
+                        None)
+    }
+
+    /// Returns the change to one side's HP from the given chip damage.
+    fn change(&self, dmg: u32) -> HpChange {
+        if self.lethal {
+            HpChange::Damage(dmg)
+        } else {
+            HpChange::Chip(dmg)
+        }
+    }
+}
+
+/// A change to one side's HP from outside of combat, like poison or a heal.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum HpChange {
+    /// Takes the given damage, which can kill.
+    Damage(u32),
+
+    /// Takes the given damage, but can't go below 1 HP, like most poison.
+    Chip(u32),
+
+    /// Heals the given amount.
+    Heal(u32),
+}
+
+impl HpChange {
+    /// Returns the HP left after the change, capping heals at the given max
+    /// HP if there is one. The dead stay dead.
+    fn apply(&self, hp: u32, max_hp: Option<u32>) -> u32 {
+        match self {
+            _ if hp == 0 => 0,
+            HpChange::Damage(dmg) => hp.saturating_sub(*dmg),
+            HpChange::Chip(dmg) => hp.saturating_sub(*dmg).max(1),
+            HpChange::Heal(heal) => {
+                let healed = hp.saturating_add(*heal);
+                max_hp.map_or(healed, |max_hp| healed.min(max_hp.max(hp)))
+            }
+        }
+    }
+}
+
+/// Applies a change to one side's HP after combat, where the change is drawn
+/// from the given distribution of changes and their probabilities (0-1). A
+/// fixed change is a distribution with a single entry of probability 1. Heals
+/// are capped at `max_hp` if it's given.
+pub fn apply_hp_change(outcomes: Vec<Outcome>, side: Side,
+                       changes: &[(f64, HpChange)],
+                       max_hp: Option<u32>) -> Vec<Outcome> {
+    let mut new_outcomes = vec!();
+    for outcome in outcomes {
+        for (prob, change) in changes {
+            new_outcomes.push(match side {
+                Side::Atk => Outcome{
+                    prob: outcome.prob * prob,
+                    atk_hp: change.apply(outcome.atk_hp, max_hp),
+                    def_hp: outcome.def_hp,
+                },
+                Side::Def => Outcome{
+                    prob: outcome.prob * prob,
+                    atk_hp: outcome.atk_hp,
+                    def_hp: change.apply(outcome.def_hp, max_hp),
+                },
+            });
+        }
+    }
+    Outcome::collect(new_outcomes)
+}
+
+/// A single enemy attacking the unit during enemy phase.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct EnemyMatchup {
+    /// The enemy's stats when attacking the unit.
+    pub enemy: CombatStats,
+
+    /// The enemy's HP before combat starts.
+    pub enemy_hp: u32,
+
+    /// The unit's stats when countering this enemy.
+    pub unit: CombatStats,
+
+    /// The speed differential, with the enemy as the attacker.
+    pub speed: SpeedDiff,
+}
+
+/// Returns the possible outcomes of the unit being attacked by each of the
+/// given enemies in order, carrying the unit's HP from one combat to the next.
+/// The unit is the defender in every combat, so `def_hp` is the unit's
+/// remaining HP and `atk_hp` is the remaining HP of the last enemy to attack.
+pub fn enemy_phase(game: FEGame, unit_hp: u32,
+                   enemies: &[EnemyMatchup]) -> Vec<Outcome> {
+    let initial = vec!(Outcome{
+        prob: 1.0,
+        atk_hp: 0,
+        def_hp: unit_hp,
+    });
+
+    enemies.iter().fold(initial, |states, matchup| {
+        attacked_by(game, matchup, states)
+    })
+}
+
+/// Returns the outcomes after the enemy in the matchup attacks the unit from
+/// each of the given states.
+fn attacked_by(game: FEGame, matchup: &EnemyMatchup,
+               states: Vec<Outcome>) -> Vec<Outcome> {
+    // every enemy starts at full HP, regardless of how the last one fared
+    let fresh = Outcome::collect(states.into_iter().map(|x| Outcome{
+        prob: x.prob,
+        atk_hp: matchup.enemy_hp,
+        def_hp: x.def_hp,
+    }).collect());
+    outcomes_after_combat(game, matchup.enemy, matchup.unit,
+                          matchup.speed, fresh)
+}
+
+/// An enemy that may attack the unit during enemy phase. Some enemies aren't
+/// limited by where they stand on the map: witches in *Gaiden* and *Shadows of
+/// Valentia* can warp next to any unit, and summons from cantors appear
+/// wherever there's room, so whether they attack a given unit is a chance
+/// instead of a certainty.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Threat {
+    /// An enemy that will attack the unit.
+    Attacks(EnemyMatchup),
+
+    /// An enemy that attacks the unit with the given probability, as a number
+    /// between 0 and 1, and otherwise goes after someone else.
+    MayAttack(EnemyMatchup, f64),
+}
+
+impl Threat {
+    /// Returns the threat of a witch that can warp next to any of `targets`
+    /// units, picking one of them uniformly.
+    pub fn witch(matchup: EnemyMatchup, targets: u32) -> Threat {
+        Threat::MayAttack(matchup, 1.0 / targets.max(1) as f64)
+    }
+
+    /// Returns the threats from the summons a cantor has brought out after the
+    /// given number of turns, one per turn, each of which attacks the unit
+    /// with probability `prob`.
+    pub fn cantor_summons(summon: EnemyMatchup, turns: u32,
+                          prob: f64) -> Vec<Threat> {
+        (0..turns).map(|_| Threat::MayAttack(summon, prob)).collect()
+    }
+}
+
+/// Like `enemy_phase`, but for threats that may or may not attack the unit.
+/// The threats still attack in the given order, skipping any that go after
+/// someone else.
+pub fn enemy_phase_threats(game: FEGame, unit_hp: u32,
+                           threats: &[Threat]) -> Vec<Outcome> {
+    let initial = vec!(Outcome{
+        prob: 1.0,
+        atk_hp: 0,
+        def_hp: unit_hp,
+    });
+
+    threats.iter().fold(initial, |states, threat| match threat {
+        Threat::Attacks(matchup) => attacked_by(game, matchup, states),
+        Threat::MayAttack(matchup, prob) => {
+            let prob = prob.clamp(0.0, 1.0);
+            let skipped: Vec<Outcome> = states.iter()
+                .map(|x| x.scale(1.0 - prob))
+                .collect();
+            let attacked = attacked_by(
+                game, matchup,
+                states.iter().map(|x| x.scale(prob)).collect());
+            Outcome::collect(skipped.into_iter().chain(attacked).collect())
+        }
+    })
+}
+
+/// The unit's chance of surviving enemy phase when the order the enemies will
+/// attack in isn't known.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderingSurvival {
+    /// The survival chance under the most dangerous ordering.
+    pub worst: f64,
+
+    /// The survival chance under the safest ordering.
+    pub best: f64,
+
+    /// The survival chance if every ordering is equally likely.
+    pub uniform: f64,
+}
+
+/// Computes the unit's chance of surviving attacks from all of the given
+/// enemies over every order they could attack in. The number of orderings
+/// grows factorially, so this is only practical for a handful of enemies.
+pub fn enemy_phase_orderings(game: FEGame, unit_hp: u32,
+                             enemies: &[EnemyMatchup]) -> OrderingSurvival {
+    let survival: Vec<f64> = permutations(enemies.len()).into_iter().map(|order| {
+        let ordered: Vec<EnemyMatchup> = order.into_iter()
+            .map(|i| enemies[i])
+            .collect();
+        enemy_phase(game, unit_hp, &ordered).prob_def_hp_at_least(1)
+    }).collect();
+
+    OrderingSurvival{
+        worst: survival.iter().cloned().fold(f64::INFINITY, f64::min),
+        best: survival.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+        uniform: survival.iter().sum::<f64>() / survival.len() as f64,
+    }
+}
+
+/// Returns every ordering of the indices 0 to n - 1.
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![vec![]];
     }
+    let mut orders = vec![];
+    for order in permutations(n - 1) {
+        // insert the new index at every possible position
+        for i in 0..n {
+            let mut new_order = order.clone();
+            new_order.insert(i, n - 1);
+            orders.push(new_order);
+        }
+    }
+    orders
 }
 
 #[cfg(test)]
@@ -190,13 +1134,513 @@ mod tests {
     fn test_outcomes() {
         dbg!(Outcome{prob: 1.0, atk_hp: 20, def_hp: 30}.add_into(vec!()));
         dbg!(CombatStats{
-            dmg: 10, hit: 90, crit: 0, is_brave: false,
+            dmg: 10, hit: 90, crit: 0, ..Default::default()
         }.possible_outcomes(FEGame::FE15,
                             vec![Outcome{prob: 1.0, atk_hp: 1, def_hp: 40}]));
         dbg!(possible_outcomes(FEGame::FE15, CombatStats{
-            dmg: 10, hit: 50, crit: 0, is_brave: false,
+            dmg: 10, hit: 50, crit: 0, ..Default::default()
         }, 30, CombatStats{
-            dmg: 10, hit: 100, crit: 0, is_brave: false
+            dmg: 10, hit: 100, crit: 0, ..Default::default()
         }, 20, SpeedDiff::AtkDoubles));
     }
+
+    #[test]
+    fn test_vantage() {
+        let atk = CombatStats{
+            dmg: 20, hit: 100, crit: 0, ..Default::default()
+        };
+        let def = CombatStats{
+            dmg: 20, hit: 100, crit: 0, ..Default::default()
+        };
+        // without Vantage the attacker kills first
+        assert_eq!(possible_outcomes(FEGame::FE9, atk, 20, def, 20, SpeedDiff::Even),
+                   vec![Outcome{prob: 1.0, atk_hp: 20, def_hp: 0}]);
+        // with Vantage the defender does
+        assert_eq!(possible_outcomes(FEGame::FE9, atk, 20, def, 20, SpeedDiff::Vantage),
+                   vec![Outcome{prob: 1.0, atk_hp: 0, def_hp: 20}]);
+        // Vantage with the attacker doubling: BAA
+        assert_eq!(possible_outcomes(FEGame::FE9, atk, 40, def, 40,
+                                     SpeedDiff::VantageAtkDoubles),
+                   vec![Outcome{prob: 1.0, atk_hp: 20, def_hp: 0}]);
+    }
+
+    #[test]
+    fn test_desperation() {
+        let atk = CombatStats{
+            dmg: 10, hit: 100, crit: 0, ..Default::default()
+        };
+        let def = CombatStats{
+            dmg: 20, hit: 100, crit: 0, ..Default::default()
+        };
+        // a normal double gets countered before the follow-up lands
+        assert_eq!(possible_outcomes(FEGame::FE13, atk, 20, def, 20,
+                                     SpeedDiff::AtkDoubles),
+                   vec![Outcome{prob: 1.0, atk_hp: 0, def_hp: 10}]);
+        // Desperation kills before the counter
+        assert_eq!(possible_outcomes(FEGame::FE13, atk, 20, def, 20,
+                                     SpeedDiff::Desperation),
+                   vec![Outcome{prob: 1.0, atk_hp: 20, def_hp: 0}]);
+    }
+
+    #[test]
+    fn test_jugdral_crits() {
+        let stats = CombatStats{
+            dmg: 5, hit: 100, crit: 100, ..Default::default()
+        };
+        let start = vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 40}];
+        // without Atk and Def, crits triple damage
+        assert_eq!(stats.possible_outcomes(FEGame::FE4, start.clone()),
+                   vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 25}]);
+        // 15 Atk against 10 Def crits for 20 damage, not 15
+        let jugdral = CombatStats{atk_def: Some((15, 10)), ..stats};
+        assert_eq!(jugdral.possible_outcomes(FEGame::FE4, start),
+                   vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 20}]);
+    }
+
+    #[test]
+    fn test_strike_procs() {
+        let astra = CombatStats{
+            dmg: 10, hit: 100, crit: 0,
+            strike_proc: Some(StrikeProc{rate: 100, strikes: 5, dmg_percent: 50}),
+            ..Default::default()
+        };
+        let start = vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 40}];
+        assert_eq!(astra.possible_outcomes(FEGame::FE10, start.clone()),
+                   vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 15}]);
+
+        let adept = CombatStats{
+            dmg: 10, hit: 100, crit: 0,
+            strike_proc: Some(StrikeProc{rate: 25, strikes: 2, dmg_percent: 100}),
+            ..Default::default()
+        };
+        assert_eq!(adept.possible_outcomes(FEGame::FE4, start),
+                   vec![Outcome{prob: 0.75, atk_hp: 30, def_hp: 30},
+                        Outcome{prob: 0.25, atk_hp: 30, def_hp: 20}]);
+    }
+
+    #[test]
+    fn test_lifesteal() {
+        let nosferatu = CombatStats{
+            dmg: 8, hit: 100, crit: 0, drain_percent: 100, max_hp: Some(30),
+            ..Default::default()
+        };
+        let enemy = CombatStats{
+            dmg: 12, hit: 100, crit: 0, ..Default::default()
+        };
+        // 20 - 12 + 8 + 8 = 24
+        assert_eq!(possible_outcomes(FEGame::FE8, nosferatu, 20, enemy, 30,
+                                     SpeedDiff::AtkDoubles),
+                   vec![Outcome{prob: 1.0, atk_hp: 24, def_hp: 14}]);
+        // healing is capped by max HP and by the target's remaining HP
+        assert_eq!(possible_outcomes(FEGame::FE8, nosferatu, 28, enemy, 5,
+                                     SpeedDiff::Even),
+                   vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 0}]);
+        assert_eq!(possible_outcomes(FEGame::FE8, nosferatu, 20, enemy, 5,
+                                     SpeedDiff::Even),
+                   vec![Outcome{prob: 1.0, atk_hp: 25, def_hp: 0}]);
+    }
+
+    #[test]
+    fn test_enemy_phase_orderings() {
+        // with lifesteal, taking the weak hit first lets the unit heal back up
+        // before the big one, but not the other way around
+        let nosferatu = CombatStats{
+            dmg: 10, hit: 100, crit: 0, drain_percent: 100, max_hp: Some(20),
+            ..Default::default()
+        };
+        let weak = EnemyMatchup{
+            enemy: CombatStats{
+                dmg: 8, hit: 100, crit: 0, ..Default::default()
+            },
+            enemy_hp: 30,
+            unit: nosferatu,
+            speed: SpeedDiff::Even,
+        };
+        let strong = EnemyMatchup{
+            enemy: CombatStats{
+                dmg: 18, hit: 100, crit: 0, ..Default::default()
+            },
+            speed: SpeedDiff::Vantage,
+            ..weak
+        };
+        let survival = enemy_phase_orderings(FEGame::FE7, 20, &[weak, strong]);
+        assert_eq!(survival, OrderingSurvival{
+            worst: 0.0,
+            best: 1.0,
+            uniform: 0.5,
+        });
+        assert_eq!(permutations(3).len(), 6);
+    }
+
+    #[test]
+    fn test_no_counter() {
+        let atk = CombatStats{
+            dmg: 10, hit: 50, crit: 0, ..Default::default()
+        };
+        let def = CombatStats{
+            dmg: 10, hit: 50, crit: 0, cannot_counter: true,
+            ..Default::default()
+        };
+        let traces = traced_outcomes(FEGame::FE1, atk, 30, def, 30,
+                                     SpeedDiff::DefDoubles);
+        // only the attacker's single strike happens
+        assert_eq!(traces.len(), 2);
+        assert!(traces.iter().all(|x| x.events.len() == 1));
+    }
+
+    #[test]
+    fn test_chip_damage() {
+        let outcomes = vec![Outcome{prob: 0.5, atk_hp: 12, def_hp: 0},
+                            Outcome{prob: 0.25, atk_hp: 8, def_hp: 4},
+                            Outcome{prob: 0.25, atk_hp: 8, def_hp: 10}];
+        let poison = ChipDamage{atk: 0, def: 5, lethal: false};
+        assert_eq!(poison.apply(outcomes.clone()),
+                   vec![Outcome{prob: 0.5, atk_hp: 12, def_hp: 0},
+                        Outcome{prob: 0.25, atk_hp: 8, def_hp: 1},
+                        Outcome{prob: 0.25, atk_hp: 8, def_hp: 5}]);
+        let fire = ChipDamage{atk: 10, def: 0, lethal: true};
+        assert_eq!(fire.apply(outcomes),
+                   vec![Outcome{prob: 0.5, atk_hp: 2, def_hp: 0},
+                        Outcome{prob: 0.25, atk_hp: 0, def_hp: 4},
+                        Outcome{prob: 0.25, atk_hp: 0, def_hp: 10}]);
+    }
+
+    #[test]
+    fn test_crit_immune() {
+        let killer = CombatStats{
+            dmg: 10, hit: 100, crit: 50, ..Default::default()
+        };
+        let iote = CombatStats{
+            dmg: 0, hit: 0, crit: 0, crit_immune: true, ..Default::default()
+        };
+        assert_eq!(possible_outcomes(FEGame::FE8, killer, 30, iote, 30,
+                                     SpeedDiff::Even),
+                   vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 20}]);
+    }
+
+    #[test]
+    fn test_hp_queries() {
+        let outcomes = [Outcome{prob: 0.5, atk_hp: 12, def_hp: 0},
+                        Outcome{prob: 0.25, atk_hp: 8, def_hp: 4},
+                        Outcome{prob: 0.25, atk_hp: 0, def_hp: 10}];
+        assert_eq!(outcomes.prob_def_hp_at_most(4), 0.75);
+        assert_eq!(outcomes.prob_def_hp_at_least(5), 0.25);
+        assert_eq!(outcomes.prob_atk_hp_at_least(8), 0.75);
+        assert_eq!(outcomes.prob_atk_hp_at_most(8), 0.5);
+        assert_eq!(outcomes.prob_def_dies(), 0.5);
+        assert_eq!(outcomes.prob_atk_dies(), 0.25);
+    }
+
+    #[test]
+    fn test_hp_percentiles() {
+        let outcomes = [Outcome{prob: 0.5, atk_hp: 12, def_hp: 0},
+                        Outcome{prob: 0.05, atk_hp: 2, def_hp: 4},
+                        Outcome{prob: 0.2, atk_hp: 12, def_hp: 7},
+                        Outcome{prob: 0.25, atk_hp: 8, def_hp: 10}];
+        assert_eq!(outcomes.atk_hp_cdf(), vec![(2, 0.05), (8, 0.3), (12, 1.0)]);
+        assert_eq!(outcomes.def_hp_cdf(), vec![(0, 0.5), (4, 0.55), (7, 0.75),
+                                               (10, 1.0)]);
+        assert_eq!(outcomes.atk_hp_percentile(0.05), 2);
+        assert_eq!(outcomes.atk_hp_percentile(0.06), 8);
+        assert_eq!(outcomes.atk_hp_percentile(1.0), 12);
+        assert_eq!(outcomes.def_hp_percentile(0.5), 0);
+        assert_eq!(outcomes.def_hp_percentile(0.9), 10);
+        assert_eq!([].def_hp_percentile(0.5), 0);
+    }
+
+    #[test]
+    fn test_devil_axe() {
+        let devil_axe = CombatStats{
+            dmg: 15, hit: 100, crit: 0,
+            backfire: Some(Backfire{rate: 20, dmg: 12}),
+            ..Default::default()
+        };
+        let start = vec![Outcome{prob: 1.0, atk_hp: 30, def_hp: 30}];
+        let outcomes = devil_axe.possible_outcomes(FEGame::FE7, start);
+        assert_eq!(outcomes.len(), 2);
+        assert!((outcomes.prob_where(|x| x.atk_hp == 18 && x.def_hp == 30) - 0.2)
+                .abs() < 1e-9);
+        assert!((outcomes.prob_where(|x| x.atk_hp == 30 && x.def_hp == 15) - 0.8)
+                .abs() < 1e-9);
+
+        // a backfire can kill the wielder before the counter even happens
+        let enemy = CombatStats{
+            dmg: 1, hit: 100, crit: 0, ..Default::default()
+        };
+        let traces = traced_outcomes(FEGame::FE7, devil_axe, 10, enemy, 30,
+                                     SpeedDiff::Even);
+        assert_eq!(traces[0].events, vec![CombatEvent::Backfire(Side::Atk)]);
+        assert_eq!(traces[0].outcome.atk_hp, 0);
+    }
+
+    #[test]
+    fn test_old_stats_load() {
+        // stats saved before the newer fields existed still load
+        let json = r#"{"dmg":1,"hit":1,"crit":1,"is_brave":false}"#;
+        let stats: CombatStats = serde_json::from_str(json).unwrap();
+        assert_eq!(stats, CombatStats{
+            dmg: 1, hit: 1, crit: 1, ..Default::default()
+        });
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(serde_json::from_str::<CombatStats>(&json).unwrap(), stats);
+    }
+
+    #[test]
+    fn test_combat_stops_on_death() {
+        // the attacker kills with the first strike, so the follow-up and the
+        // counter never happen
+        let killer = CombatStats{
+            dmg: 10, hit: 100, crit: 0, ..Default::default()
+        };
+        let traces = traced_outcomes(FEGame::FE7, killer, 20, killer, 10,
+                                     SpeedDiff::AtkDoubles);
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].events, vec![CombatEvent::Hit(Side::Atk)]);
+        assert_eq!(traces[0].outcome.atk_hp, 20);
+    }
+
+    #[test]
+    fn test_traces() {
+        let atk = CombatStats{
+            dmg: 10, hit: 50, crit: 0, ..Default::default()
+        };
+        let def = CombatStats{
+            dmg: 10, hit: 100, crit: 0, ..Default::default()
+        };
+        let traces = traced_outcomes(FEGame::FE1, atk, 30, def, 30,
+                                     SpeedDiff::AtkDoubles);
+        // two coin flips for the attacker, with the counter always hitting
+        assert_eq!(traces.len(), 4);
+        assert_eq!(traces[0].events, vec![CombatEvent::Miss(Side::Atk),
+                                          CombatEvent::Hit(Side::Def),
+                                          CombatEvent::Miss(Side::Atk)]);
+        let total: f64 = traces.iter().map(|x| x.outcome.prob).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        // merging the traces gives the usual outcomes back
+        let merged = Outcome::collect(traces.iter().map(|x| x.outcome).collect());
+        assert_eq!(merged, possible_outcomes(FEGame::FE1, atk, 30, def, 30,
+                                             SpeedDiff::AtkDoubles));
+    }
+
+    #[test]
+    fn test_enemy_phase() {
+        let matchup = EnemyMatchup{
+            enemy: CombatStats{
+                dmg: 10, hit: 100, crit: 0, ..Default::default()
+            },
+            enemy_hp: 30,
+            unit: CombatStats{
+                dmg: 5, hit: 100, crit: 0, ..Default::default()
+            },
+            speed: SpeedDiff::Even,
+        };
+        let outcomes = enemy_phase(FEGame::FE7, 25, &[matchup, matchup]);
+        assert_eq!(outcomes, vec![Outcome{prob: 1.0, atk_hp: 25, def_hp: 5}]);
+
+        // a 50% hit enemy splits the distribution in two
+        let coin_flip = EnemyMatchup{
+            enemy: CombatStats{
+                dmg: 10, hit: 50, crit: 0, ..Default::default()
+            },
+            ..matchup
+        };
+        let outcomes = enemy_phase(FEGame::FE7, 25, &[coin_flip, matchup]);
+        assert_eq!(outcomes.len(), 2);
+        let hit_twice = outcomes.prob_where(|x| x.def_hp == 5);
+        assert!((hit_twice - FEGame::FE7.true_hit(50)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_dmg_procs() {
+        let luna = CombatStats{
+            dmg: 10, hit: 100, crit: 0, atk_def: Some((20, 10)),
+            dmg_proc: Some(DamageProc{
+                rate: 25, effect: DamageEffect::IgnoreDef(50)
+            }),
+            ..Default::default()
+        };
+        let outcomes = luna.possible_outcomes(FEGame::FE13, vec!(Outcome{
+            prob: 1.0, atk_hp: 20, def_hp: 30
+        }));
+        assert_eq!(outcomes, vec!(
+            Outcome{prob: 0.75, atk_hp: 20, def_hp: 20},
+            Outcome{prob: 0.25, atk_hp: 20, def_hp: 15},
+        ));
+        // ignoring more than all of the target's Def is the same as all of it
+        let overkill = CombatStats{
+            dmg_proc: Some(DamageProc{
+                rate: 100, effect: DamageEffect::IgnoreDef(150)
+            }),
+            ..luna
+        };
+        let outcomes = overkill.possible_outcomes(FEGame::FE13, vec!(Outcome{
+            prob: 1.0, atk_hp: 20, def_hp: 30
+        }));
+        assert_eq!(outcomes, vec!(Outcome{prob: 1.0, atk_hp: 20, def_hp: 10}));
+
+        // Lethality kills through any amount of HP, but only on a hit
+        let lethality = CombatStats{
+            dmg: 1, hit: 50, crit: 0,
+            dmg_proc: Some(DamageProc{rate: 10, effect: DamageEffect::Lethal}),
+            ..Default::default()
+        };
+        let outcomes = possible_outcomes(FEGame::FE8, lethality, 20,
+                                         CombatStats::default(), 60,
+                                         SpeedDiff::Even);
+        let expected = FEGame::FE8.true_hit(50) * 0.1;
+        assert!((outcomes.prob_def_dies() - expected).abs() < 1e-9);
+
+        // procs are still recorded in traces
+        let traces = traced_outcomes(FEGame::FE8, lethality, 20,
+                                     CombatStats::default(), 60,
+                                     SpeedDiff::Even);
+        let proc = CombatEvent::Proc(Side::Atk);
+        assert!(traces.iter().any(|x| x.events.contains(&proc)));
+    }
+
+    #[test]
+    fn test_guard() {
+        let atk = CombatStats{dmg: 10, hit: 100, crit: 0, ..Default::default()};
+        let pavise = CombatStats{
+            guard: Some(DefenseProc{rate: 20, dmg_percent: 50}),
+            ..Default::default()
+        };
+        let outcomes = possible_outcomes(FEGame::FE9, atk, 20, pavise, 30,
+                                         SpeedDiff::Even);
+        assert_eq!(outcomes, vec!(
+            Outcome{prob: 0.8, atk_hp: 20, def_hp: 20},
+            Outcome{prob: 0.2, atk_hp: 20, def_hp: 25},
+        ));
+
+        // Great Shield negates even crits, but not Lethality
+        let great_shield = CombatStats{
+            guard: Some(DefenseProc{rate: 50, dmg_percent: 0}),
+            ..Default::default()
+        };
+        let lethal = CombatStats{
+            crit: 100,
+            dmg_proc: Some(DamageProc{rate: 100, effect: DamageEffect::Lethal}),
+            ..atk
+        };
+        let outcomes = possible_outcomes(FEGame::FE9, lethal, 20, great_shield,
+                                         30, SpeedDiff::Even);
+        assert_eq!(outcomes.prob_def_dies(), 1.0);
+        let crits = CombatStats{crit: 100, ..atk};
+        let traces = traced_outcomes(FEGame::FE9, crits, 20, great_shield, 30,
+                                     SpeedDiff::Even);
+        let guard = CombatEvent::Guard(Side::Def);
+        let blocked: f64 = traces.iter()
+            .filter(|x| x.events.contains(&guard))
+            .map(|x| x.outcome.prob)
+            .sum();
+        assert!((blocked - 0.5).abs() < 1e-9);
+        assert!(traces.iter().all(|x| !x.events.contains(&guard)
+                                  || x.outcome.def_hp == 30));
+    }
+
+    #[test]
+    fn test_degenerate_inputs() {
+        let start = vec![Outcome{prob: 1.0, atk_hp: 20, def_hp: 20}];
+        // 0 hit, 0 damage, and dead defenders all leave nothing to branch on
+        for stats in [
+            CombatStats{dmg: 10, hit: 0, crit: 50, ..Default::default()},
+            CombatStats{dmg: 0, hit: 80, crit: 50, ..Default::default()},
+        ].iter() {
+            let outcomes = stats.possible_outcomes(FEGame::FE7, start.clone());
+            assert_eq!(outcomes, start);
+        }
+        let dead = possible_outcomes(
+            FEGame::FE7, CombatStats{dmg: 10, hit: 80, ..Default::default()},
+            20, CombatStats::default(), 0, SpeedDiff::AtkDoubles
+        );
+        assert_eq!(dead, vec![Outcome{prob: 1.0, atk_hp: 20, def_hp: 0}]);
+
+        // listed rates above 100 count as 100
+        let over = CombatStats{dmg: 5, hit: 150, crit: 0, ..Default::default()};
+        let outcomes = over.possible_outcomes(FEGame::FE1, start.clone());
+        assert_eq!(outcomes, vec![Outcome{prob: 1.0, atk_hp: 20, def_hp: 15}]);
+
+        // traces don't keep impossible branches either
+        let traces = traced_outcomes(
+            FEGame::FE7, CombatStats{dmg: 10, hit: 0, ..Default::default()},
+            20, CombatStats::default(), 20, SpeedDiff::Even
+        );
+        assert_eq!(traces.len(), 1);
+        assert_eq!(traces[0].events, vec![CombatEvent::Miss(Side::Atk),
+                                          CombatEvent::Miss(Side::Def)]);
+    }
+
+    #[test]
+    fn test_hp_change() {
+        let outcomes = vec![
+            Outcome{prob: 0.5, atk_hp: 15, def_hp: 0},
+            Outcome{prob: 0.5, atk_hp: 8, def_hp: 10},
+        ];
+        // poison, then a heal that might not come
+        let poisoned = apply_hp_change(outcomes, Side::Atk,
+                                       &[(1.0, HpChange::Damage(10))], None);
+        assert!((poisoned.prob_atk_dies() - 0.5).abs() < 1e-9);
+        let healed = apply_hp_change(poisoned, Side::Atk, &[
+            (0.8, HpChange::Heal(14)),
+            (0.2, HpChange::Heal(0)),
+        ], Some(18));
+        assert!((healed.prob_atk_dies() - 0.5).abs() < 1e-9);
+        assert!((healed.prob_where(|x| x.atk_hp == 18) - 0.4).abs() < 1e-9);
+        assert!((healed.prob_where(|x| x.atk_hp == 5) - 0.1).abs() < 1e-9);
+        // the defender's HP is untouched
+        assert!((healed.prob_def_dies() - 0.5).abs() < 1e-9);
+        // chip damage leaves the living at 1 HP
+        let chipped = apply_hp_change(healed, Side::Atk,
+                                      &[(1.0, HpChange::Chip(30))], None);
+        assert!((chipped.prob_atk_dies() - 0.5).abs() < 1e-9);
+        assert!((chipped.prob_where(|x| x.atk_hp == 1) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_enemy_phase_threats() {
+        let witch = EnemyMatchup{
+            enemy: CombatStats{
+                dmg: 12, hit: 100, crit: 0, ..Default::default()
+            },
+            enemy_hp: 20,
+            unit: CombatStats{
+                dmg: 5, hit: 100, crit: 0, ..Default::default()
+            },
+            speed: SpeedDiff::AtkDoubles,
+        };
+        // certain attacks are the same as a normal enemy phase
+        assert_eq!(enemy_phase_threats(FEGame::FE15, 30, &[Threat::Attacks(witch)]),
+                   enemy_phase(FEGame::FE15, 30, &[witch]));
+
+        // a witch with four targets only comes after this unit a quarter of
+        // the time, and kills whenever she does
+        let outcomes = enemy_phase_threats(FEGame::FE15, 20,
+                                           &[Threat::witch(witch, 4)]);
+        assert!((outcomes.prob_def_dies() - 0.25).abs() < 1e-9);
+
+        // two summons that each show up half the time
+        let summons = Threat::cantor_summons(witch, 2, 0.5);
+        assert_eq!(summons.len(), 2);
+        let outcomes = enemy_phase_threats(FEGame::FE15, 30, &summons);
+        assert!((outcomes.prob_def_hp_at_most(6) - 0.75).abs() < 1e-9);
+        assert!((outcomes.prob_def_hp_at_least(30) - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_always_hits() {
+        // FE5 caps listed hit at 99, but a strike that can't miss still lands
+        let gambit = CombatStats{
+            dmg: 10, hit: 0, crit: 20, always_hits: true, ..Default::default()
+        };
+        assert_eq!(gambit.true_hit(FEGame::FE5), 1.0);
+        assert!((gambit.prob_crit(FEGame::FE5) - 0.2).abs() < 1e-9);
+        let outcomes = gambit.possible_outcomes(FEGame::FE5, vec!(Outcome{
+            prob: 1.0, atk_hp: 20, def_hp: 10
+        }));
+        assert_eq!(outcomes.prob_def_dies(), 1.0);
+        // the same strike without the guarantee is clamped as usual
+        let listed = CombatStats{hit: 100, always_hits: false, ..gambit};
+        assert!((listed.true_hit(FEGame::FE5) - 0.99).abs() < 1e-9);
+    }
 }
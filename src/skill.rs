@@ -0,0 +1,292 @@
+//! Skills: abilities like Astra, Luna, Pavise, or a flat bonus to hit that
+//! change how a unit fights. Most of them roll to activate, usually with a
+//! chance based on one of the user's stats. The outcome engine already knows
+//! how to branch on these procs through `CombatStats`, so a skill here just
+//! says which of those hooks it fills in and how often it activates.
+
+use crate::calc::CombatSide;
+use crate::damage::DamageType;
+use crate::fegame::FEGame;
+use crate::growth::{Class, Stat};
+use crate::simple_calc::{CombatStats, DamageProc, DefenseProc, StrikeProc};
+use crate::stats::StatBlock;
+use crate::unit::{Unit, UnitType};
+use crate::weapon::{Item, Weapon, WeaponRank, WeaponType};
+
+use serde::{Deserialize, Serialize};
+
+/// How often a skill activates.
+#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    /// The skill is always active.
+    Always,
+
+    /// The skill activates with a fixed chance (0-100).
+    Flat(u32),
+
+    /// The skill activates with a chance of the given percentage of one of
+    /// the user's stats, plus a flat bonus: Luna in the GBA games is 100% of
+    /// Skl, and Astra in Tellius is 50% of Skl.
+    Stat {
+        /// The stat the chance comes from.
+        stat: Stat,
+
+        /// The percentage of the stat that counts.
+        percent: u32,
+
+        /// The chance added on top.
+        bonus: u32,
+    },
+}
+
+impl Activation {
+    /// Returns the chance (0-100) that the skill activates for a user with
+    /// the given stats.
+    pub fn rate(&self, stats: &StatBlock) -> u32 {
+        let rate = match self {
+            Activation::Always => 100,
+            Activation::Flat(rate) => *rate,
+            Activation::Stat{stat, percent, bonus} => {
+                stats.get(*stat) * percent / 100 + bonus
+            }
+        };
+        rate.min(100)
+    }
+}
+
+/// A skill a unit can have. Every hook does nothing by default, so a skill
+/// only needs to fill in the ones it uses. Hooks that take a rate are given
+/// the skill's activation chance (0-100) for its user.
+pub trait Skill {
+    /// The skill's name.
+    fn name(&self) -> &str;
+
+    /// How often the skill activates.
+    fn activation(&self) -> Activation {
+        Activation::Always
+    }
+
+    /// Changes the user's side before combat, like skills that add hit,
+    /// crit, or damage.
+    fn modify_side(&self, _game: FEGame, side: CombatSide) -> CombatSide {
+        side
+    }
+
+    /// A proc that can replace the first strike of each of the user's attacks
+    /// with a flurry of strikes, like Astra.
+    fn strike_proc(&self, _rate: u32) -> Option<StrikeProc> {
+        None
+    }
+
+    /// A proc that can change the damage of the user's strikes that land,
+    /// like Luna.
+    fn dmg_proc(&self, _rate: u32) -> Option<DamageProc> {
+        None
+    }
+
+    /// A proc that can reduce the damage of strikes against the user, like
+    /// Pavise.
+    fn guard(&self, _rate: u32) -> Option<DefenseProc> {
+        None
+    }
+
+    /// The percentage of damage dealt that heals the user after each strike,
+    /// like Sol in games where it always heals.
+    fn drain_percent(&self) -> u32 {
+        0
+    }
+
+    /// The strikes the skill adds to each of the user's attacks. A skill that
+    /// makes every attack strike twice, like a brave weapon, adds one.
+    fn extra_strikes(&self) -> u32 {
+        0
+    }
+}
+
+/// Returns the given side with every one of the unit's skills' changes.
+pub fn modify_side(game: FEGame, unit: &dyn Unit,
+                   side: CombatSide) -> CombatSide {
+    unit.skills().into_iter()
+        .fold(side, |side, skill| skill.modify_side(game, side))
+}
+
+/// Returns the given stats with the unit's skills filled into the outcome
+/// engine's hooks. A side can only have one proc of each kind, so when two
+/// skills fill in the same hook, the one listed first wins, while extra
+/// strikes from different skills add up. Skills that can't activate are
+/// skipped.
+pub fn apply_skills(unit: &dyn Unit, stats: CombatStats) -> CombatStats {
+    let user_stats = unit.stats();
+    unit.skills().into_iter().fold(stats, |stats, skill| {
+        let rate = skill.activation().rate(&user_stats);
+        if rate == 0 {
+            return stats;
+        }
+        CombatStats{
+            strike_proc: stats.strike_proc.or_else(|| skill.strike_proc(rate)),
+            dmg_proc: stats.dmg_proc.or_else(|| skill.dmg_proc(rate)),
+            guard: stats.guard.or_else(|| skill.guard(rate)),
+            drain_percent: stats.drain_percent.max(skill.drain_percent()),
+            extra_strikes: stats.extra_strikes + skill.extra_strikes(),
+            ..stats
+        }
+    })
+}
+
+/// A unit with skills added on top of whatever it already has.
+pub struct SkilledUnit<U> {
+    /// The unit itself.
+    pub unit: U,
+
+    /// The skills added to the unit.
+    pub skills: Vec<Box<dyn Skill>>,
+}
+
+impl<U: Unit> Unit for SkilledUnit<U> {
+    fn hp(&self) -> u32 {
+        self.unit.hp()
+    }
+
+    fn class(&self) -> Option<&Class> {
+        self.unit.class()
+    }
+
+    fn unit_types(&self) -> &[UnitType] {
+        self.unit.unit_types()
+    }
+
+    fn stats(&self) -> StatBlock {
+        self.unit.stats()
+    }
+
+    fn skills(&self) -> Vec<&dyn Skill> {
+        let mut skills = self.unit.skills();
+        skills.extend(self.skills.iter().map(|x| x.as_ref()));
+        skills
+    }
+
+    fn weapon_rank(&self, weapon_type: WeaponType) -> Option<WeaponRank> {
+        self.unit.weapon_rank(weapon_type)
+    }
+
+    fn combat_side(&self, game: FEGame, weapon: &dyn Weapon,
+                   item: Option<&dyn Item>,
+                   enemy_damage: DamageType) -> CombatSide {
+        self.unit.combat_side(game, weapon, item, enemy_damage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_calc::{DamageEffect, Side};
+    use crate::unit::{calc_units, combat_stats, CombatContext, Combatant,
+                      GbaUnit};
+    use crate::weapon::Range;
+
+    /// Tellius's Astra: five strikes at half damage, Skl / 2% of the time.
+    struct Astra;
+
+    impl Skill for Astra {
+        fn name(&self) -> &str {
+            "Astra"
+        }
+
+        fn activation(&self) -> Activation {
+            Activation::Stat{stat: Stat::Skl, percent: 50, bonus: 0}
+        }
+
+        fn strike_proc(&self, rate: u32) -> Option<StrikeProc> {
+            Some(StrikeProc{rate, strikes: 5, dmg_percent: 50})
+        }
+    }
+
+    /// A passive skill adding 10 hit, and a Luna that always activates.
+    struct Focus;
+
+    impl Skill for Focus {
+        fn name(&self) -> &str {
+            "Focus"
+        }
+
+        fn modify_side(&self, _game: FEGame, side: CombatSide) -> CombatSide {
+            CombatSide{hit: side.hit + 10, ..side}
+        }
+
+        fn dmg_proc(&self, rate: u32) -> Option<DamageProc> {
+            Some(DamageProc{rate, effect: DamageEffect::IgnoreDef(100)})
+        }
+    }
+
+    /// A skill that makes every attack strike three times.
+    struct Triple;
+
+    impl Skill for Triple {
+        fn name(&self) -> &str {
+            "Triple"
+        }
+
+        fn extra_strikes(&self) -> u32 {
+            2
+        }
+    }
+
+    struct Sword;
+
+    impl Weapon for Sword {
+        fn might(&self) -> u32 { 5 }
+        fn hit(&self) -> u32 { 50 }
+        fn crit(&self) -> u32 { 0 }
+        fn weight(&self) -> u32 { 0 }
+        fn range(&self) -> Range { Range::melee() }
+        fn weapon_type(&self) -> WeaponType { WeaponType::Sword }
+    }
+
+    #[test]
+    fn test_skills() {
+        let hero = SkilledUnit{
+            unit: GbaUnit{
+                hp: 30,
+                stats: StatBlock::new(&[(Stat::Str, 10), (Stat::Skl, 30)]),
+                ..Default::default()
+            },
+            skills: vec!(Box::new(Astra), Box::new(Focus)),
+        };
+        let enemy = GbaUnit{hp: 30, ..Default::default()};
+        let atk = Combatant{unit: &hero, weapon: &Sword, item: None};
+        let def = Combatant{unit: &enemy, weapon: &Sword, item: None};
+        let (atk_stats, def_stats, _) = combat_stats(
+            FEGame::FE9, &atk, &def, &CombatContext::melee()
+        );
+        assert_eq!(atk_stats.strike_proc,
+                   Some(StrikeProc{rate: 15, strikes: 5, dmg_percent: 50}));
+        assert_eq!(atk_stats.dmg_proc.map(|x| x.rate), Some(100));
+        // 50 + 2 * 30 + 10, against 0 avoid
+        assert_eq!(atk_stats.hit, 100);
+        assert_eq!(def_stats.strike_proc, None);
+        // a skill that can't activate does nothing
+        let clumsy = SkilledUnit{
+            unit: GbaUnit::default(),
+            skills: vec!(Box::new(Astra)),
+        };
+        assert_eq!(apply_skills(&clumsy, CombatStats::default()),
+                   CombatStats::default());
+        // skills count in full reports too, and extra strikes stack on top
+        // of a brave weapon
+        let triple = SkilledUnit{
+            unit: GbaUnit{
+                hp: 30,
+                stats: StatBlock::new(&[(Stat::Str, 10), (Stat::Skl, 30)]),
+                ..Default::default()
+            },
+            skills: vec!(Box::new(Triple)),
+        };
+        let atk = Combatant{unit: &triple, weapon: &Sword, item: None};
+        let report = calc_units(FEGame::FE9, &atk, &def);
+        let atk_strikes = report.strikes.iter()
+            .filter(|x| x.striker == Side::Atk).count();
+        assert_eq!(atk_strikes, 3);
+        let brave = CombatStats{is_brave: true, ..Default::default()};
+        assert_eq!(apply_skills(&triple, brave).strikes_per_attack(), 4);
+    }
+}
@@ -0,0 +1,160 @@
+//! Unit stats across games. The games mostly share the same stats, but not
+//! quite: the GBA games fold Mag into Str, *Gaiden* has no Mag at all, Con is
+//! called Bld in some games and missing in others, and *Three Houses* adds
+//! Charm. `StatBlock` keys every game's stats by the same `growth::Stat`, so
+//! units, classes, and growths can share one representation.
+
+use crate::damage::DamageStats;
+use crate::fegame::{Era, FEGame};
+use crate::growth::Stat;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A unit's stats. Stats that aren't listed are 0. This serializes as a plain
+/// map from stat to value.
+#[derive(Default, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct StatBlock {
+    /// The value of each stat.
+    pub values: BTreeMap<Stat, u32>,
+}
+
+impl StatBlock {
+    /// Makes a stat block from the given stats.
+    pub fn new(values: &[(Stat, u32)]) -> StatBlock {
+        StatBlock{values: values.iter().cloned().collect()}
+    }
+
+    /// Returns the given stat, or 0 if it isn't listed.
+    pub fn get(&self, stat: Stat) -> u32 {
+        self.values.get(&stat).cloned().unwrap_or(0)
+    }
+
+    /// Sets the given stat.
+    pub fn set(&mut self, stat: Stat, value: u32) {
+        self.values.insert(stat, value);
+    }
+
+    /// HP.
+    pub fn hp(&self) -> u32 {
+        self.get(Stat::HP)
+    }
+
+    /// Strength.
+    pub fn str(&self) -> u32 {
+        self.get(Stat::Str)
+    }
+
+    /// Magic. Games that combine Str and Mag only list Str, so this falls back
+    /// to it when Mag isn't listed.
+    pub fn mag(&self) -> u32 {
+        self.values.get(&Stat::Mag).cloned().unwrap_or_else(|| self.str())
+    }
+
+    /// Skill, or Dexterity.
+    pub fn skl(&self) -> u32 {
+        self.get(Stat::Skl)
+    }
+
+    /// Speed.
+    pub fn spd(&self) -> u32 {
+        self.get(Stat::Spd)
+    }
+
+    /// Luck.
+    pub fn lck(&self) -> u32 {
+        self.get(Stat::Lck)
+    }
+
+    /// Defense.
+    pub fn def(&self) -> u32 {
+        self.get(Stat::Def)
+    }
+
+    /// Resistance.
+    pub fn res(&self) -> u32 {
+        self.get(Stat::Res)
+    }
+
+    /// Constitution, or Build.
+    pub fn con(&self) -> u32 {
+        self.get(Stat::Con)
+    }
+
+    /// Charm.
+    pub fn cha(&self) -> u32 {
+        self.get(Stat::Cha)
+    }
+
+    /// Returns the stats that matter for damage.
+    pub fn damage_stats(&self) -> DamageStats {
+        DamageStats{
+            str: self.str(),
+            mag: self.mag(),
+            def: self.def(),
+            res: self.res(),
+        }
+    }
+}
+
+impl FEGame {
+    /// Returns the stats units have in this game, in the order the game shows
+    /// them. Movement and weapon levels aren't included.
+    pub fn stats(&self) -> Vec<Stat> {
+        let has_mag = !matches!(self, FEGame::FE1 | FEGame::FE2 | FEGame::FE3)
+            && self.era() != Era::Gba;
+        let has_con = matches!(self, FEGame::FE5 | FEGame::FE17)
+            || matches!(self.era(), Era::Gba | Era::Tellius);
+        let mut stats = vec!(Stat::HP, Stat::Str);
+        if has_mag {
+            stats.push(Stat::Mag);
+        }
+        stats.extend(&[Stat::Skl, Stat::Spd, Stat::Lck, Stat::Def, Stat::Res]);
+        if has_con {
+            stats.push(Stat::Con);
+        }
+        if *self == FEGame::FE16 {
+            stats.push(Stat::Cha);
+        }
+        stats
+    }
+
+    /// Returns what this game calls the given stat.
+    pub fn stat_name(&self, stat: Stat) -> &'static str {
+        match (self, stat) {
+            (FEGame::FE2, Stat::Str) => "Atk",
+            (FEGame::FE16, Stat::Skl) | (FEGame::FE17, Stat::Skl) => "Dex",
+            (FEGame::FE5, Stat::Con) | (FEGame::FE17, Stat::Con) => "Bld",
+            (_, Stat::HP) => "HP",
+            (_, Stat::Str) => "Str",
+            (_, Stat::Mag) => "Mag",
+            (_, Stat::Skl) => "Skl",
+            (_, Stat::Spd) => "Spd",
+            (_, Stat::Lck) => "Lck",
+            (_, Stat::Def) => "Def",
+            (_, Stat::Res) => "Res",
+            (_, Stat::Con) => "Con",
+            (_, Stat::Cha) => "Cha",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stat_block() {
+        let mage = StatBlock::new(&[(Stat::Str, 9), (Stat::Res, 6)]);
+        // GBA magic uses Str
+        assert_eq!(mage.mag(), 9);
+        assert_eq!(mage.damage_stats().res, 6);
+        assert_eq!(mage.con(), 0);
+        assert!(!FEGame::FE7.stats().contains(&Stat::Mag));
+        assert!(FEGame::FE7.stats().contains(&Stat::Con));
+        assert_eq!(FEGame::FE16.stats().last(), Some(&Stat::Cha));
+        assert_eq!(FEGame::FE17.stat_name(Stat::Con), "Bld");
+        assert_eq!(FEGame::FE9.stat_name(Stat::Con), "Con");
+    }
+}
@@ -0,0 +1,233 @@
+//! Which of each game's mechanics the crate actually models. Rules get added a
+//! game at a time, so some games are covered better than others; this lets
+//! tools built on the crate show accuracy disclaimers from the same source of
+//! truth instead of keeping their own lists.
+
+use crate::affinity::Affinity;
+use crate::fegame::{Era, FEGame};
+use crate::weapon::{WeaponRank, WeaponType};
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A game mechanic that affects the crate's calculations.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Mechanic {
+    /// How listed hit rates turn into true hit rates.
+    HitRates,
+
+    /// How critical damage is computed.
+    CritDamage,
+
+    /// Where crit rates come from and how crit avoid reduces them.
+    CritRates,
+
+    /// How hit and avoid come from a unit's stats.
+    HitAvoidFormulas,
+
+    /// How much faster a unit needs to be to attack twice.
+    Doubling,
+
+    /// How weapon weight lowers attack speed.
+    AttackSpeed,
+
+    /// How the combat preview rounds and clamps the raw formulas.
+    PreviewRounding,
+
+    /// The weapon triangle's hit and damage bonuses.
+    WeaponTriangle,
+
+    /// How much effective weapons multiply their might by.
+    Effectiveness,
+
+    /// The combat bonuses from supports.
+    SupportBonuses,
+
+    /// The avoid, defense, and healing from terrain.
+    Terrain,
+
+    /// The highest value each stat can reach.
+    StatCaps,
+
+    /// The bonuses units get from their weapon rank.
+    RankBonuses,
+
+    /// The EXP gained from combat.
+    CombatExp,
+
+    /// The accuracy of status staves like Sleep and Berserk.
+    StaffAccuracy,
+
+    /// How much healing staves heal and how far ranged staves reach.
+    StaffHealing,
+
+    /// How growth rates turn into stat gains on level up.
+    LevelUps,
+}
+
+/// How well the crate models a mechanic in a game.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum SupportStatus {
+    /// The game's rules are implemented.
+    Implemented,
+
+    /// There's an implementation, but it uses rules from other games or leaves
+    /// out known special cases, so results may be off.
+    Stubbed,
+
+    /// The crate doesn't model this at all, so it has to be accounted for by
+    /// the caller.
+    Unsupported,
+}
+
+impl FEGame {
+    /// Returns how well the crate models the given mechanic in this game.
+    pub fn support(&self, mechanic: Mechanic) -> SupportStatus {
+        match mechanic {
+            // the Switch RN systems come from community testing that hasn't
+            // been checked against the games' code
+            Mechanic::HitRates => match self {
+                FEGame::FE16 | FEGame::FE17 => SupportStatus::Stubbed,
+                _ => SupportStatus::Implemented,
+            },
+            Mechanic::CritDamage => SupportStatus::Implemented,
+            // only the GBA games have been checked to floor fractions
+            Mechanic::PreviewRounding => match self {
+                FEGame::Custom(_) => SupportStatus::Implemented,
+                _ if self.era() == Era::Gba => SupportStatus::Implemented,
+                _ => SupportStatus::Stubbed,
+            },
+            // FE1 crits only come from weapons, which the caller has to
+            // account for
+            Mechanic::CritRates => match self {
+                FEGame::FE1 => SupportStatus::Stubbed,
+                _ => SupportStatus::Implemented,
+            },
+            // the Archanea games' weapon-rank-locked doubling and brave quirks
+            // aren't modeled
+            Mechanic::Doubling => match self {
+                FEGame::FE1 | FEGame::FE3 => SupportStatus::Stubbed,
+                _ => SupportStatus::Implemented,
+            },
+            Mechanic::HitAvoidFormulas => match self.hit_rate(0, 0, 0) {
+                Some(_) => SupportStatus::Implemented,
+                None => SupportStatus::Unsupported,
+            },
+            // these follow the stat the games use, but the exact formulas
+            // haven't been checked
+            Mechanic::AttackSpeed => match self {
+                FEGame::FE1 | FEGame::FE3 | FEGame::FE4 | FEGame::FE11
+                    | FEGame::FE12 | FEGame::FE14 => SupportStatus::Stubbed,
+                _ => SupportStatus::Implemented,
+            },
+            Mechanic::WeaponTriangle => match self {
+                FEGame::FE9 | FEGame::FE10 | FEGame::FE14 => {
+                    SupportStatus::Stubbed
+                }
+                FEGame::FE4 | FEGame::FE13 => SupportStatus::Unsupported,
+                _ => SupportStatus::Implemented,
+            },
+            Mechanic::Effectiveness => match self {
+                FEGame::FE9 | FEGame::FE10 => SupportStatus::Stubbed,
+                _ => SupportStatus::Implemented,
+            },
+            // FE6's bonuses, Tellius affinities, 3DS support ranks, and
+            // Three Houses adjacency bonuses aren't modeled
+            Mechanic::SupportBonuses => {
+                match self.support_bonus(Affinity::Fire, &[]) {
+                    Some(_) => SupportStatus::Implemented,
+                    None => SupportStatus::Unsupported,
+                }
+            }
+            Mechanic::Terrain => match self {
+                FEGame::FE7 | FEGame::FE8 => SupportStatus::Implemented,
+                _ => SupportStatus::Unsupported,
+            },
+            // the Archanea caps haven't been checked, and class and character
+            // caps need data from the caller
+            Mechanic::StatCaps => match self {
+                FEGame::FE1 | FEGame::FE3 | FEGame::FE11 => {
+                    SupportStatus::Stubbed
+                }
+                _ if self.era() == Era::Gba => SupportStatus::Implemented,
+                _ => SupportStatus::Unsupported,
+            },
+            Mechanic::RankBonuses => match self.era() {
+                Era::Tellius => SupportStatus::Stubbed,
+                _ => match self.rank_bonus(WeaponType::Sword, WeaponRank::S) {
+                    Some(_) => SupportStatus::Implemented,
+                    None => SupportStatus::Unsupported,
+                },
+            },
+            Mechanic::CombatExp => match self.era() {
+                Era::Gba => SupportStatus::Implemented,
+                _ => SupportStatus::Unsupported,
+            },
+            // the GBA formula hasn't been checked against the games yet
+            Mechanic::StaffAccuracy => match self.staff_formula() {
+                Some(_) => SupportStatus::Stubbed,
+                None => SupportStatus::Unsupported,
+            },
+            // the GBA ranges haven't been checked, and Fortify and other
+            // staves aren't covered
+            Mechanic::StaffHealing => match self.era() {
+                Era::Gba => SupportStatus::Stubbed,
+                _ => SupportStatus::Unsupported,
+            },
+            // the later games' rerolls and dynamic growths haven't been
+            // checked, so they fall back to plain random growths
+            Mechanic::LevelUps => match self.era() {
+                Era::Kaga | Era::Gba | Era::Tellius => {
+                    SupportStatus::Implemented
+                }
+                _ => SupportStatus::Stubbed,
+            },
+        }
+    }
+
+    /// Returns the support status of every mechanic in this game.
+    pub fn support_table(&self) -> Vec<(Mechanic, SupportStatus)> {
+        Mechanic::iter().map(|x| (x, self.support(x))).collect()
+    }
+
+    /// Returns the mechanics that aren't fully implemented in this game, for
+    /// showing as a disclaimer.
+    pub fn caveats(&self) -> Vec<(Mechanic, SupportStatus)> {
+        self.support_table().into_iter()
+            .filter(|(_, status)| *status != SupportStatus::Implemented)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_support() {
+        assert_eq!(FEGame::FE7.support_table().len(), Mechanic::iter().count());
+        assert_eq!(FEGame::FE7.caveats(), vec!(
+            (Mechanic::StaffAccuracy, SupportStatus::Stubbed),
+            (Mechanic::StaffHealing, SupportStatus::Stubbed),
+        ));
+        assert_eq!(FEGame::FE5.support(Mechanic::Doubling),
+                   SupportStatus::Implemented);
+        assert_eq!(FEGame::FE3.support(Mechanic::Doubling),
+                   SupportStatus::Stubbed);
+        assert_eq!(FEGame::FE1.support(Mechanic::CritRates),
+                   SupportStatus::Stubbed);
+        assert_eq!(FEGame::FE14.support(Mechanic::CombatExp),
+                   SupportStatus::Unsupported);
+        assert_eq!(FEGame::FE13.support(Mechanic::LevelUps),
+                   SupportStatus::Stubbed);
+        assert_eq!(FEGame::FE10.support(Mechanic::PreviewRounding),
+                   SupportStatus::Stubbed);
+        for game in &[FEGame::FE6, FEGame::FE9, FEGame::FE13, FEGame::FE16] {
+            assert_eq!(game.support(Mechanic::SupportBonuses),
+                       SupportStatus::Unsupported);
+        }
+    }
+}
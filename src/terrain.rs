@@ -0,0 +1,92 @@
+//! Terrain bonuses: units standing on forests, forts, thrones, and the like get
+//! extra avoid and defense, and some terrain heals at the start of the turn.
+//! These change enemy-phase survival odds a lot, so they need to be added to
+//! the defender's stats before calculating.
+
+use crate::calc::CombatSide;
+use crate::fegame::FEGame;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A kind of terrain a unit can stand on.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Deserialize, Serialize)]
+pub enum Terrain {
+    Plain,
+    Forest,
+    Mountain,
+    Peak,
+    Fort,
+    Gate,
+    Throne,
+    Pillar,
+    Village,
+    Sand,
+}
+
+/// The bonuses a unit gets from the terrain it's standing on.
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize,
+         Serialize)]
+pub struct TerrainBonus {
+    /// The bonus to avoid.
+    pub avoid: u32,
+
+    /// The bonus to defense.
+    pub prt: u32,
+
+    /// The percentage of max HP healed at the start of the unit's turn.
+    pub heal_percent: u32,
+}
+
+impl TerrainBonus {
+    /// Returns the side with these bonuses added.
+    pub fn apply(&self, side: CombatSide) -> CombatSide {
+        CombatSide{
+            avoid: side.avoid + self.avoid,
+            prt: side.prt + self.prt,
+            ..side
+        }
+    }
+}
+
+impl FEGame {
+    /// Returns the bonuses for standing on the given terrain in this game, or
+    /// `None` if the crate doesn't know this game's terrain. Only *The Blazing
+    /// Blade* and *The Sacred Stones* are covered so far.
+    pub fn terrain_bonus(&self, terrain: Terrain) -> Option<TerrainBonus> {
+        if !matches!(self, FEGame::FE7 | FEGame::FE8) {
+            return None;
+        }
+        let (avoid, prt, heal_percent) = match terrain {
+            Terrain::Plain => (0, 0, 0),
+            Terrain::Forest => (20, 1, 0),
+            Terrain::Mountain => (30, 1, 0),
+            Terrain::Peak => (40, 2, 0),
+            Terrain::Fort => (20, 2, 20),
+            Terrain::Gate => (20, 3, 10),
+            Terrain::Throne => (30, 3, 10),
+            Terrain::Pillar => (20, 1, 0),
+            Terrain::Village => (10, 0, 0),
+            Terrain::Sand => (5, 0, 0),
+        };
+        Some(TerrainBonus{avoid, prt, heal_percent})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terrain() {
+        let throne = FEGame::FE7.terrain_bonus(Terrain::Throne).unwrap();
+        assert_eq!(throne, TerrainBonus{avoid: 30, prt: 3, heal_percent: 10});
+        let boss = throne.apply(CombatSide{avoid: 15, prt: 12,
+                                           ..Default::default()});
+        assert_eq!((boss.avoid, boss.prt), (45, 15));
+        assert_eq!(FEGame::FE8.terrain_bonus(Terrain::Plain),
+                   Some(TerrainBonus::default()));
+        assert_eq!(FEGame::FE13.terrain_bonus(Terrain::Forest), None);
+    }
+}
@@ -0,0 +1,352 @@
+//! The weapon triangle: swords beat axes, axes beat lances, and lances beat
+//! swords, with the winner getting extra hit and damage and the loser losing
+//! the same amount. The GBA games add a second triangle for magic, and *Fates*
+//! folds every weapon into one bigger triangle. Some games have no triangle at
+//! all.
+//!
+//! This also covers the other bonus weapons get from ranks: some games give
+//! units extra hit or crit once they master a weapon type.
+
+use crate::calc::CombatSide;
+use crate::fegame::{Era, FEGame};
+use crate::weapon::{Weapon, WeaponRank, WeaponType};
+
+use serde::{Deserialize, Serialize};
+
+/// The hit and damage a weapon gets from the weapon triangle. Both are negative
+/// at a disadvantage.
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize,
+         Deserialize)]
+pub struct TriangleBonus {
+    /// The change in hit.
+    pub hit: i32,
+
+    /// The change in damage.
+    pub dmg: i32,
+}
+
+impl TriangleBonus {
+    /// Returns the side with this bonus added. Hit and Atk can't go below 0.
+    pub fn apply(&self, side: CombatSide) -> CombatSide {
+        let add = |x: u32, bonus: i32| (x as i32 + bonus).max(0) as u32;
+        CombatSide{
+            hit: add(side.hit, self.hit),
+            atk: add(side.atk, self.dmg),
+            ..side
+        }
+    }
+}
+
+/// The bonus a unit gets from their rank with the weapon they're using.
+#[derive(Default, Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize,
+         Deserialize)]
+pub struct RankBonus {
+    /// The bonus to hit.
+    pub hit: u32,
+
+    /// The bonus to crit.
+    pub crit: u32,
+
+    /// The bonus to Atk.
+    pub atk: u32,
+}
+
+impl RankBonus {
+    /// Returns the side with these bonuses added.
+    pub fn apply(&self, side: CombatSide) -> CombatSide {
+        CombatSide{
+            hit: side.hit + self.hit,
+            crit: side.crit + self.crit,
+            atk: side.atk + self.atk,
+            ..side
+        }
+    }
+}
+
+/// What the crate knows about a game's weapon triangle.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum Triangle {
+    /// The game has no weapon triangle.
+    Absent,
+
+    /// The kind of triangle the game uses and the bonus at an advantage.
+    Known(TriangleKind, TriangleBonus),
+
+    /// The game has a triangle, but the crate doesn't know its numbers.
+    Unknown,
+}
+
+/// Which weapons are part of a game's triangle.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum TriangleKind {
+    /// Only swords, lances, and axes.
+    Physical,
+
+    /// The physical triangle, plus anima beating light, light beating dark,
+    /// and dark beating anima.
+    WithMagic,
+
+    /// Swords and tomes beat axes and bows, which beat lances and daggers,
+    /// which beat swords and tomes.
+    Fates,
+}
+
+impl TriangleKind {
+    /// Returns which triangle a weapon is in and its place in that triangle,
+    /// where each place beats the next one. Returns `None` for weapons outside
+    /// the triangle.
+    fn place(&self, weapon: WeaponType) -> Option<(u32, u32)> {
+        match (self, weapon) {
+            (_, WeaponType::Sword) => Some((0, 0)),
+            (_, WeaponType::Axe) => Some((0, 1)),
+            (_, WeaponType::Lance) => Some((0, 2)),
+            (TriangleKind::WithMagic, WeaponType::Anima) => Some((1, 0)),
+            (TriangleKind::WithMagic, WeaponType::Light) => Some((1, 1)),
+            (TriangleKind::WithMagic, WeaponType::Dark) => Some((1, 2)),
+            (TriangleKind::Fates, WeaponType::Anima)
+                | (TriangleKind::Fates, WeaponType::Light)
+                | (TriangleKind::Fates, WeaponType::Dark) => Some((0, 0)),
+            (TriangleKind::Fates, WeaponType::Bow) => Some((0, 1)),
+            (TriangleKind::Fates, WeaponType::Dagger) => Some((0, 2)),
+            _ => None,
+        }
+    }
+
+    /// Returns 1 if the attacker's weapon beats the defender's, -1 if it loses,
+    /// and 0 otherwise.
+    fn advantage(&self, attacker: WeaponType, defender: WeaponType) -> i32 {
+        match (self.place(attacker), self.place(defender)) {
+            (Some((atk_tri, atk)), Some((def_tri, def)))
+                if atk_tri == def_tri => {
+                if (atk + 1) % 3 == def {
+                    1
+                } else if (def + 1) % 3 == atk {
+                    -1
+                } else {
+                    0
+                }
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl FEGame {
+    /// Returns what the crate knows about this game's triangle for an attacker
+    /// with the given rank in their weapon.
+    fn triangle(&self, weapon_rank: WeaponRank) -> Triangle {
+        match self {
+            FEGame::FE1 | FEGame::FE2 | FEGame::FE3 | FEGame::FE5
+                | FEGame::FE11 | FEGame::FE12 | FEGame::FE15 | FEGame::FE16
+                | FEGame::FE17 => Triangle::Absent,
+            _ if self.era() == Era::Gba => Triangle::Known(
+                TriangleKind::WithMagic, TriangleBonus{hit: 15, dmg: 1},
+            ),
+            // the Tellius bonus grows with the attacker's rank, but the
+            // numbers haven't been checked against the games
+            _ if self.era() == Era::Tellius => Triangle::Known(
+                TriangleKind::Physical, match weapon_rank {
+                    WeaponRank::E | WeaponRank::D => {
+                        TriangleBonus{hit: 5, dmg: 0}
+                    }
+                    WeaponRank::C | WeaponRank::B => {
+                        TriangleBonus{hit: 10, dmg: 1}
+                    }
+                    WeaponRank::A | WeaponRank::S => {
+                        TriangleBonus{hit: 15, dmg: 1}
+                    }
+                },
+            ),
+            // Fates doesn't scale the triangle with rank, but the numbers
+            // haven't been checked
+            FEGame::FE14 => Triangle::Known(
+                TriangleKind::Fates, TriangleBonus{hit: 10, dmg: 1},
+            ),
+            // the Genealogy and Awakening triangles
+            _ => Triangle::Unknown,
+        }
+    }
+
+    /// Returns whether this game has a weapon triangle that changes hit or
+    /// damage, even if the crate doesn't know by how much.
+    pub fn has_triangle(&self) -> bool {
+        self.triangle(WeaponRank::E) != Triangle::Absent
+    }
+
+    /// Returns the bonus a unit gets for using a weapon of the given type at
+    /// the given rank, or `None` if the crate doesn't know this game's rank
+    /// bonuses. Most games don't have any.
+    pub fn rank_bonus(&self, weapon_type: WeaponType,
+                      weapon_rank: WeaponRank) -> Option<RankBonus> {
+        match self {
+            // Tellius rewards S rank, with Radiant Dawn's bonus depending on
+            // the weapon type, but this hasn't been checked against the games
+            FEGame::FE9 => Some(match weapon_rank {
+                WeaponRank::S => RankBonus{hit: 5, crit: 5, atk: 0},
+                _ => RankBonus::default(),
+            }),
+            FEGame::FE10 => Some(match (weapon_rank, weapon_type) {
+                (_, WeaponType::Staff) => RankBonus::default(),
+                (WeaponRank::S, WeaponType::Sword)
+                    | (WeaponRank::S, WeaponType::Dagger) => {
+                    RankBonus{hit: 0, crit: 5, atk: 1}
+                }
+                (WeaponRank::S, WeaponType::Lance) => {
+                    RankBonus{hit: 5, crit: 0, atk: 1}
+                }
+                (WeaponRank::S, WeaponType::Axe) => {
+                    RankBonus{hit: 10, crit: 0, atk: 0}
+                }
+                (WeaponRank::S, _) => RankBonus{hit: 5, crit: 5, atk: 0},
+                _ => RankBonus::default(),
+            }),
+            FEGame::FE6 | FEGame::FE7 | FEGame::FE8 | FEGame::FE13
+                | FEGame::FE15 => Some(RankBonus::default()),
+            _ => None,
+        }
+    }
+
+    /// Returns the weapon triangle bonus for an attacker using the first
+    /// weapon type against a defender using the second, or `None` if the crate
+    /// doesn't know this game's triangle. Games without a triangle always give
+    /// no bonus. The weapon rank is the attacker's, which matters in games
+    /// like the Tellius ones where the bonus grows with rank.
+    pub fn triangle_bonus(&self, attacker_type: WeaponType,
+                          defender_type: WeaponType,
+                          weapon_rank: WeaponRank) -> Option<TriangleBonus> {
+        let (kind, bonus) = match self.triangle(weapon_rank) {
+            Triangle::Known(kind, bonus) => (kind, bonus),
+            Triangle::Absent => return Some(TriangleBonus::default()),
+            Triangle::Unknown => return None,
+        };
+        let advantage = kind.advantage(attacker_type, defender_type);
+        Some(TriangleBonus{
+            hit: advantage * bonus.hit,
+            dmg: advantage * bonus.dmg,
+        })
+    }
+
+    /// Like `triangle_bonus`, but for reavers: a reaver on either side
+    /// reverses the triangle and doubles its effect. Two reavers reverse it
+    /// back, still doubled.
+    pub fn reaver_triangle_bonus(&self, attacker_type: WeaponType,
+                                 attacker_reaver: bool,
+                                 defender_type: WeaponType,
+                                 defender_reaver: bool,
+                                 weapon_rank: WeaponRank)
+                                 -> Option<TriangleBonus> {
+        let bonus = self.triangle_bonus(attacker_type, defender_type,
+                                        weapon_rank)?;
+        let scale = match (attacker_reaver, defender_reaver) {
+            (false, false) => 1,
+            (true, true) => 2,
+            _ => -2,
+        };
+        Some(TriangleBonus{hit: scale * bonus.hit, dmg: scale * bonus.dmg})
+    }
+
+    /// Returns the triangle bonus for an attacker using the first weapon
+    /// against a defender using the second, including reavers, or `None` as
+    /// in `triangle_bonus`.
+    pub fn weapon_triangle_bonus(&self, attacker: &dyn Weapon,
+                                 defender: &dyn Weapon,
+                                 weapon_rank: WeaponRank)
+                                 -> Option<TriangleBonus> {
+        self.reaver_triangle_bonus(attacker.weapon_type(),
+                                   attacker.reverses_triangle(),
+                                   defender.weapon_type(),
+                                   defender.reverses_triangle(), weapon_rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_triangle() {
+        let rank = WeaponRank::C;
+        let win = TriangleBonus{hit: 15, dmg: 1};
+        let lose = TriangleBonus{hit: -15, dmg: -1};
+        let even = TriangleBonus::default();
+        let gba = FEGame::FE7;
+        assert_eq!(gba.triangle_bonus(WeaponType::Sword, WeaponType::Axe, rank),
+                   Some(win));
+        assert_eq!(gba.triangle_bonus(WeaponType::Sword, WeaponType::Lance,
+                                      rank), Some(lose));
+        assert_eq!(gba.triangle_bonus(WeaponType::Dark, WeaponType::Anima,
+                                      rank), Some(win));
+        assert_eq!(gba.triangle_bonus(WeaponType::Anima, WeaponType::Axe, rank),
+                   Some(even));
+        assert_eq!(gba.triangle_bonus(WeaponType::Bow, WeaponType::Lance, rank),
+                   Some(even));
+        // bows and tomes join the triangle in Fates
+        let fates = FEGame::FE14;
+        assert_eq!(fates.triangle_bonus(WeaponType::Bow, WeaponType::Dagger,
+                                        rank).unwrap().hit, 10);
+        assert_eq!(fates.triangle_bonus(WeaponType::Light, WeaponType::Bow,
+                                        rank).unwrap().dmg, 1);
+        assert_eq!(FEGame::FE16.triangle_bonus(WeaponType::Sword,
+                                               WeaponType::Axe, rank),
+                   Some(even));
+        assert_eq!(FEGame::FE13.triangle_bonus(WeaponType::Sword,
+                                               WeaponType::Axe, rank), None);
+        // Tellius grows the bonus with the attacker's rank
+        let tellius = FEGame::FE10;
+        assert_eq!(tellius.triangle_bonus(WeaponType::Lance, WeaponType::Sword,
+                                          WeaponRank::E),
+                   Some(TriangleBonus{hit: 5, dmg: 0}));
+        assert_eq!(tellius.triangle_bonus(WeaponType::Lance, WeaponType::Sword,
+                                          WeaponRank::S),
+                   Some(TriangleBonus{hit: 15, dmg: 1}));
+        assert!(!FEGame::FE16.has_triangle());
+        // the DS remakes drop the triangle, but Genealogy's is just unknown
+        assert!(!FEGame::FE11.has_triangle());
+        assert_eq!(FEGame::FE11.triangle_bonus(WeaponType::Sword,
+                                               WeaponType::Axe, rank),
+                   Some(even));
+        assert!(FEGame::FE4.has_triangle());
+    }
+
+    #[test]
+    fn test_reavers() {
+        let rank = WeaponRank::C;
+        let gba = FEGame::FE8;
+        // a Lancereaver turns a losing matchup against axes into double the
+        // usual advantage
+        assert_eq!(gba.reaver_triangle_bonus(WeaponType::Lance, true,
+                                             WeaponType::Axe, false, rank),
+                   Some(TriangleBonus{hit: 30, dmg: 2}));
+        // and a Swordreaver's wielder loses badly to an Axe user
+        assert_eq!(gba.reaver_triangle_bonus(WeaponType::Axe, false,
+                                             WeaponType::Sword, true, rank),
+                   Some(TriangleBonus{hit: 30, dmg: 2}));
+        assert_eq!(gba.reaver_triangle_bonus(WeaponType::Sword, true,
+                                             WeaponType::Axe, true, rank),
+                   Some(TriangleBonus{hit: 30, dmg: 2}));
+        let bonus = TriangleBonus{hit: -30, dmg: -2};
+        let side = CombatSide{hit: 80, atk: 1, ..Default::default()};
+        let side = bonus.apply(side);
+        assert_eq!((side.hit, side.atk), (50, 0));
+    }
+
+    #[test]
+    fn test_rank_bonus() {
+        let s_rank = FEGame::FE9.rank_bonus(WeaponType::Sword, WeaponRank::S)
+            .unwrap();
+        assert_eq!(s_rank, RankBonus{hit: 5, crit: 5, atk: 0});
+        assert_eq!(s_rank.apply(CombatSide{hit: 90, ..Default::default()}).hit,
+                   95);
+        assert_eq!(FEGame::FE10.rank_bonus(WeaponType::Sword, WeaponRank::A),
+                   Some(RankBonus::default()));
+        // Radiant Dawn's bonus depends on the weapon type
+        assert_eq!(FEGame::FE10.rank_bonus(WeaponType::Axe, WeaponRank::S),
+                   Some(RankBonus{hit: 10, crit: 0, atk: 0}));
+        assert_eq!(FEGame::FE10.rank_bonus(WeaponType::Staff, WeaponRank::S),
+                   Some(RankBonus::default()));
+        assert_eq!(FEGame::FE7.rank_bonus(WeaponType::Axe, WeaponRank::S),
+                   Some(RankBonus::default()));
+        assert_eq!(FEGame::FE14.rank_bonus(WeaponType::Axe, WeaponRank::S),
+                   None);
+    }
+}
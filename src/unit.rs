@@ -3,13 +3,486 @@
 //! different things, this is a trait that has to be implemented specifically
 //! for different games.
 
-use crate::weapon::{Weapon, Item};
+use crate::calc::{strike_stats, CombatSide};
+use crate::damage::DamageType;
+use crate::fegame::{CritAvoid, FEGame};
+use crate::growth::{Class, Stat};
+use crate::report::CombatReport;
+use crate::simple_calc::{CombatStats, SpeedDiff};
+use crate::skill::{apply_skills, modify_side, Skill};
+use crate::stats::StatBlock;
+use crate::terrain::Terrain;
+use crate::weapon::{Item, Weapon, WeaponRank, WeaponType};
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+
+/// A kind of unit that some weapons are effective against.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display, EnumString, EnumIter,
+         Serialize, Deserialize)]
+pub enum UnitType {
+    /// Pegasus, wyvern, and other flying units, weak to bows and wind magic.
+    Flier,
+
+    /// Knights and generals, weak to armorslayers and hammers.
+    Armored,
+
+    /// Mounted units, weak to horseslayers.
+    Cavalry,
+
+    /// Dragons and manaketes, weak to wyrmslayers.
+    Dragon,
+
+    /// Monsters, weak to holy weapons.
+    Monster,
+}
 
 /// A unit in Fire Emblem that can attack and defend.
-pub trait Unit: Sized {
-    /// Attacks the target with the given weapon, updating both this unit and
-    /// the target.
-    fn attack(&mut self, enemy: &mut Self,
-              atk_weapon: dyn Weapon<Self>, def_weapon: dyn Weapon<Self>,
-              atk_item: dyn Item<Self>, def_item: dyn Item<Self>);
+pub trait Unit {
+    /// The unit's current HP.
+    fn hp(&self) -> u32;
+
+    /// The unit's class, if it's known.
+    fn class(&self) -> Option<&Class> {
+        None
+    }
+
+    /// The kinds of unit this is, for effective weapons. By default, these
+    /// come from the unit's class, and units without one have none.
+    fn unit_types(&self) -> &[UnitType] {
+        self.class().map_or(&[], |x| &x.unit_types)
+    }
+
+    /// The unit's stats, for skills whose activation depends on them. Units
+    /// that don't give them have all 0s.
+    fn stats(&self) -> StatBlock {
+        StatBlock::default()
+    }
+
+    /// The unit's skills, in order of priority.
+    fn skills(&self) -> Vec<&dyn Skill> {
+        vec!()
+    }
+
+    /// The unit's rank with the given type of weapon, if it's known. This is
+    /// the rank the unit has reached, not the highest their class allows.
+    fn weapon_rank(&self, _weapon_type: WeaponType) -> Option<WeaponRank> {
+        None
+    }
+
+    /// Returns the side of combat this unit presents in the given game when
+    /// using the given weapon and holding the given item, against an enemy
+    /// whose weapon deals the given kind of damage.
+    fn combat_side(&self, game: FEGame, weapon: &dyn Weapon,
+                   item: Option<&dyn Item>,
+                   enemy_damage: DamageType) -> CombatSide;
+}
+
+/// A unit from the GBA games, which share the same stats: Str covers magic, and
+/// Con offsets weapon weight. The stats here are the unit's own, and their
+/// class's bases are added on top, so leave the class's bases empty when giving
+/// the totals shown in game.
+#[derive(Default, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub struct GbaUnit {
+    /// Current HP.
+    pub hp: u32,
+
+    /// The unit's own stats, with Str standing in for magic. Magic, Charm,
+    /// and other stats the GBA games don't have are ignored. The unit's HP
+    /// comes from `hp` instead.
+    pub stats: StatBlock,
+
+    /// The kinds of unit this is, like fliers or cavalry. If this is empty,
+    /// the class's are used instead.
+    pub unit_types: Vec<UnitType>,
+
+    /// The unit's class.
+    pub class: Option<Class>,
+
+    /// The unit's rank with each type of weapon they've used.
+    #[serde(default)]
+    pub weapon_ranks: BTreeMap<WeaponType, WeaponRank>,
+}
+
+impl Unit for GbaUnit {
+    fn hp(&self) -> u32 {
+        self.hp
+    }
+
+    fn class(&self) -> Option<&Class> {
+        self.class.as_ref()
+    }
+
+    /// The unit's own stats with their class's bases added. HP is the unit's
+    /// current HP, so class HP bases don't change it.
+    fn stats(&self) -> StatBlock {
+        let mut stats = match &self.class {
+            Some(class) => class.add_bases(&self.stats),
+            None => self.stats.clone(),
+        };
+        stats.set(Stat::HP, self.hp);
+        stats
+    }
+
+    fn weapon_rank(&self, weapon_type: WeaponType) -> Option<WeaponRank> {
+        self.weapon_ranks.get(&weapon_type).copied()
+    }
+
+    fn unit_types(&self) -> &[UnitType] {
+        match &self.class {
+            Some(class) if self.unit_types.is_empty() => &class.unit_types,
+            _ => &self.unit_types,
+        }
+    }
+
+    /// Uses the GBA formulas, with hit and avoid falling back to them in games
+    /// where the crate doesn't know its own. Class crit bonuses, like a
+    /// Swordmaster's, aren't included.
+    fn combat_side(&self, game: FEGame, weapon: &dyn Weapon,
+                   item: Option<&dyn Item>,
+                   enemy_damage: DamageType) -> CombatSide {
+        let stats = self.stats();
+        let attack_speed = game.attack_speed(stats.spd(), weapon.weight(),
+                                             stats.con());
+        let hit = game.hit_rate(stats.skl(), stats.lck(), weapon.hit())
+            .unwrap_or(weapon.hit() + 2 * stats.skl() + stats.lck() / 2);
+        let avoid = game.avoid(attack_speed, stats.lck())
+            .unwrap_or(2 * attack_speed + stats.lck());
+        let prt = match enemy_damage {
+            DamageType::Physical => stats.def(),
+            DamageType::Magical | DamageType::Hybrid => stats.res(),
+        };
+        let dodge = match item.and_then(|x| x.crit_avoid()) {
+            Some(CritAvoid::Negate) => u32::MAX,
+            Some(CritAvoid::Bonus(bonus)) => stats.lck() + bonus,
+            None => stats.lck(),
+        };
+        CombatSide{
+            hp: self.hp,
+            atk: stats.str() + weapon.might(),
+            prt,
+            hit,
+            avoid,
+            crit: weapon.crit() + stats.skl() / 2,
+            dodge,
+            attack_speed,
+            is_brave: weapon.is_brave(),
+            has_pursuit: false,
+        }
+    }
+}
+
+/// A unit ready for combat: the unit, the weapon they're using, and the item
+/// they're holding.
+#[derive(Copy, Clone)]
+pub struct Combatant<'a> {
+    /// The unit fighting.
+    pub unit: &'a dyn Unit,
+
+    /// The weapon the unit is using.
+    pub weapon: &'a dyn Weapon,
+
+    /// The item the unit is holding, if it matters.
+    pub item: Option<&'a dyn Item>,
+}
+
+impl<'a> Combatant<'a> {
+    /// Returns this side of combat against the given enemy, with the unit's
+    /// skills, the weapon's might multiplied if it's effective against them,
+    /// and the weapon triangle applied. The triangle is skipped in games where
+    /// the crate doesn't know it.
+    fn side_against(&self, game: FEGame, enemy: &Combatant) -> CombatSide {
+        let side = modify_side(game, self.unit, self.unit.combat_side(
+            game, self.weapon, self.item, enemy.weapon.damage_type()
+        ));
+        let might = self.weapon.might();
+        let effective = game.effective_might(
+            might, self.weapon.effective_against(), enemy.unit.unit_types()
+        );
+        let side = CombatSide{atk: side.atk + effective - might, ..side};
+        // the wielder's rank decides the triangle in games where it scales.
+        // If that isn't known, the weapon's required rank is the best guess,
+        // and character-locked weapons without one count as E
+        let rank = self.unit.weapon_rank(self.weapon.weapon_type())
+            .or_else(|| self.weapon.rank())
+            .unwrap_or(WeaponRank::E);
+        let triangle = game.weapon_triangle_bonus(self.weapon, enemy.weapon,
+                                                  rank);
+        match triangle {
+            Some(bonus) => bonus.apply(side),
+            None => side,
+        }
+    }
+}
+
+/// Computes the full report for the attacker initiating combat against the
+/// defender in the given game, as in `calc::calc`, including the procs from
+/// each side's skills.
+pub fn calc_units(game: FEGame, atk: &Combatant,
+                  def: &Combatant) -> CombatReport {
+    let atk_side = atk.side_against(game, def);
+    let def_side = def.side_against(game, atk);
+    let atk_stats = apply_skills(atk.unit,
+                                 strike_stats(game, &atk_side, &def_side));
+    let def_stats = apply_skills(def.unit,
+                                 strike_stats(game, &def_side, &atk_side));
+    let speed = game.speed_diff(&atk_side, &def_side);
+    CombatReport::new(game, atk_stats, atk_side.hp, def_stats, def_side.hp,
+                      speed)
+}
+
+/// Where combat takes place: how far apart the two units are and the terrain
+/// each is standing on.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+pub struct CombatContext {
+    /// The distance between the units, in tiles.
+    pub distance: u32,
+
+    /// The terrain the attacker is standing on, if it matters.
+    pub atk_terrain: Option<Terrain>,
+
+    /// The terrain the defender is standing on, if it matters.
+    pub def_terrain: Option<Terrain>,
+}
+
+impl CombatContext {
+    /// Combat between adjacent units, ignoring terrain.
+    pub fn melee() -> CombatContext {
+        CombatContext{distance: 1, atk_terrain: None, def_terrain: None}
+    }
+}
+
+/// Returns the side with the bonuses for the given terrain added, if the crate
+/// knows this game's terrain.
+fn with_terrain(game: FEGame, side: CombatSide,
+                terrain: Option<Terrain>) -> CombatSide {
+    match terrain.and_then(|x| game.terrain_bonus(x)) {
+        Some(bonus) => bonus.apply(side),
+        None => side,
+    }
+}
+
+/// Derives the inputs `simple_calc` needs for the attacker initiating combat
+/// against the defender in the given game and context: each side's stats,
+/// including the procs from their skills, and who doubles. The defender can't
+/// counter if their weapon doesn't reach the attacker. This assumes the
+/// attacker's weapon does reach. Terrain is ignored in games where the crate
+/// doesn't know the bonuses.
+pub fn combat_stats(game: FEGame, atk: &Combatant, def: &Combatant,
+                    context: &CombatContext) -> (CombatStats, CombatStats,
+                                                 SpeedDiff) {
+    let atk_side = with_terrain(game, atk.side_against(game, def),
+                                context.atk_terrain);
+    let def_side = with_terrain(game, def.side_against(game, atk),
+                                context.def_terrain);
+    let atk_stats = apply_skills(atk.unit,
+                                 strike_stats(game, &atk_side, &def_side));
+    let def_stats = CombatStats{
+        cannot_counter: !def.weapon.range().contains(context.distance),
+        ..apply_skills(def.unit, strike_stats(game, &def_side, &atk_side))
+    };
+    let speed = game.speed_diff(&atk_side, &def_side);
+    (atk_stats, def_stats, speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weapon::{Range, WeaponData, WeaponType};
+    use std::collections::BTreeMap;
+
+    /// Makes GBA stats from Str, Skl, Spd, Lck, Def, Res, and Con, in order.
+    fn gba_stats(values: &[u32; 7]) -> StatBlock {
+        let stats = [Stat::Str, Stat::Skl, Stat::Spd, Stat::Lck, Stat::Def,
+                     Stat::Res, Stat::Con];
+        StatBlock{values: stats.iter().cloned().zip(values.iter().cloned())
+                  .collect()}
+    }
+
+    /// A unit whose stats are just added to the weapon's.
+    struct Flat {
+        hp: u32,
+        def: u32,
+        res: u32,
+    }
+
+    impl Unit for Flat {
+        fn hp(&self) -> u32 {
+            self.hp
+        }
+
+        fn combat_side(&self, _game: FEGame, weapon: &dyn Weapon,
+                       _item: Option<&dyn Item>,
+                       enemy_damage: DamageType) -> CombatSide {
+            let prt = match enemy_damage {
+                DamageType::Physical => self.def,
+                _ => self.res,
+            };
+            CombatSide{
+                hp: self.hp, atk: weapon.might(), prt, hit: weapon.hit(),
+                crit: weapon.crit(), ..Default::default()
+            }
+        }
+    }
+
+    struct Tome;
+
+    impl Weapon for Tome {
+        fn might(&self) -> u32 { 10 }
+        fn hit(&self) -> u32 { 100 }
+        fn crit(&self) -> u32 { 0 }
+        fn weight(&self) -> u32 { 0 }
+        fn range(&self) -> Range { Range::new(1, 2) }
+        fn weapon_type(&self) -> WeaponType { WeaponType::Anima }
+    }
+
+    #[test]
+    fn test_calc_units() {
+        let mage = Flat{hp: 20, def: 2, res: 8};
+        let knight = Flat{hp: 30, def: 15, res: 0};
+        let atk = Combatant{unit: &mage, weapon: &Tome, item: None};
+        let def = Combatant{unit: &knight, weapon: &Tome, item: None};
+        // magic goes through the knight's 0 Res, not their 15 Def
+        let report = calc_units(FEGame::FE7, &atk, &def);
+        assert_eq!(report.outcomes.iter().map(|x| x.def_hp).min(), Some(20));
+    }
+
+    #[test]
+    fn test_gba_unit() {
+        let unit = GbaUnit{
+            hp: 30,
+            stats: gba_stats(&[10, 12, 11, 7, 8, 3, 9]),
+            unit_types: vec!(), class: None, weapon_ranks: BTreeMap::new(),
+        };
+        let side = unit.combat_side(FEGame::FE7, &Tome, None,
+                                    DamageType::Magical);
+        assert_eq!(side.atk, 20);
+        assert_eq!(side.prt, 3);
+        // 100 + 2 * 12 + 7 / 2
+        assert_eq!(side.hit, 127);
+        assert_eq!(side.avoid, 29);
+        assert_eq!(side.crit, 6);
+        assert_eq!(side.dodge, 7);
+    }
+
+    struct Sword;
+
+    impl Weapon for Sword {
+        fn might(&self) -> u32 { 5 }
+        fn hit(&self) -> u32 { 90 }
+        fn crit(&self) -> u32 { 0 }
+        fn weight(&self) -> u32 { 5 }
+        fn range(&self) -> Range { Range::melee() }
+        fn weapon_type(&self) -> WeaponType { WeaponType::Sword }
+    }
+
+    #[test]
+    fn test_combat_stats() {
+        let mage = GbaUnit{
+            hp: 20,
+            stats: gba_stats(&[8, 6, 10, 4, 2, 6, 5]),
+            unit_types: vec!(), class: None, weapon_ranks: BTreeMap::new(),
+        };
+        let fighter = GbaUnit{
+            hp: 30,
+            stats: gba_stats(&[10, 8, 5, 2, 5, 0, 10]),
+            unit_types: vec!(), class: None, weapon_ranks: BTreeMap::new(),
+        };
+        let atk = Combatant{unit: &mage, weapon: &Tome, item: None};
+        let def = Combatant{unit: &fighter, weapon: &Sword, item: None};
+        let (atk_stats, def_stats, speed) = combat_stats(
+            FEGame::FE7, &atk, &def, &CombatContext::melee()
+        );
+        assert_eq!(atk_stats.dmg, 18);
+        assert!(!def_stats.cannot_counter);
+        assert_eq!(def_stats.dmg, 13);
+        assert_eq!(speed, SpeedDiff::AtkDoubles);
+        // a forest adds 20 avoid and 1 to Def and Res, and swords can't reach
+        // 2 tiles
+        let context = CombatContext{
+            distance: 2, atk_terrain: None, def_terrain: Some(Terrain::Forest),
+        };
+        let (forest, def_stats, _) = combat_stats(FEGame::FE7, &atk, &def,
+                                                  &context);
+        // 114 hit against 12 avoid, which would be 102 without the forest
+        assert_eq!(atk_stats.hit, 100);
+        assert_eq!(forest.hit, 82);
+        assert_eq!(forest.dmg, 17);
+        assert!(def_stats.cannot_counter);
+    }
+
+    #[test]
+    fn test_effectiveness() {
+        let archer = GbaUnit{
+            stats: StatBlock::new(&[(Stat::Str, 6)]), ..Default::default()
+        };
+        let pegasus_knight = Class{
+            name: "Pegasus Knight".to_string(),
+            bases: StatBlock::new(&[(Stat::Spd, 5), (Stat::HP, 10)]),
+            mov: 7,
+            unit_types: vec!(UnitType::Flier),
+            weapon_ranks: vec!((WeaponType::Lance, WeaponRank::A)).into_iter()
+                .collect(),
+            max_level: 20,
+            promote_level: 10,
+            promotion_gains: StatBlock::default(),
+            caps: StatBlock::default(),
+            promotions: vec!(),
+        };
+        assert!(pegasus_knight.can_use(WeaponType::Lance, WeaponRank::C));
+        assert!(!pegasus_knight.can_use(WeaponType::Sword, WeaponRank::E));
+        // fliers are weak to bows through their class
+        let pegasus = GbaUnit{
+            hp: 20, stats: StatBlock::new(&[(Stat::Def, 4), (Stat::Spd, 3)]),
+            class: Some(pegasus_knight), ..Default::default()
+        };
+        // class bases add to the unit's own stats, but not to current HP
+        assert_eq!(pegasus.stats().spd(), 8);
+        assert_eq!(pegasus.stats().hp(), 20);
+        let bow = WeaponData{
+            might: 6, hit: 85, crit: 0, weight: 5, range: Range::new(2, 2),
+            uses: Some(45), rank: Some(WeaponRank::E),
+            weapon_type: WeaponType::Bow, damage_type: DamageType::Physical,
+            is_brave: false, reverses_triangle: false,
+            effective_against: vec!(UnitType::Flier),
+        };
+        let atk = Combatant{unit: &archer, weapon: &bow, item: None};
+        let def = Combatant{unit: &pegasus, weapon: &Sword, item: None};
+        let context = CombatContext{distance: 2, ..CombatContext::melee()};
+        // 6 Str + 3 * 6 might - 4 Def
+        let (atk_stats, _, _) = combat_stats(FEGame::FE7, &atk, &def, &context);
+        assert_eq!(atk_stats.dmg, 20);
+        // Tellius only doubles might
+        let (atk_stats, _, _) = combat_stats(FEGame::FE9, &atk, &def, &context);
+        assert_eq!(atk_stats.dmg, 14);
+    }
+
+    #[test]
+    fn test_weapon_rank() {
+        let axe = WeaponData{
+            might: 8, hit: 75, crit: 0, weight: 10, range: Range::melee(),
+            uses: Some(45), rank: Some(WeaponRank::E),
+            weapon_type: WeaponType::Axe, damage_type: DamageType::Physical,
+            is_brave: false, reverses_triangle: false,
+            effective_against: vec!(),
+        };
+        let fighter = GbaUnit{hp: 30, ..Default::default()};
+        let novice = GbaUnit{hp: 30, ..Default::default()};
+        let master = GbaUnit{
+            hp: 30,
+            weapon_ranks: vec!((WeaponType::Sword, WeaponRank::A)).into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let def = Combatant{unit: &fighter, weapon: &axe, item: None};
+        // the Tellius triangle uses the wielder's rank, not the weapon's
+        let hit = |unit: &GbaUnit| {
+            let atk = Combatant{unit, weapon: &Sword, item: None};
+            atk.side_against(FEGame::FE10, &def).hit
+        };
+        assert_eq!(hit(&novice), 90 + 5);
+        assert_eq!(hit(&master), 90 + 15);
+    }
 }
@@ -0,0 +1,235 @@
+//! Checking the crate's predictions against what the games actually do. The
+//! usual way to find out what a game does is to watch its RAM in an emulator
+//! while fighting the same battle over and over, logging every strike. This
+//! module takes those logs and compares them against a `CombatReport`,
+//! flagging anything that would be surprising if the predictions were right.
+//!
+//! Logs are read as JSON, one combat per line, so any RAM-watch script that can
+//! print JSON can feed into this.
+
+use crate::report::CombatReport;
+use crate::simple_calc::Side;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What happened on a single strike.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum StrikeResult {
+    Miss,
+    Hit,
+    Crit,
+}
+
+/// A single logged strike.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct LoggedStrike {
+    /// The side that made the strike.
+    pub striker: Side,
+
+    /// Whether the strike missed, hit, or crit.
+    pub result: StrikeResult,
+
+    /// The damage the strike did.
+    pub dmg: u32,
+}
+
+/// A single logged combat.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct LoggedCombat {
+    /// Every strike made, in order.
+    pub strikes: Vec<LoggedStrike>,
+
+    /// The attacker's HP at the end of combat.
+    pub atk_hp: u32,
+
+    /// The defender's HP at the end of combat.
+    pub def_hp: u32,
+}
+
+/// Reads logged combats written as JSON, one per line. Blank lines are
+/// skipped.
+pub fn parse_log(text: &str) -> serde_json::Result<Vec<LoggedCombat>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+/// A statistic where the logs disagree with the prediction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Discrepancy {
+    /// What was measured, like "strike 2 hit rate".
+    pub description: String,
+
+    /// The predicted probability.
+    pub expected: f64,
+
+    /// The fraction of logged combats where it happened.
+    pub observed: f64,
+
+    /// The number of standard deviations between the two. Anything that the
+    /// prediction says is impossible gets an infinite score.
+    pub z_score: f64,
+}
+
+/// The results of comparing logs against a prediction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// The number of logged combats.
+    pub combats: usize,
+
+    /// Pearson's chi-squared statistic for the final HPs across every outcome
+    /// the prediction says is possible.
+    pub chi_squared: f64,
+
+    /// The degrees of freedom for `chi_squared`: one less than the number of
+    /// possible outcomes.
+    pub degrees_of_freedom: usize,
+
+    /// Every statistic whose z-score was past the threshold, plus any
+    /// outcomes that happened despite being impossible.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+/// Returns the z-score of an observed fraction out of `n` trials against an
+/// expected probability.
+fn z_score(expected: f64, observed: f64, n: usize) -> f64 {
+    let sd = (expected * (1.0 - expected) / n as f64).sqrt();
+    if sd > 0.0 {
+        (observed - expected) / sd
+    } else if (observed - expected).abs() < 1e-12 {
+        0.0
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// Compares the logged combats against the given prediction, flagging any
+/// statistic more than `z_threshold` standard deviations off. Strikes are
+/// matched up by their position in combat, and each strike's hit and crit
+/// rates are only measured over the combats where that strike happened.
+pub fn compare(report: &CombatReport, logs: &[LoggedCombat],
+               z_threshold: f64) -> ValidationReport {
+    let mut discrepancies = vec!();
+    let mut check = |description: String, expected: f64, observed: f64,
+                     n: usize| {
+        let z_score = z_score(expected, observed, n);
+        if z_score.abs() > z_threshold {
+            discrepancies.push(Discrepancy{
+                description,
+                expected,
+                observed,
+                z_score,
+            });
+        }
+    };
+
+    for row in report.strikes.iter() {
+        let happened: Vec<&LoggedStrike> = logs.iter()
+            .filter_map(|x| x.strikes.get(row.index))
+            .collect();
+        let n = happened.len();
+        if n == 0 {
+            continue;
+        }
+        let fraction = |pred: &dyn Fn(&LoggedStrike) -> bool| {
+            happened.iter().filter(|x| pred(x)).count() as f64 / n as f64
+        };
+        check(format!("strike {} striker", row.index + 1), 1.0,
+              fraction(&|x| x.striker == row.striker), n);
+        check(format!("strike {} hit rate", row.index + 1), row.true_hit,
+              fraction(&|x| x.result != StrikeResult::Miss), n);
+        check(format!("strike {} crit rate", row.index + 1), row.true_crit,
+              fraction(&|x| x.result == StrikeResult::Crit), n);
+    }
+
+    // compare the final HPs against the predicted outcomes
+    let mut observed: BTreeMap<(u32, u32), usize> = BTreeMap::new();
+    for log in logs {
+        *observed.entry((log.atk_hp, log.def_hp)).or_insert(0) += 1;
+    }
+    let n = logs.len();
+    let mut chi_squared = 0.0;
+    for outcome in report.outcomes.iter() {
+        let expected = outcome.prob * n as f64;
+        let count = observed.remove(&(outcome.atk_hp, outcome.def_hp))
+            .unwrap_or(0);
+        if expected > 0.0 {
+            chi_squared += (count as f64 - expected).powi(2) / expected;
+        }
+    }
+    // anything left over can't happen according to the prediction
+    for ((atk_hp, def_hp), count) in observed {
+        check(format!("outcome with {} attacker HP and {} defender HP",
+                      atk_hp, def_hp),
+              0.0, count as f64 / n as f64, n);
+    }
+
+    ValidationReport{
+        combats: n,
+        chi_squared,
+        degrees_of_freedom: report.outcomes.len().saturating_sub(1),
+        discrepancies,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fegame::FEGame;
+    use crate::simple_calc::{CombatStats, SpeedDiff};
+
+    fn strike(striker: Side, result: StrikeResult, dmg: u32) -> LoggedStrike {
+        LoggedStrike{striker, result, dmg}
+    }
+
+    #[test]
+    fn test_matching_logs() {
+        // a 1RN game with an even split makes exact logs easy to write
+        let atk = CombatStats{dmg: 10, hit: 50, crit: 0, ..Default::default()};
+        let def = CombatStats{dmg: 10, hit: 0, crit: 0, ..Default::default()};
+        let report = CombatReport::new(FEGame::FE1, atk, 20, def, 30,
+                                       SpeedDiff::Even);
+        let hit = LoggedCombat{
+            strikes: vec![strike(Side::Atk, StrikeResult::Hit, 10),
+                          strike(Side::Def, StrikeResult::Miss, 0)],
+            atk_hp: 20,
+            def_hp: 20,
+        };
+        let miss = LoggedCombat{
+            strikes: vec![strike(Side::Atk, StrikeResult::Miss, 0),
+                          strike(Side::Def, StrikeResult::Miss, 0)],
+            atk_hp: 20,
+            def_hp: 30,
+        };
+        let logs: Vec<LoggedCombat> = (0..50).flat_map(|_| {
+            vec![hit.clone(), miss.clone()]
+        }).collect();
+
+        // the logs round-trip through JSON
+        let text: Vec<String> = logs.iter()
+            .map(|x| serde_json::to_string(x).unwrap())
+            .collect();
+        assert_eq!(parse_log(&text.join("\n")).unwrap(), logs);
+
+        let validation = compare(&report, &logs, 3.0);
+        assert_eq!(validation.combats, 100);
+        assert_eq!(validation.discrepancies, vec!());
+        assert!(validation.chi_squared.abs() < 1e-9);
+
+        // a defender that somehow hits gets flagged twice: once for the hit
+        // rate and once for the impossible outcome
+        let mut wrong = logs;
+        wrong[0] = LoggedCombat{
+            strikes: vec![strike(Side::Atk, StrikeResult::Hit, 10),
+                          strike(Side::Def, StrikeResult::Hit, 10)],
+            atk_hp: 10,
+            def_hp: 20,
+        };
+        let validation = compare(&report, &wrong, 3.0);
+        assert_eq!(validation.discrepancies.len(), 2);
+        assert_eq!(validation.discrepancies[0].description, "strike 2 hit rate");
+        assert!(validation.discrepancies[1].z_score.is_infinite());
+    }
+}
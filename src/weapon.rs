@@ -1,14 +1,288 @@
 //! This file defines a unified way of dealing with different weapons and held
 //! items.
 
-use crate::unit::Unit;
+use crate::damage::DamageType;
+use crate::fegame::CritAvoid;
+use crate::unit::UnitType;
 
-/// A weapon that is used to attack among units of type U.
-pub trait Weapon<U> where U: Unit {
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
 
+/// A weapon a unit can attack with. The stats are the weapon's own, before
+/// the wielder's stats are added.
+pub trait Weapon {
+    /// The weapon's might.
+    fn might(&self) -> u32;
+
+    /// The weapon's hit rate.
+    fn hit(&self) -> u32;
+
+    /// The weapon's crit rate.
+    fn crit(&self) -> u32;
+
+    /// The weapon's weight.
+    fn weight(&self) -> u32;
+
+    /// The distances the weapon can attack at.
+    fn range(&self) -> Range;
+
+    /// The kind of weapon, for the weapon triangle.
+    fn weapon_type(&self) -> WeaponType;
+
+    /// Which stats the weapon's damage uses. By default, tomes and staves are
+    /// magical and everything else is physical.
+    fn damage_type(&self) -> DamageType {
+        match self.weapon_type() {
+            WeaponType::Anima | WeaponType::Light | WeaponType::Dark
+                | WeaponType::Staff => DamageType::Magical,
+            _ => DamageType::Physical,
+        }
+    }
+
+    /// Whether the weapon strikes twice per attack.
+    fn is_brave(&self) -> bool {
+        false
+    }
+
+    /// Whether the weapon reverses the weapon triangle, like a Lancereaver.
+    fn reverses_triangle(&self) -> bool {
+        false
+    }
+
+    /// The rank needed to use the weapon, or `None` for weapons locked to
+    /// specific characters or if it isn't known.
+    fn rank(&self) -> Option<WeaponRank> {
+        None
+    }
+
+    /// The kinds of units the weapon is effective against.
+    fn effective_against(&self) -> &[UnitType] {
+        &[]
+    }
+
+    /// How many uses the weapon has left, or `None` if it doesn't break.
+    fn uses(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// A held item that affects combat, like a shield that blocks crits.
+pub trait Item {
+    /// The crit avoid the item gives its holder, if any.
+    fn crit_avoid(&self) -> Option<CritAvoid> {
+        None
+    }
+}
+
+/// The kinds of weapons that matter for the weapon triangle. Games that split
+/// magic into more schools, like *Radiant Dawn*'s fire, wind, and thunder,
+/// count them all as anima, and *Fates*' shuriken count as daggers.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display,
+         EnumString, EnumIter, Serialize, Deserialize)]
+pub enum WeaponType {
+    Sword,
+    Lance,
+    Axe,
+    Bow,
+    Dagger,
+    Anima,
+    Light,
+    Dark,
+    Staff,
+}
+
+/// A unit's skill with a type of weapon, from lowest to highest.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Display,
+         EnumString, EnumIter, Serialize, Deserialize)]
+pub enum WeaponRank {
+    E,
+    D,
+    C,
+    B,
+    A,
+    S,
+}
+
+/// The distances, in tiles, a weapon can attack at. Most melee weapons are
+/// 1-1, most bows are 2-2, and hand axes and javelins are 1-2. Some games give
+/// bows 2-3 instead.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct Range {
+    /// The shortest distance the weapon can attack at.
+    pub min: u32,
+
+    /// The longest distance the weapon can attack at.
+    pub max: u32,
+}
+
+impl Range {
+    /// Creates a range from `min` to `max` tiles, inclusive.
+    pub fn new(min: u32, max: u32) -> Range {
+        Range{min, max}
+    }
+
+    /// A weapon that can only attack adjacent units.
+    pub fn melee() -> Range {
+        Range::new(1, 1)
+    }
+
+    /// Returns whether the weapon can attack at the given distance.
+    pub fn contains(&self, distance: u32) -> bool {
+        self.min <= distance && distance <= self.max
+    }
+}
+
+/// Skills that let a unit counter at ranges their weapon normally can't reach.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub enum CounterSkill {
+    /// Lets a unit counter adjacent attackers with a weapon that can't normally
+    /// attack at 1 range, like a bow.
+    CloseCounter,
+
+    /// Lets a unit counter attackers at any distance.
+    DistantCounter,
+}
+
+impl CounterSkill {
+    /// Returns the range a unit with this skill can counter at, given the
+    /// range of their weapon.
+    pub fn counter_range(&self, weapon: Range) -> Range {
+        match self {
+            CounterSkill::CloseCounter => Range::new(1, weapon.max),
+            CounterSkill::DistantCounter => Range::new(1, u32::MAX),
+        }
+    }
+}
+
+/// A weapon's stats, as listed in the game's data.
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+pub struct WeaponData {
+    /// The weapon's might.
+    pub might: u32,
+
+    /// The weapon's hit rate.
+    pub hit: u32,
+
+    /// The weapon's crit rate.
+    pub crit: u32,
+
+    /// The weapon's weight.
+    pub weight: u32,
+
+    /// The distances the weapon can attack at.
+    pub range: Range,
+
+    /// How many times the weapon can be used, or `None` if it doesn't break.
+    pub uses: Option<u32>,
+
+    /// The rank needed to use the weapon, or `None` for weapons locked to
+    /// specific characters.
+    pub rank: Option<WeaponRank>,
+
+    /// The kind of weapon.
+    pub weapon_type: WeaponType,
+
+    /// Which stats the weapon's damage uses, which doesn't always follow from
+    /// its type, like a Levin Sword's magic damage.
+    pub damage_type: DamageType,
+
+    /// Whether the weapon strikes twice per attack.
+    pub is_brave: bool,
+
+    /// Whether the weapon reverses the weapon triangle, like a Lancereaver.
+    pub reverses_triangle: bool,
+
+    /// The kinds of units the weapon is effective against.
+    pub effective_against: Vec<UnitType>,
 }
 
-/// A held item that affects combat among units of type U.
-pub trait Item<U> where U: Unit {
+impl Weapon for WeaponData {
+    fn might(&self) -> u32 {
+        self.might
+    }
+
+    fn hit(&self) -> u32 {
+        self.hit
+    }
+
+    fn crit(&self) -> u32 {
+        self.crit
+    }
+
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    fn range(&self) -> Range {
+        self.range
+    }
+
+    fn weapon_type(&self) -> WeaponType {
+        self.weapon_type
+    }
+
+    fn damage_type(&self) -> DamageType {
+        self.damage_type
+    }
+
+    fn is_brave(&self) -> bool {
+        self.is_brave
+    }
+
+    fn reverses_triangle(&self) -> bool {
+        self.reverses_triangle
+    }
+
+    fn rank(&self) -> Option<WeaponRank> {
+        self.rank
+    }
+
+    fn effective_against(&self) -> &[UnitType] {
+        &self.effective_against
+    }
+
+    fn uses(&self) -> Option<u32> {
+        self.uses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range() {
+        assert!(Range::melee().contains(1));
+        assert!(!Range::melee().contains(2));
+        let longbow = Range::new(2, 3);
+        assert!(!longbow.contains(1));
+        assert!(longbow.contains(3));
+        let close = CounterSkill::CloseCounter.counter_range(longbow);
+        assert!(close.contains(1) && close.contains(3) && !close.contains(4));
+        assert!(CounterSkill::DistantCounter.counter_range(Range::melee())
+                .contains(5));
+    }
 
+    #[test]
+    fn test_weapon_data() {
+        let steel_bow = WeaponData{
+            might: 9, hit: 70, crit: 0, weight: 9, range: Range::new(2, 2),
+            uses: Some(30), rank: Some(WeaponRank::D),
+            weapon_type: WeaponType::Bow, damage_type: DamageType::Physical,
+            is_brave: false, reverses_triangle: false,
+            effective_against: vec!(UnitType::Flier),
+        };
+        let weapon: &dyn Weapon = &steel_bow;
+        assert_eq!(weapon.damage_type(), DamageType::Physical);
+        assert_eq!(weapon.effective_against(), &[UnitType::Flier]);
+        assert!(!weapon.range().contains(1));
+        let levin_sword = WeaponData{
+            might: 10, hit: 70, crit: 0, weight: 9, range: Range::new(1, 2),
+            uses: Some(25), rank: Some(WeaponRank::B),
+            weapon_type: WeaponType::Sword, damage_type: DamageType::Magical,
+            is_brave: false, reverses_triangle: false,
+            effective_against: vec!(),
+        };
+        assert_eq!(levin_sword.damage_type(), DamageType::Magical);
+    }
 }